@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use serde::Serialize;
+use crate::node_manager::{NodeStatus, ResourceUsage};
+use crate::node_lifecycle::NodePhase;
+use crate::state_manager::StateManager;
+use crate::logger::log_warn;
+
+// Totals are written to node_state.json this often, so a crash loses at most this much
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+// A longer gap between ticks means the app was suspended; that time isn't counted
+const MAX_TICK_GAP: Duration = Duration::from_secs(30);
+// Only the p2p service's traffic is relayed chain data; the rest is local RPC and
+// inter-service messaging
+const RELAY_SERVICE: &str = "p2p";
+const BYTES_PER_GB: f64 = 1_000_000_000.0;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LifetimeStats {
+    pub uptime_seconds: u64,
+    pub blocks_validated: u64,
+    pub data_relayed_gb: f64,
+    pub install_date: String,
+    pub first_sync_completed: bool,
+}
+
+/// From the node entering an active phase until it stopped
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionStats {
+    pub started_at: String,
+    /// None while the session is ongoing
+    pub ended_at: Option<String>,
+    pub uptime_seconds: u64,
+    pub blocks_validated: u64,
+    pub data_relayed_gb: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountingReport {
+    pub lifetime: LifetimeStats,
+    pub session: Option<SessionStats>,
+}
+
+#[derive(Default)]
+struct Pending {
+    uptime: Duration,
+    blocks: u64,
+    relayed_bytes: u64,
+}
+
+struct Session {
+    started_at: String,
+    ended_at: Option<String>,
+    uptime: Duration,
+    blocks: u64,
+    relayed_bytes: u64,
+}
+
+struct Inner {
+    session: Option<Session>,
+    pending: Pending,
+    last_tick: Option<Instant>,
+    last_flush: Instant,
+    last_height: Option<u64>,
+    // Cumulative (rx + tx) per container, to turn the counters into deltas
+    last_net: HashMap<String, u64>,
+}
+
+/// Drives the lifetime counters in `NodeState` from the status loop: uptime while
+/// the node is syncing or running, blocks from head height deltas and relayed data
+/// from the p2p container's network counters. Deltas are buffered and flushed
+/// periodically.
+#[derive(Clone)]
+pub struct Accounting {
+    state_manager: Arc<Mutex<StateManager>>,
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Accounting {
+    pub fn new(state_manager: Arc<Mutex<StateManager>>) -> Self {
+        Self {
+            state_manager,
+            inner: Arc::new(Mutex::new(Inner {
+                session: None,
+                pending: Pending::default(),
+                last_tick: None,
+                last_flush: Instant::now(),
+                last_height: None,
+                last_net: HashMap::new(),
+            })),
+        }
+    }
+
+    /// Account for one tick of the status loop
+    pub fn tick(&self, phase: NodePhase, status: &NodeStatus, usage: Option<&ResourceUsage>) {
+        let now = Instant::now();
+        let mut inner = self.inner.lock().unwrap();
+        let elapsed = inner.last_tick.map(|t| now.duration_since(t));
+        inner.last_tick = Some(now);
+
+        if !matches!(phase, NodePhase::Syncing | NodePhase::Running) {
+            if let Some(session) = inner.session.as_mut().filter(|s| s.ended_at.is_none()) {
+                session.ended_at = Some(chrono::Local::now().to_rfc3339());
+            }
+            inner.last_height = None;
+            inner.last_net.clear();
+            drop(inner);
+            self.flush_if(|pending| pending.uptime.as_secs() > 0 || pending.blocks > 0 || pending.relayed_bytes > 0);
+            return;
+        }
+
+        if inner.session.as_ref().is_none_or(|s| s.ended_at.is_some()) {
+            inner.session = Some(Session {
+                started_at: chrono::Local::now().to_rfc3339(),
+                ended_at: None,
+                uptime: Duration::ZERO,
+                blocks: 0,
+                relayed_bytes: 0,
+            });
+        }
+
+        let uptime = elapsed.filter(|e| *e <= MAX_TICK_GAP).unwrap_or_default();
+
+        // The head only moves forward by applying blocks; a lower height means a reset
+        let height = status.current_block;
+        let blocks = match inner.last_height {
+            Some(last) if height > last => height - last,
+            _ => 0,
+        };
+        if height > 0 {
+            inner.last_height = Some(height);
+        }
+
+        let mut relayed = 0;
+        for service in usage.map(|u| u.services.as_slice()).unwrap_or_default() {
+            if service.service != RELAY_SERVICE {
+                continue;
+            }
+            let (Some(rx), Some(tx)) = (service.net_rx_bytes, service.net_tx_bytes) else {
+                continue;
+            };
+            let total = rx + tx;
+            // Counters restart with the container
+            relayed += match inner.last_net.insert(service.service.clone(), total) {
+                Some(last) if total >= last => total - last,
+                Some(_) => total,
+                None => 0,
+            };
+        }
+
+        inner.pending.uptime += uptime;
+        inner.pending.blocks += blocks;
+        inner.pending.relayed_bytes += relayed;
+        let session = inner.session.as_mut().unwrap();
+        session.uptime += uptime;
+        session.blocks += blocks;
+        session.relayed_bytes += relayed;
+
+        let due = now.duration_since(inner.last_flush) >= FLUSH_INTERVAL;
+        drop(inner);
+        if due {
+            self.flush_if(|_| true);
+        }
+    }
+
+    // Write buffered deltas to node_state.json
+    fn flush_if(&self, condition: impl Fn(&Pending) -> bool) {
+        let mut inner = self.inner.lock().unwrap();
+        if !condition(&inner.pending) {
+            return;
+        }
+        let pending = std::mem::take(&mut inner.pending);
+        inner.last_flush = Instant::now();
+        drop(inner);
+
+        {
+            let mut state_manager = self.state_manager.lock().unwrap();
+            state_manager.increment_uptime(pending.uptime.as_secs());
+            state_manager.increment_blocks_validated(pending.blocks);
+            state_manager.add_data_relayed((pending.relayed_bytes as f64 / BYTES_PER_GB) as f32);
+            if let Err(e) = state_manager.save() {
+                log_warn("Failed to save node totals", Some(&e.to_string()));
+            }
+        }
+        // Sub-second remainders are carried over rather than dropped
+        let remainder = pending.uptime - Duration::from_secs(pending.uptime.as_secs());
+        self.inner.lock().unwrap().pending.uptime += remainder;
+    }
+
+    pub fn report(&self) -> AccountingReport {
+        let inner = self.inner.lock().unwrap();
+        let state_manager = self.state_manager.lock().unwrap();
+        let state = state_manager.get_state();
+
+        AccountingReport {
+            lifetime: LifetimeStats {
+                uptime_seconds: state.total_uptime_seconds + inner.pending.uptime.as_secs(),
+                blocks_validated: state.blocks_validated + inner.pending.blocks,
+                data_relayed_gb: state.data_relayed_gb as f64 + inner.pending.relayed_bytes as f64 / BYTES_PER_GB,
+                install_date: state.install_date.clone(),
+                first_sync_completed: state.first_sync_completed,
+            },
+            session: inner.session.as_ref().map(|s| SessionStats {
+                started_at: s.started_at.clone(),
+                ended_at: s.ended_at.clone(),
+                uptime_seconds: s.uptime.as_secs(),
+                blocks_validated: s.blocks,
+                data_relayed_gb: s.relayed_bytes as f64 / BYTES_PER_GB,
+            }),
+        }
+    }
+}
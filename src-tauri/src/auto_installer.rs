@@ -1,12 +1,13 @@
 use std::process::Command;
 use tokio::process::Command as AsyncCommand;
 use crate::logger::{log_debug, log_info, log_warn, log_error};
+use crate::error::NodeError;
 
 pub struct AutoInstaller;
 
 impl AutoInstaller {
     /// Automatically install all requirements
-    pub async fn install_all_requirements() -> Result<String, String> {
+    pub async fn install_all_requirements() -> Result<String, NodeError> {
         log_info("Starting automatic requirements installation", None);
         let mut installed_items = Vec::new();
         
@@ -54,7 +55,7 @@ impl AutoInstaller {
         {
             // Check for Docker on Windows
             if !Self::is_docker_installed() {
-                return Err("Docker Desktop must be installed manually on Windows. Please download from docker.com".to_string());
+                return Err(NodeError::DockerMissing("Docker Desktop must be installed manually on Windows. Please download from docker.com".to_string()));
             }
         }
         
@@ -126,7 +127,7 @@ impl AutoInstaller {
             .unwrap_or(false)
     }
     
-    async fn install_homebrew() -> Result<(), String> {
+    async fn install_homebrew() -> Result<(), NodeError> {
         // Double-check if Homebrew is already installed
         if Self::is_homebrew_installed() {
             println!("Homebrew is already installed!");
@@ -147,20 +148,20 @@ end tell
             .arg(applescript)
             .output()
             .await
-            .map_err(|e| format!("Failed to open Terminal for Homebrew installation: {}", e))?;
+            .map_err(|e| NodeError::from(e).context("Failed to open Terminal for Homebrew installation"))?;
         
         if !output.status.success() {
             // If Terminal approach fails, provide manual instructions
-            return Err("Please install Homebrew manually:\n1. Open Terminal\n2. Run: /bin/bash -c \"$(curl -fsSL https://raw.githubusercontent.com/Homebrew/install/HEAD/install.sh)\"\n3. Then click 'Check Again'".to_string());
+            return Err(NodeError::DockerMissing("Please install Homebrew manually:\n1. Open Terminal\n2. Run: /bin/bash -c \"$(curl -fsSL https://raw.githubusercontent.com/Homebrew/install/HEAD/install.sh)\"\n3. Then click 'Check Again'".to_string()));
         }
         
         // Wait for user to complete installation
         // Since we opened Terminal, we need to give the user time to complete it
         // Return a message asking them to wait
-        Err("Homebrew installation started in Terminal. Please:\n1. Complete the installation in Terminal\n2. Enter your password when prompted\n3. Wait for it to finish\n4. Click 'Check Again' to continue".to_string())
+        Err(NodeError::Other("Homebrew installation started in Terminal. Please:\n1. Complete the installation in Terminal\n2. Enter your password when prompted\n3. Wait for it to finish\n4. Click 'Check Again' to continue".to_string()))
     }
     
-    async fn install_docker_mac() -> Result<(), String> {
+    async fn install_docker_mac() -> Result<(), NodeError> {
         // First ensure Homebrew is available
         if !Self::is_homebrew_installed() {
             Self::install_homebrew().await?;
@@ -181,7 +182,7 @@ end tell
             .args(&["install", "--cask", "docker"])
             .output()
             .await
-            .map_err(|e| format!("Failed to install Docker: {}", e))?;
+            .map_err(|e| NodeError::from(e).context("Failed to install Docker"))?;
         
         if !output.status.success() {
             let error = String::from_utf8_lossy(&output.stderr);
@@ -197,14 +198,14 @@ end tell
                     .args(&["-f", "brew install --cask docker"])
                     .output();
                 
-                return Err("Another installation was in progress. It has been stopped.\nClick 'Check Again' to retry.".to_string());
+                return Err(NodeError::Other("Another installation was in progress. It has been stopped.\nClick 'Check Again' to retry.".to_string()));
             }
             
             if error.contains("sudo") || error.contains("password") {
-                return Err("Docker installation needs admin privileges.\nClick 'Check Again' and enter your password when prompted.".to_string());
+                return Err(NodeError::Process("Docker installation needs admin privileges.\nClick 'Check Again' and enter your password when prompted.".to_string()));
             }
             
-            return Err(format!("Docker installation failed: {}", error));
+            return Err(NodeError::Process(format!("Docker installation failed: {}", error)));
         }
         
         // Wait a moment for installation to complete
@@ -213,12 +214,12 @@ end tell
         Ok(())
     }
     
-    async fn start_docker_mac() -> Result<(), String> {
+    async fn start_docker_mac() -> Result<(), NodeError> {
         // Open Docker Desktop
         Command::new("open")
             .arg("/Applications/Docker.app")
             .spawn()
-            .map_err(|e| format!("Failed to open Docker: {}", e))?;
+            .map_err(|e| NodeError::from(e).context("Failed to open Docker"))?;
         
         // Wait for Docker to start (check every 2 seconds for up to 30 seconds)
         for _ in 0..15 {
@@ -232,7 +233,7 @@ end tell
         Ok(())
     }
     
-    async fn install_docker_linux() -> Result<(), String> {
+    async fn install_docker_linux() -> Result<(), NodeError> {
         // Use the official Docker installation script
         let install_script = r#"
             curl -fsSL https://get.docker.com -o get-docker.sh
@@ -246,10 +247,10 @@ end tell
             .arg(install_script)
             .output()
             .await
-            .map_err(|e| format!("Failed to install Docker: {}", e))?;
+            .map_err(|e| NodeError::from(e).context("Failed to install Docker"))?;
         
         if !output.status.success() {
-            return Err("Docker installation failed".to_string());
+            return Err(NodeError::Process("Docker installation failed".to_string()));
         }
         
         Ok(())
@@ -0,0 +1,373 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use futures_util::future::join_all;
+use serde::Serialize;
+use crate::koinos_rpc::{HeadInfo, KoinosRpc, RpcError};
+use crate::logger::{log_debug, log_info, log_warn};
+
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+// Head unchanged for this long while the node runs counts as stalled
+const STALL_AFTER: Duration = Duration::from_secs(600);
+
+/// Emitted as `consistency_alert` when first raised
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ConsistencyAlert {
+    /// The local node has a different block at an irreversible height than a reference
+    Fork {
+        endpoint: String,
+        height: u64,
+        local_block_id: String,
+        reference_block_id: String,
+    },
+    /// The local head hasn't moved for a while
+    Stalled {
+        height: u64,
+        stalled_seconds: u64,
+    },
+}
+
+impl ConsistencyAlert {
+    // Identifies the condition, so an alert that persists is only raised once
+    fn key(&self) -> String {
+        match self {
+            ConsistencyAlert::Fork { endpoint, .. } => format!("fork:{}", endpoint),
+            ConsistencyAlert::Stalled { height, .. } => format!("stalled:{}", height),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            ConsistencyAlert::Fork { endpoint, height, local_block_id, reference_block_id } => format!(
+                "Block {} is {} locally but {} on {}",
+                height, local_block_id, reference_block_id, endpoint
+            ),
+            ConsistencyAlert::Stalled { height, stalled_seconds } => {
+                format!("Head stuck at block {} for {}s", height, stalled_seconds)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReferenceCheck {
+    pub endpoint: String,
+    pub head_height: Option<u64>,
+    /// Irreversible height both sides were compared at
+    pub compared_height: Option<u64>,
+    pub matches: Option<bool>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ConsistencyReport {
+    pub checked_at: Option<String>,
+    pub local_height: Option<u64>,
+    pub references: Vec<ReferenceCheck>,
+    pub alerts: Vec<ConsistencyAlert>,
+}
+
+struct Inner {
+    report: ConsistencyReport,
+    last_head: Option<String>,
+    head_since: Instant,
+}
+
+/// Periodically compares the local chain with reference endpoints to catch a node
+/// that ended up on another fork or stopped applying blocks.
+#[derive(Clone)]
+pub struct ConsistencyChecker {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl ConsistencyChecker {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                report: ConsistencyReport::default(),
+                last_head: None,
+                head_since: Instant::now(),
+            })),
+        }
+    }
+
+    pub fn report(&self) -> ConsistencyReport {
+        self.inner.lock().unwrap().report.clone()
+    }
+
+    /// Call when the node isn't running, so stopped time isn't counted as a stall
+    pub fn reset(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.last_head = None;
+        inner.report.alerts.clear();
+    }
+
+    /// Run one check and return the alerts that weren't raised before
+    pub async fn check(&self, local: &KoinosRpc, references: &[String]) -> Vec<ConsistencyAlert> {
+        let head = match local.head_info().await {
+            Ok(head) => head,
+            Err(e) => {
+                log_debug("Consistency check skipped, local node unavailable", Some(&e.to_string()));
+                return Vec::new();
+            }
+        };
+
+        let mut alerts = Vec::new();
+        if let Some(alert) = self.check_stall(&head) {
+            alerts.push(alert);
+        }
+
+        let comparisons =
+            join_all(references.iter().map(|endpoint| compare(local, &head, endpoint))).await;
+        let mut references = Vec::new();
+        for Comparison { check, fork } in comparisons {
+            if let Some(error) = &check.error {
+                log_debug("Reference endpoint check failed", Some(&format!("{}: {}", check.endpoint, error)));
+            }
+            alerts.extend(fork);
+            references.push(check);
+        }
+
+        let mut inner = self.inner.lock().unwrap();
+        let raised: Vec<ConsistencyAlert> = alerts
+            .iter()
+            .filter(|a| !inner.report.alerts.iter().any(|old| old.key() == a.key()))
+            .cloned()
+            .collect();
+        for alert in &raised {
+            log_warn("Consistency alert", Some(&alert.describe()));
+        }
+        if alerts.is_empty() && !inner.report.alerts.is_empty() {
+            log_info("Consistency alerts cleared", None);
+        }
+        inner.report = ConsistencyReport {
+            checked_at: Some(chrono::Local::now().to_rfc3339()),
+            local_height: Some(head.head_topology.height),
+            references,
+            alerts,
+        };
+        raised
+    }
+
+    fn check_stall(&self, head: &HeadInfo) -> Option<ConsistencyAlert> {
+        self.check_stall_at(head, Instant::now())
+    }
+
+    fn check_stall_at(&self, head: &HeadInfo, now: Instant) -> Option<ConsistencyAlert> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.last_head.as_deref() != Some(head.head_topology.id.as_str()) {
+            inner.last_head = Some(head.head_topology.id.clone());
+            inner.head_since = now;
+            return None;
+        }
+        let stalled = now.saturating_duration_since(inner.head_since);
+        (stalled >= STALL_AFTER).then_some(ConsistencyAlert::Stalled {
+            height: head.head_topology.height,
+            stalled_seconds: stalled.as_secs(),
+        })
+    }
+}
+
+struct Comparison {
+    check: ReferenceCheck,
+    fork: Option<ConsistencyAlert>,
+}
+
+// Compare at the highest block both sides consider irreversible, which must be
+// identical on any node following the same chain
+async fn compare(local: &KoinosRpc, local_head: &HeadInfo, endpoint: &str) -> Comparison {
+    let mut check = ReferenceCheck {
+        endpoint: endpoint.to_string(),
+        head_height: None,
+        compared_height: None,
+        matches: None,
+        error: None,
+    };
+    let reference = KoinosRpc::new(endpoint).with_timeout(Duration::from_secs(10));
+
+    let result: Result<Option<ConsistencyAlert>, RpcError> = async {
+        let reference_head = reference.head_info().await?;
+        check.head_height = Some(reference_head.head_topology.height);
+
+        let height = local_head
+            .last_irreversible_block
+            .min(reference_head.last_irreversible_block);
+        if height == 0 {
+            return Ok(None);
+        }
+        check.compared_height = Some(height);
+
+        let local_id = block_id_at(local, &local_head.head_topology.id, height).await?;
+        let reference_id = block_id_at(&reference, &reference_head.head_topology.id, height).await?;
+        let matches = local_id == reference_id;
+        check.matches = Some(matches);
+
+        Ok((!matches).then(|| ConsistencyAlert::Fork {
+            endpoint: endpoint.to_string(),
+            height,
+            local_block_id: local_id,
+            reference_block_id: reference_id,
+        }))
+    }
+    .await;
+
+    match result {
+        Ok(fork) => Comparison { check, fork },
+        Err(e) => {
+            check.error = Some(e.to_string());
+            Comparison { check, fork: None }
+        }
+    }
+}
+
+async fn block_id_at(rpc: &KoinosRpc, head_id: &str, height: u64) -> Result<String, RpcError> {
+    rpc.blocks_by_height(head_id, height, 1, false)
+        .await?
+        .into_iter()
+        .next()
+        .map(|item| item.block_id)
+        .ok_or_else(|| RpcError::Decode(format!("no block at height {}", height)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::koinos_rpc::BlockTopology;
+    use serde_json::{json, Value};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn head(id: &str, height: u64) -> HeadInfo {
+        HeadInfo {
+            head_topology: BlockTopology { id: id.to_string(), height, ..Default::default() },
+            last_irreversible_block: 0,
+            head_block_time: 0,
+            head_state_merkle_root: String::new(),
+        }
+    }
+
+    #[test]
+    fn stall_needs_an_unchanged_head_for_the_whole_period() {
+        let checker = ConsistencyChecker::new();
+        let start = Instant::now();
+
+        assert!(checker.check_stall_at(&head("0xa", 100), start).is_none());
+        let almost = start + STALL_AFTER - Duration::from_secs(1);
+        assert!(checker.check_stall_at(&head("0xa", 100), almost).is_none());
+
+        match checker.check_stall_at(&head("0xa", 100), start + STALL_AFTER) {
+            Some(ConsistencyAlert::Stalled { height, stalled_seconds }) => {
+                assert_eq!(height, 100);
+                assert_eq!(stalled_seconds, STALL_AFTER.as_secs());
+            }
+            other => panic!("expected a stall, got {:?}", other),
+        }
+
+        // A new head clears it and restarts the clock
+        let moved = start + STALL_AFTER + Duration::from_secs(1);
+        assert!(checker.check_stall_at(&head("0xb", 101), moved).is_none());
+        assert!(checker.check_stall_at(&head("0xb", 101), moved + STALL_AFTER / 2).is_none());
+    }
+
+    fn block_id(height: u64, fork_at: Option<u64>) -> String {
+        match fork_at {
+            Some(fork) if height >= fork => format!("0xb{}", height),
+            _ => format!("0xa{}", height),
+        }
+    }
+
+    fn answer(request: &Value, height: u64, irreversible: u64, fork_at: Option<u64>) -> Value {
+        let result = match request["method"].as_str() {
+            Some("chain.get_head_info") => json!({
+                "head_topology": { "id": block_id(height, fork_at), "height": height.to_string() },
+                "last_irreversible_block": irreversible.to_string(),
+                "head_block_time": "0"
+            }),
+            Some("block_store.get_blocks_by_height") => {
+                let start: u64 = request["params"]["ancestor_start_height"].as_str().unwrap().parse().unwrap();
+                json!({ "block_items": [{ "block_id": block_id(start, fork_at), "block_height": start.to_string() }] })
+            }
+            _ => return json!({ "jsonrpc": "2.0", "id": request["id"], "error": { "code": -32601, "message": "unknown method" } }),
+        };
+        json!({ "jsonrpc": "2.0", "id": request["id"], "result": result })
+    }
+
+    // JSON-RPC endpoint serving a chain of `height` blocks whose ids differ from
+    // `fork_at` on
+    async fn mock_node(height: u64, irreversible: u64, fork_at: Option<u64>) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut buffer = Vec::new();
+                    let mut chunk = [0u8; 4096];
+                    let body = loop {
+                        let read = socket.read(&mut chunk).await.unwrap_or(0);
+                        if read == 0 {
+                            return;
+                        }
+                        buffer.extend_from_slice(&chunk[..read]);
+                        let text = String::from_utf8_lossy(&buffer);
+                        if let Some(end) = text.find("\r\n\r\n") {
+                            let length = text[..end]
+                                .lines()
+                                .find_map(|line| {
+                                    let (name, value) = line.split_once(':')?;
+                                    name.eq_ignore_ascii_case("content-length").then(|| value.trim().parse().ok())?
+                                })
+                                .unwrap_or(0);
+                            if buffer.len() >= end + 4 + length {
+                                break buffer[end + 4..end + 4 + length].to_vec();
+                            }
+                        }
+                    };
+                    let request: Value = serde_json::from_slice(&body).unwrap();
+                    let reply = answer(&request, height, irreversible, fork_at).to_string();
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        reply.len(),
+                        reply
+                    );
+                    socket.write_all(response.as_bytes()).await.ok();
+                });
+            }
+        });
+        format!("http://{}", address)
+    }
+
+    #[tokio::test]
+    async fn same_chain_matches_at_the_common_irreversible_height() {
+        let local = KoinosRpc::new(&mock_node(1000, 900, None).await);
+        let reference = mock_node(1200, 1100, None).await;
+
+        let comparison = compare(&local, &local.head_info().await.unwrap(), &reference).await;
+        assert!(comparison.fork.is_none());
+        assert_eq!(comparison.check.head_height, Some(1200));
+        assert_eq!(comparison.check.compared_height, Some(900));
+        assert_eq!(comparison.check.matches, Some(true));
+        assert_eq!(comparison.check.error, None);
+    }
+
+    #[tokio::test]
+    async fn fork_is_raised_once() {
+        let local = KoinosRpc::new(&mock_node(1000, 900, Some(850)).await);
+        let reference = mock_node(1000, 950, None).await;
+        let checker = ConsistencyChecker::new();
+
+        let raised = checker.check(&local, std::slice::from_ref(&reference)).await;
+        match raised.as_slice() {
+            [ConsistencyAlert::Fork { endpoint, height, local_block_id, reference_block_id }] => {
+                assert_eq!(endpoint, &reference);
+                assert_eq!(*height, 900);
+                assert_eq!(local_block_id, "0xb900");
+                assert_eq!(reference_block_id, "0xa900");
+            }
+            other => panic!("expected one fork alert, got {:?}", other),
+        }
+        assert_eq!(checker.report().references[0].matches, Some(false));
+
+        // Still forked, but already reported
+        assert!(checker.check(&local, &[reference]).await.is_empty());
+        assert_eq!(checker.report().alerts.len(), 1);
+    }
+}
@@ -0,0 +1,560 @@
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use std::sync::Arc;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::process::Command as AsyncCommand;
+use crate::logger::{log_debug, log_error};
+use crate::error::NodeError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuntimeKind {
+    Docker,
+    Podman,
+    DockerComposeV1,
+    Nerdctl,
+}
+
+impl RuntimeKind {
+    pub const ALL: [RuntimeKind; 4] = [
+        RuntimeKind::Docker,
+        RuntimeKind::Podman,
+        RuntimeKind::DockerComposeV1,
+        RuntimeKind::Nerdctl,
+    ];
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            RuntimeKind::Docker => "Docker",
+            RuntimeKind::Podman => "Podman",
+            RuntimeKind::DockerComposeV1 => "docker-compose",
+            RuntimeKind::Nerdctl => "nerdctl",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuntimeInfo {
+    pub kind: RuntimeKind,
+    pub name: String,
+    pub installed: bool,
+    pub daemon_running: bool,
+    pub compose_available: bool,
+    pub rootless: bool,
+    pub version: Option<String>,
+    pub error: Option<String>,
+}
+
+/// One container's line from `stats --no-stream`. CPU is percent of one core.
+#[derive(Debug, Clone, Default)]
+pub struct ContainerStats {
+    pub name: String,
+    pub cpu_percent: f64,
+    pub memory_bytes: u64,
+    pub net_rx_bytes: u64,
+    pub net_tx_bytes: u64,
+    pub block_read_bytes: u64,
+    pub block_write_bytes: u64,
+}
+
+// Fields of the stats template, all engines support these
+const STATS_FORMAT: &str = "{{.Name}}\t{{.CPUPerc}}\t{{.MemUsage}}\t{{.NetIO}}\t{{.BlockIO}}";
+
+/// Sizes as printed by `stats`, e.g. `12.5MiB`, `3.4kB` or `0B`
+pub fn parse_size(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let split = text.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number.trim().parse().ok()?;
+    let multiplier = match unit.trim() {
+        "" | "B" => 1.0,
+        "kB" | "KB" => 1e3,
+        "MB" => 1e6,
+        "GB" => 1e9,
+        "TB" => 1e12,
+        "KiB" => 1024.0,
+        "MiB" => 1024.0 * 1024.0,
+        "GiB" => 1024.0 * 1024.0 * 1024.0,
+        "TiB" => 1024.0 * 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    Some((number * multiplier) as u64)
+}
+
+// `1.2kB / 3.4MB` -> (1200, 3400000)
+fn parse_size_pair(text: &str) -> Option<(u64, u64)> {
+    let (a, b) = text.split_once('/')?;
+    Some((parse_size(a)?, parse_size(b)?))
+}
+
+fn parse_stats_line(line: &str) -> Option<ContainerStats> {
+    let fields: Vec<&str> = line.split('\t').map(str::trim).collect();
+    let [name, cpu, memory, net, block] = fields[..] else {
+        return None;
+    };
+    let (memory_bytes, _) = parse_size_pair(memory)?;
+    let (net_rx_bytes, net_tx_bytes) = parse_size_pair(net).unwrap_or_default();
+    let (block_read_bytes, block_write_bytes) = parse_size_pair(block).unwrap_or_default();
+    Some(ContainerStats {
+        name: name.to_string(),
+        cpu_percent: cpu.trim_end_matches('%').parse().ok()?,
+        memory_bytes,
+        net_rx_bytes,
+        net_tx_bytes,
+        block_read_bytes,
+        block_write_bytes,
+    })
+}
+
+/// Everything NodeManager needs from a container engine and its compose frontend.
+#[async_trait]
+pub trait ContainerRuntime: Send + Sync {
+    fn kind(&self) -> RuntimeKind;
+
+    /// Whether the engine binary can be found at all
+    fn detect(&self) -> bool;
+
+    /// Whether a compose frontend is usable with this engine
+    fn has_compose(&self) -> bool;
+
+    /// Engine version, daemon reachability and compose support
+    fn info(&self) -> RuntimeInfo;
+
+    async fn compose_up(&self, project_dir: &Path, profiles: &[String]) -> Result<(), NodeError>;
+    async fn compose_down(&self, project_dir: &Path, profiles: &[String]) -> Result<(), NodeError>;
+    async fn compose_pull(&self, project_dir: &Path) -> Result<(), NodeError>;
+
+    /// Containers of the project as JSON, in the shape of `compose ps --format json`
+    async fn compose_ps(&self, project_dir: &Path) -> Result<String, NodeError>;
+    async fn compose_logs(&self, project_dir: &Path, tail: u32) -> Result<String, NodeError>;
+
+    async fn logs(&self, container: &str, tail: u32) -> Result<String, NodeError>;
+    async fn exec(&self, container: &str, cmd: &[&str]) -> Result<String, NodeError>;
+    async fn inspect(&self, container: &str) -> Result<serde_json::Value, NodeError>;
+
+    /// Names of all running containers
+    async fn running_containers(&self) -> Result<Vec<String>, NodeError>;
+
+    /// A single stats sample for the given containers
+    async fn stats(&self, containers: &[String]) -> Result<Vec<ContainerStats>, NodeError>;
+
+    /// Find the running container for a compose service. Compose v2 names containers
+    /// `project-service-1` while compose v1 and podman-compose use `project_service_1`.
+    async fn find_container(&self, project: &str, service: &str) -> Option<String> {
+        let running = self.running_containers().await.ok()?;
+        running
+            .into_iter()
+            .find(|name| container_matches(name, project, service))
+    }
+}
+
+pub fn container_matches(name: &str, project: &str, service: &str) -> bool {
+    ["-", "_"].iter().any(|sep| {
+        name.strip_prefix(&format!("{}{}{}{}", project, sep, service, sep))
+            .map(|index| !index.is_empty() && index.chars().all(|c| c.is_ascii_digit()))
+            .unwrap_or(false)
+    })
+}
+
+/// A runtime driven through its command line tools. All supported backends share the
+/// same CLI surface and differ only in which binaries they resolve.
+pub struct CliRuntime {
+    kind: RuntimeKind,
+    engine_candidates: Vec<&'static str>,
+    compose_candidates: Vec<&'static str>,
+}
+
+impl CliRuntime {
+    pub fn docker() -> Self {
+        Self {
+            kind: RuntimeKind::Docker,
+            engine_candidates: vec![
+                "/usr/local/bin/docker",
+                "/opt/homebrew/bin/docker",
+                "/Applications/Docker.app/Contents/Resources/bin/docker",
+                "/usr/bin/docker",
+                "docker",
+            ],
+            compose_candidates: vec![],
+        }
+    }
+
+    pub fn podman() -> Self {
+        Self {
+            kind: RuntimeKind::Podman,
+            engine_candidates: vec![
+                "/usr/bin/podman",
+                "/usr/local/bin/podman",
+                "/opt/homebrew/bin/podman",
+                "/opt/podman/bin/podman",
+                "podman",
+            ],
+            compose_candidates: vec![],
+        }
+    }
+
+    pub fn docker_compose_v1() -> Self {
+        Self {
+            kind: RuntimeKind::DockerComposeV1,
+            engine_candidates: vec![
+                "/usr/local/bin/docker",
+                "/opt/homebrew/bin/docker",
+                "/usr/bin/docker",
+                "docker",
+            ],
+            compose_candidates: vec![
+                "docker-compose",
+                "/opt/homebrew/bin/docker-compose",
+                "/usr/local/bin/docker-compose",
+                "/usr/bin/docker-compose",
+            ],
+        }
+    }
+
+    pub fn nerdctl() -> Self {
+        Self {
+            kind: RuntimeKind::Nerdctl,
+            engine_candidates: vec![
+                "/usr/local/bin/nerdctl",
+                "/usr/bin/nerdctl",
+                "/opt/homebrew/bin/nerdctl",
+                "nerdctl",
+            ],
+            compose_candidates: vec![],
+        }
+    }
+
+    // Resolve a working engine binary (handles PATH issues on macOS)
+    fn engine(&self) -> Option<String> {
+        for c in &self.engine_candidates {
+            if let Ok(output) = Command::new(c).arg("--version").output() {
+                if output.status.success() {
+                    log_debug(&format!("Found {} at: {}", self.kind.display_name(), c), None);
+                    return Some(c.to_string());
+                }
+            }
+        }
+        None
+    }
+
+    fn compose_invocation(&self) -> Option<(String, Vec<String>)> {
+        // Standalone compose binary (docker-compose v1)
+        if !self.compose_candidates.is_empty() {
+            for c in &self.compose_candidates {
+                if Command::new(c)
+                    .arg("--version")
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .status()
+                    .map(|s| s.success())
+                    .unwrap_or(false)
+                {
+                    return Some((c.to_string(), vec![]));
+                }
+            }
+            return None;
+        }
+
+        // Compose as an engine subcommand ('docker compose', 'podman compose', 'nerdctl compose')
+        let engine = self.engine()?;
+        if Command::new(&engine)
+            .arg("compose")
+            .arg("version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map(|s| s.success())
+            .unwrap_or(false)
+        {
+            return Some((engine, vec!["compose".into()]));
+        }
+        None
+    }
+
+    async fn run_compose(&self, project_dir: &Path, args: Vec<String>) -> Result<Output, NodeError> {
+        let (program, mut base_args) = self.compose_invocation().ok_or_else(|| {
+            NodeError::ComposeMissing(format!("No compose frontend is available for {}", self.kind.display_name()))
+        })?;
+        base_args.extend(args);
+        AsyncCommand::new(program)
+            .args(base_args)
+            .current_dir(project_dir)
+            .output()
+            .await
+            .map_err(|e| NodeError::from(e).context("Failed to run compose"))
+    }
+
+    async fn run_engine(&self, args: &[&str]) -> Result<Output, NodeError> {
+        let engine = self
+            .engine()
+            .ok_or_else(|| NodeError::DockerMissing(format!("{} not found", self.kind.display_name())))?;
+        AsyncCommand::new(engine)
+            .args(args)
+            .output()
+            .await
+            .map_err(|e| NodeError::from(e).context(&format!("Failed to run {}", self.kind.display_name())))
+    }
+
+    fn profile_args(profiles: &[String]) -> Vec<String> {
+        profiles
+            .iter()
+            .flat_map(|p| vec!["--profile".to_string(), p.clone()])
+            .collect()
+    }
+
+    fn is_rootless(&self, engine: &str) -> bool {
+        let args: &[&str] = match self.kind {
+            RuntimeKind::Podman => &["info", "--format", "{{.Host.Security.Rootless}}"],
+            _ => &["info", "--format", "{{.SecurityOptions}}"],
+        };
+        Command::new(engine)
+            .args(args)
+            .output()
+            .map(|o| {
+                let out = String::from_utf8_lossy(&o.stdout);
+                out.contains("true") || out.contains("rootless")
+            })
+            .unwrap_or(false)
+    }
+}
+
+fn check_output(output: Output, action: &str) -> Result<String, NodeError> {
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(NodeError::Process(format!("{}: {}", action, String::from_utf8_lossy(&output.stderr))))
+    }
+}
+
+#[async_trait]
+impl ContainerRuntime for CliRuntime {
+    fn kind(&self) -> RuntimeKind {
+        self.kind
+    }
+
+    fn detect(&self) -> bool {
+        self.engine().is_some()
+    }
+
+    fn has_compose(&self) -> bool {
+        self.compose_invocation().is_some()
+    }
+
+    fn info(&self) -> RuntimeInfo {
+        let mut info = RuntimeInfo {
+            kind: self.kind,
+            name: self.kind.display_name().to_string(),
+            installed: false,
+            daemon_running: false,
+            compose_available: false,
+            rootless: false,
+            version: None,
+            error: None,
+        };
+
+        let engine = match self.engine() {
+            Some(engine) => engine,
+            None => return info,
+        };
+        info.installed = true;
+
+        if let Ok(output) = Command::new(&engine).arg("--version").output() {
+            info.version = Some(String::from_utf8_lossy(&output.stdout).trim().to_string());
+        }
+
+        match Command::new(&engine).arg("info").output() {
+            Ok(output) if output.status.success() => {
+                info.daemon_running = true;
+                info.rootless = self.is_rootless(&engine);
+            }
+            Ok(output) => {
+                info.error = Some(String::from_utf8_lossy(&output.stderr).trim().to_string());
+            }
+            Err(e) => {
+                info.error = Some(e.to_string());
+            }
+        }
+
+        info.compose_available = self.has_compose();
+        info
+    }
+
+    async fn compose_up(&self, project_dir: &Path, profiles: &[String]) -> Result<(), NodeError> {
+        let mut args = Self::profile_args(profiles);
+        args.extend(vec!["up".into(), "-d".into()]);
+        let output = self.run_compose(project_dir, args).await?;
+        check_output(output, "Failed to start node").map(|_| ())
+    }
+
+    async fn compose_down(&self, project_dir: &Path, profiles: &[String]) -> Result<(), NodeError> {
+        let mut args = Self::profile_args(profiles);
+        args.push("down".into());
+        let output = self.run_compose(project_dir, args).await?;
+        check_output(output, "Failed to stop node").map(|_| ())
+    }
+
+    async fn compose_pull(&self, project_dir: &Path) -> Result<(), NodeError> {
+        let output = self.run_compose(project_dir, vec!["pull".into()]).await?;
+        check_output(output, "Failed to pull images").map(|_| ())
+    }
+
+    async fn compose_ps(&self, project_dir: &Path) -> Result<String, NodeError> {
+        if self.kind != RuntimeKind::DockerComposeV1 {
+            let output = self
+                .run_compose(project_dir, vec!["ps".into(), "--format".into(), "json".into()])
+                .await?;
+            return check_output(output, "Failed to get container status");
+        }
+
+        // docker-compose v1 has no --format, so look the containers up by id instead
+        let output = self.run_compose(project_dir, vec!["ps".into(), "-q".into()]).await?;
+        let ids = check_output(output, "Failed to get container status")?;
+        let ids: Vec<&str> = ids.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+        if ids.is_empty() {
+            return Ok("[]".to_string());
+        }
+        let mut args = vec!["inspect"];
+        args.extend(ids);
+        let output = self.run_engine(&args).await?;
+        let inspected: Vec<serde_json::Value> = serde_json::from_str(&check_output(output, "Failed to inspect containers")?)
+            .map_err(|e| NodeError::from(e).context("Failed to parse inspect output"))?;
+        let containers: Vec<serde_json::Value> = inspected
+            .iter()
+            .map(|c| {
+                serde_json::json!({
+                    "Name": c["Name"].as_str().unwrap_or_default().trim_start_matches('/'),
+                    "Service": c["Config"]["Labels"]["com.docker.compose.service"],
+                    "State": c["State"]["Status"],
+                })
+            })
+            .collect();
+        Ok(serde_json::Value::Array(containers).to_string())
+    }
+
+    async fn compose_logs(&self, project_dir: &Path, tail: u32) -> Result<String, NodeError> {
+        let output = self
+            .run_compose(project_dir, vec!["logs".into(), "--tail".into(), tail.to_string()])
+            .await?;
+        check_output(output, "Failed to get logs")
+    }
+
+    async fn logs(&self, container: &str, tail: u32) -> Result<String, NodeError> {
+        let tail = tail.to_string();
+        let output = self.run_engine(&["logs", "--tail", &tail, container]).await?;
+        // Services log to stderr as often as stdout
+        let mut logs = String::from_utf8_lossy(&output.stdout).to_string();
+        logs.push_str(&String::from_utf8_lossy(&output.stderr));
+        Ok(logs)
+    }
+
+    async fn exec(&self, container: &str, cmd: &[&str]) -> Result<String, NodeError> {
+        let mut args = vec!["exec", container];
+        args.extend_from_slice(cmd);
+        let output = self.run_engine(&args).await?;
+        check_output(output, &format!("Failed to exec in {}", container))
+    }
+
+    async fn inspect(&self, container: &str) -> Result<serde_json::Value, NodeError> {
+        let output = self.run_engine(&["inspect", container]).await?;
+        let text = check_output(output, &format!("Failed to inspect {}", container))?;
+        serde_json::from_str(&text).map_err(|e| NodeError::from(e).context("Failed to parse inspect output"))
+    }
+
+    async fn running_containers(&self) -> Result<Vec<String>, NodeError> {
+        let output = self.run_engine(&["ps", "--format", "{{.Names}}"]).await?;
+        let text = check_output(output, "Failed to list containers")?;
+        Ok(text
+            .lines()
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty())
+            .collect())
+    }
+
+    async fn stats(&self, containers: &[String]) -> Result<Vec<ContainerStats>, NodeError> {
+        if containers.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut args = vec!["stats", "--no-stream", "--format", STATS_FORMAT];
+        args.extend(containers.iter().map(String::as_str));
+        let output = self.run_engine(&args).await?;
+        let text = check_output(output, "Failed to read container stats")?;
+        Ok(text.lines().filter_map(parse_stats_line).collect())
+    }
+}
+
+pub fn runtime_for(kind: RuntimeKind) -> Arc<dyn ContainerRuntime> {
+    match kind {
+        RuntimeKind::Docker => Arc::new(CliRuntime::docker()),
+        RuntimeKind::Podman => Arc::new(CliRuntime::podman()),
+        RuntimeKind::DockerComposeV1 => Arc::new(CliRuntime::docker_compose_v1()),
+        RuntimeKind::Nerdctl => Arc::new(CliRuntime::nerdctl()),
+    }
+}
+
+/// Pick the persisted runtime if it is still installed, otherwise the first one found.
+pub fn detect_runtime(preferred: Option<RuntimeKind>) -> Arc<dyn ContainerRuntime> {
+    if let Some(kind) = preferred {
+        let runtime = runtime_for(kind);
+        if runtime.detect() {
+            return runtime;
+        }
+        log_error(
+            "Configured container runtime not found",
+            Some(kind.display_name()),
+        );
+    }
+
+    // Prefer an engine that also has a working compose frontend
+    let installed: Vec<_> = RuntimeKind::ALL
+        .iter()
+        .map(|kind| runtime_for(*kind))
+        .filter(|runtime| runtime.detect())
+        .collect();
+    if let Some(runtime) = installed.iter().find(|runtime| runtime.has_compose()) {
+        log_debug("Using container runtime", Some(runtime.kind().display_name()));
+        return runtime.clone();
+    }
+    if let Some(runtime) = installed.into_iter().next() {
+        log_debug("Using container runtime without compose", Some(runtime.kind().display_name()));
+        return runtime;
+    }
+
+    // Nothing installed yet - default to Docker so the install flow can take over
+    runtime_for(RuntimeKind::Docker)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_in_decimal_and_binary_units() {
+        assert_eq!(parse_size("0B"), Some(0));
+        assert_eq!(parse_size("3.4kB"), Some(3400));
+        assert_eq!(parse_size(" 12.5MiB "), Some(13_107_200));
+        assert_eq!(parse_size("1GB"), Some(1_000_000_000));
+        assert_eq!(parse_size("2 GiB"), Some(2_147_483_648));
+        assert_eq!(parse_size("12"), Some(12));
+        assert_eq!(parse_size("5 parsecs"), None);
+        assert_eq!(parse_size("MB"), None);
+    }
+
+    #[test]
+    fn stats_line_is_split_into_fields() {
+        let stats = parse_stats_line("koinos-chain-1\t12.50%\t256MiB / 7.6GiB\t1.2kB / 3.4MB\t0B / 1GB").unwrap();
+        assert_eq!(stats.name, "koinos-chain-1");
+        assert_eq!(stats.cpu_percent, 12.5);
+        assert_eq!(stats.memory_bytes, 268_435_456);
+        assert_eq!((stats.net_rx_bytes, stats.net_tx_bytes), (1200, 3_400_000));
+        assert_eq!((stats.block_read_bytes, stats.block_write_bytes), (0, 1_000_000_000));
+    }
+
+    #[test]
+    fn stats_line_tolerates_missing_io_but_not_memory() {
+        let stats = parse_stats_line("p2p\t0.00%\t10MB / 1GB\t--\t--").unwrap();
+        assert_eq!((stats.net_rx_bytes, stats.block_write_bytes), (0, 0));
+        assert!(parse_stats_line("p2p\t0.00%\t--\t--\t--").is_none());
+        assert!(parse_stats_line("p2p\t0.00%").is_none());
+    }
+}
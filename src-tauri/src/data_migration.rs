@@ -0,0 +1,428 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use crate::node_manager::{NodeManager, NodeMode, SERVICES};
+use crate::node_config::EnvFile;
+use crate::state_manager::write_atomic;
+use crate::logger::{log_error, log_info, log_warn};
+use crate::error::NodeError;
+
+// Headroom on top of the data being copied, for files the filesystem rounds up
+const SPACE_MARGIN: f64 = 0.05;
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Default)]
+struct Undo {
+    // (from, to) of directories renamed into the target
+    moved: Vec<(PathBuf, PathBuf)>,
+    // Directories copied into the target; the source is left alone until the end
+    copied: Vec<(PathBuf, PathBuf)>,
+    // Config files with their content before the rewrite
+    files: Vec<(PathBuf, String)>,
+    switched: bool,
+}
+
+/// Move the node data to `target`: stop the node, move or copy every service
+/// directory, point the compose `.env` (or the native configs) at the new location
+/// and start the node again. Any failure puts everything back the way it was.
+///
+/// Only the service directories move; app state such as `node_state.json` and the
+/// settings stay in `~/.koinos`.
+pub async fn migrate_data_dir(
+    node_manager: Arc<Mutex<NodeManager>>,
+    target: PathBuf,
+    progress: impl Fn(f32, String) + Send + Sync + 'static,
+) -> Result<(), NodeError> {
+    // The job can't be cancelled, and the job registry keeps every other node
+    // operation out until it finishes. Running on its own task also means a dropped
+    // caller can't interrupt a rollback halfway.
+    tokio::spawn(migrate(node_manager, target, progress)).await?
+}
+
+async fn migrate(
+    node_manager: Arc<Mutex<NodeManager>>,
+    target: PathBuf,
+    progress: impl Fn(f32, String) + Send + Sync + 'static,
+) -> Result<(), NodeError> {
+    let manager = node_manager.lock().await.clone();
+    let source = manager.data_path.clone();
+    let dirs = plan(&source, &target)?;
+    log_info("Migrating node data", Some(&format!("{} -> {}", source.display(), target.display())));
+
+    let was_running = manager.lifecycle.phase().is_active();
+    if was_running {
+        progress(0.0, "Stopping node".to_string());
+        manager.stop_node().await?;
+    }
+
+    let mut undo = Undo::default();
+    let result = async {
+        transfer(&source, &target, &dirs, &mut undo, &progress).await?;
+
+        progress(96.0, "Updating configuration".to_string());
+        rewrite_configs(&manager, &source, &target, &mut undo)?;
+        node_manager.lock().await.set_data_path(target.clone())?;
+        undo.switched = true;
+
+        if was_running {
+            progress(98.0, "Starting node".to_string());
+            let manager = node_manager.lock().await.clone();
+            if let Err(e) = manager.start_node().await {
+                manager.stop_node().await.ok();
+                return Err(e.context("Node did not start from the new data directory"));
+            }
+        }
+        Ok(())
+    }
+    .await;
+
+    if let Err(e) = result {
+        log_error("Data migration failed, rolling back", Some(&e.to_string()));
+        progress(0.0, "Rolling back".to_string());
+        rollback(&node_manager, &source, undo).await;
+        if was_running {
+            let manager = node_manager.lock().await.clone();
+            if let Err(e) = manager.start_node().await {
+                log_warn("Failed to restart node after rollback", Some(&e.to_string()));
+            }
+        }
+        return Err(e);
+    }
+
+    for (from, _) in &undo.copied {
+        if let Err(e) = fs::remove_dir_all(from) {
+            log_warn("Failed to remove old data", Some(&format!("{}: {}", from.display(), e)));
+        }
+    }
+    log_info("Node data migrated", Some(&target.display().to_string()));
+    Ok(())
+}
+
+// Check the target and pick the directories to move
+fn plan(source: &Path, target: &Path) -> Result<Vec<&'static str>, NodeError> {
+    if !target.is_absolute() {
+        return Err(NodeError::InvalidInput("The new data directory must be an absolute path".to_string()));
+    }
+    if target.is_file() {
+        return Err(NodeError::InvalidInput(format!("{} is a file", target.display())));
+    }
+    let same = target == source
+        || fs::canonicalize(target).ok().is_some_and(|t| fs::canonicalize(source).ok() == Some(t));
+    if same {
+        return Err(NodeError::InvalidInput("The node data is already there".to_string()));
+    }
+
+    let dirs: Vec<&'static str> = SERVICES.iter().copied().filter(|dir| source.join(dir).is_dir()).collect();
+    for dir in &dirs {
+        if target.starts_with(source.join(dir)) {
+            return Err(NodeError::InvalidInput(format!(
+                "The new data directory can't be inside {}",
+                source.join(dir).display()
+            )));
+        }
+        let existing = target.join(dir);
+        if fs::read_dir(&existing).is_ok_and(|mut entries| entries.next().is_some()) {
+            return Err(NodeError::InvalidInput(format!("{} already exists and is not empty", existing.display())));
+        }
+    }
+    Ok(dirs)
+}
+
+async fn transfer(
+    source: &Path,
+    target: &Path,
+    dirs: &[&'static str],
+    undo: &mut Undo,
+    progress: &(impl Fn(f32, String) + Send + Sync),
+) -> Result<(), NodeError> {
+    fs::create_dir_all(target).map_err(|e| NodeError::from(e).context("Failed to create the new data directory"))?;
+
+    // A rename is instant on the same filesystem, so try that first
+    let mut to_copy = Vec::new();
+    for dir in dirs {
+        let (from, to) = (source.join(dir), target.join(dir));
+        // An empty directory left by an earlier attempt would make the rename fail
+        fs::remove_dir(&to).ok();
+        match fs::rename(&from, &to) {
+            Ok(()) => undo.moved.push((from, to)),
+            Err(_) => to_copy.push((from, to)),
+        }
+    }
+    if to_copy.is_empty() {
+        progress(95.0, "Data moved".to_string());
+        return Ok(());
+    }
+
+    let listings: Vec<BTreeMap<PathBuf, u64>> =
+        to_copy.iter().map(|(from, _)| list_files(from)).collect::<io::Result<_>>()?;
+    let total: u64 = listings.iter().flat_map(|files| files.values()).sum();
+    let available = fs2::available_space(target)
+        .map_err(|e| NodeError::from(e).context("Failed to check free space"))?;
+    let needed = (total as f64 * (1.0 + SPACE_MARGIN)) as u64;
+    if available < needed {
+        return Err(NodeError::DiskFull(format!(
+            "{:.1} GB needed in {}, {:.1} GB available",
+            needed as f64 / 1e9,
+            target.display(),
+            available as f64 / 1e9
+        )));
+    }
+
+    let mut done = 0u64;
+    for ((from, to), expected) in to_copy.into_iter().zip(listings) {
+        let expected_bytes: u64 = expected.values().sum();
+        log_info("Copying node data", Some(&format!("{} -> {}", from.display(), to.display())));
+        undo.copied.push((from.clone(), to.clone()));
+        let (copy_from, copy_to) = (from.clone(), to.clone());
+        let counter = Arc::new(AtomicU64::new(0));
+        let copy_counter = counter.clone();
+        let mut copied = tokio::task::spawn_blocking(move || {
+            copy_tree(&copy_from, &copy_to, &mut |len| {
+                copy_counter.fetch_add(len, Ordering::Relaxed);
+            })
+        });
+
+        // The copy only counts bytes; progress is reported from here
+        let mut ticker = tokio::time::interval(PROGRESS_INTERVAL);
+        loop {
+            tokio::select! {
+                result = &mut copied => {
+                    result?.map_err(|e| NodeError::from(e).context(&format!("Failed to copy {}", from.display())))?;
+                    break;
+                }
+                _ = ticker.tick() => {
+                    let bytes = counter.load(Ordering::Relaxed);
+                    progress(
+                        progress_percent(done + bytes, total),
+                        format!("Copying {} ({:.1} / {:.1} GB)", dir_name(&from), (done + bytes) as f64 / 1e9, total as f64 / 1e9),
+                    );
+                }
+            }
+        }
+
+        progress(progress_percent(done + expected_bytes, total), format!("Verifying {}", dir_name(&from)));
+        let verify_to = to.clone();
+        let actual = tokio::task::spawn_blocking(move || list_files(&verify_to)).await??;
+        if let Some(problem) = compare_listings(&expected, &actual) {
+            return Err(NodeError::Io(format!("Copy of {} is incomplete: {}", from.display(), problem)));
+        }
+        done += expected_bytes;
+    }
+    Ok(())
+}
+
+// Copying takes up to 95%; the rest is config and restart
+fn progress_percent(done: u64, total: u64) -> f32 {
+    if total == 0 {
+        return 95.0;
+    }
+    (done as f64 / total as f64 * 95.0).min(95.0) as f32
+}
+
+fn dir_name(path: &Path) -> String {
+    path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+/// Size of every regular file under `root`, keyed by its path relative to `root`
+fn list_files(root: &Path) -> io::Result<BTreeMap<PathBuf, u64>> {
+    fn walk(root: &Path, dir: &Path, files: &mut BTreeMap<PathBuf, u64>) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                walk(root, &entry.path(), files)?;
+            } else if file_type.is_file() {
+                let path = entry.path();
+                let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+                files.insert(relative, entry.metadata()?.len());
+            }
+        }
+        Ok(())
+    }
+    let mut files = BTreeMap::new();
+    walk(root, root, &mut files)?;
+    Ok(files)
+}
+
+/// The first difference between the source and copied listings, if any
+fn compare_listings(expected: &BTreeMap<PathBuf, u64>, actual: &BTreeMap<PathBuf, u64>) -> Option<String> {
+    for (path, size) in expected {
+        match actual.get(path) {
+            None => return Some(format!("{} is missing", path.display())),
+            Some(copied) if copied != size => {
+                return Some(format!("{} is {} bytes, expected {}", path.display(), copied, size));
+            }
+            _ => {}
+        }
+    }
+    actual
+        .keys()
+        .find(|path| !expected.contains_key(*path))
+        .map(|path| format!("unexpected file {}", path.display()))
+}
+
+fn copy_tree(from: &Path, to: &Path, on_file: &mut impl FnMut(u64)) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let dest = to.join(entry.file_name());
+        if file_type.is_dir() {
+            copy_tree(&entry.path(), &dest, on_file)?;
+        } else if file_type.is_symlink() {
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(fs::read_link(entry.path())?, &dest)?;
+        } else {
+            let len = fs::copy(entry.path(), &dest)?;
+            on_file(len);
+        }
+    }
+    // Make sure the data is on disk before the original is deleted
+    fs::File::open(to)?.sync_all().ok();
+    Ok(())
+}
+
+// Point the node's configuration at the new location, keeping the old content
+fn rewrite_configs(manager: &NodeManager, source: &Path, target: &Path, undo: &mut Undo) -> Result<(), NodeError> {
+    let target_str = target.to_string_lossy();
+    let mut rewrite = |path: PathBuf, change: &dyn Fn(&str) -> String| -> Result<(), NodeError> {
+        let Ok(content) = fs::read_to_string(&path) else {
+            return Ok(());
+        };
+        let updated = change(&content);
+        if updated != content {
+            write_atomic(&path, updated.as_bytes())
+                .map_err(|e| e.context(&format!("Failed to update {}", path.display())))?;
+            undo.files.push((path, content));
+        }
+        Ok(())
+    };
+
+    match manager.mode {
+        NodeMode::Docker => {
+            rewrite(manager.koinos_path.join(".env"), &|content| {
+                let mut env = EnvFile::parse(content);
+                env.set("BASEDIR", &target_str);
+                env.to_string()
+            })?;
+            // Volumes written out as literal paths rather than through BASEDIR
+            let source_str = source.to_string_lossy();
+            rewrite(manager.koinos_path.join("docker-compose.yml"), &|content| {
+                replace_path(content, &source_str, &target_str)
+            })?;
+        }
+        NodeMode::Native => {
+            let entries = fs::read_dir(manager.native.config_dir())
+                .map_err(|e| NodeError::from(e).context("Failed to read native configs"))?;
+            for path in entries.flatten().map(|e| e.path()) {
+                if path.extension().is_some_and(|ext| ext == "json") {
+                    rewrite(path, &|content| retarget_data_dir(content, source, target))?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Replace `from` with `to` wherever it appears as a whole path, or as the leading
+/// components of one, so `/home/me/.koinos` doesn't match inside `/home/me/.koinos-node`
+fn replace_path(content: &str, from: &str, to: &str) -> String {
+    let is_path_char = |c: char| c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | '/' | '~');
+    let mut result = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(at) = rest.find(from) {
+        let (before, after) = (&rest[..at], &rest[at + from.len()..]);
+        let starts = before.chars().next_back().is_none_or(|c| !is_path_char(c));
+        let ends = after.chars().next().is_none_or(|c| c == '/' || !is_path_char(c));
+        result.push_str(before);
+        result.push_str(if starts && ends { to } else { from });
+        rest = after;
+    }
+    result.push_str(rest);
+    result
+}
+
+fn retarget_data_dir(content: &str, source: &Path, target: &Path) -> String {
+    let Ok(mut config) = serde_json::from_str::<serde_json::Value>(content) else {
+        return content.to_string();
+    };
+    let Some(dir) = config.get("data_dir").and_then(|d| d.as_str()).map(PathBuf::from) else {
+        return content.to_string();
+    };
+    let Ok(rest) = dir.strip_prefix(source) else {
+        return content.to_string();
+    };
+    config["data_dir"] = serde_json::Value::String(target.join(rest).to_string_lossy().to_string());
+    serde_json::to_string_pretty(&config).unwrap_or_else(|_| content.to_string())
+}
+
+// Best effort; every step is attempted even if an earlier one failed
+async fn rollback(node_manager: &Arc<Mutex<NodeManager>>, source: &Path, undo: Undo) {
+    if undo.switched {
+        if let Err(e) = node_manager.lock().await.set_data_path(source.to_path_buf()) {
+            log_error("Rollback: failed to restore data path", Some(&e.to_string()));
+        }
+    }
+    for (path, content) in undo.files {
+        if let Err(e) = write_atomic(&path, content.as_bytes()) {
+            log_error("Rollback: failed to restore config", Some(&format!("{}: {}", path.display(), e)));
+        }
+    }
+    for (from, to) in undo.moved {
+        if let Err(e) = fs::rename(&to, &from) {
+            log_error(
+                "Rollback: failed to move data back",
+                Some(&format!("{} -> {}: {}", to.display(), from.display(), e)),
+            );
+        }
+    }
+    for (_, to) in undo.copied {
+        if let Err(e) = fs::remove_dir_all(&to) {
+            log_warn("Rollback: failed to remove partial copy", Some(&format!("{}: {}", to.display(), e)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replace_path_matches_whole_components() {
+        let compose = "volumes:\n  - /home/me/.koinos/chain:/koinos/chain\n  - /home/me/.koinos-node/p2p:/koinos/p2p\n  - \"/home/me/.koinos\"\n";
+        assert_eq!(
+            replace_path(compose, "/home/me/.koinos", "/data/koinos"),
+            "volumes:\n  - /data/koinos/chain:/koinos/chain\n  - /home/me/.koinos-node/p2p:/koinos/p2p\n  - \"/data/koinos\"\n"
+        );
+        assert_eq!(replace_path("/srv/home/me/.koinos/chain", "/home/me/.koinos", "/data"), "/srv/home/me/.koinos/chain");
+    }
+
+    #[test]
+    fn listings_report_size_mismatches() {
+        let expected = BTreeMap::from([(PathBuf::from("chain/a.sst"), 10), (PathBuf::from("chain/b.sst"), 20)]);
+        assert_eq!(compare_listings(&expected, &expected.clone()), None);
+
+        // Same file count and total, different files
+        let swapped = BTreeMap::from([(PathBuf::from("chain/a.sst"), 20), (PathBuf::from("chain/b.sst"), 10)]);
+        assert_eq!(compare_listings(&expected, &swapped).unwrap(), "chain/a.sst is 20 bytes, expected 10");
+
+        let renamed = BTreeMap::from([(PathBuf::from("chain/a.sst"), 10), (PathBuf::from("chain/c.sst"), 20)]);
+        assert_eq!(compare_listings(&expected, &renamed).unwrap(), "chain/b.sst is missing");
+    }
+
+    #[test]
+    fn data_dir_is_retargeted_under_the_new_root() {
+        let config = r#"{"data_dir": "/home/me/.koinos/chain", "amqp": "amqp://localhost"}"#;
+        let updated = retarget_data_dir(config, Path::new("/home/me/.koinos"), Path::new("/data/koinos"));
+        let value: serde_json::Value = serde_json::from_str(&updated).unwrap();
+        assert_eq!(value["data_dir"], "/data/koinos/chain");
+
+        let elsewhere = r#"{"data_dir": "/opt/chain"}"#;
+        assert_eq!(retarget_data_dir(elsewhere, Path::new("/home/me/.koinos"), Path::new("/data")), elsewhere);
+    }
+}
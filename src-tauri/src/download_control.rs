@@ -0,0 +1,311 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use chrono::NaiveTime;
+use serde::{Deserialize, Serialize};
+use crate::logger::log_info;
+use crate::error::NodeError;
+use crate::state_manager::write_atomic;
+
+/// Daily time range (local time, `HH:MM`) in which snapshot downloads may run.
+/// `end` before `start` wraps past midnight.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadWindow {
+    pub start: String,
+    pub end: String,
+}
+
+impl DownloadWindow {
+    fn parse(value: &str) -> Result<NaiveTime, NodeError> {
+        NaiveTime::parse_from_str(value, "%H:%M")
+            .map_err(|_| NodeError::InvalidInput(format!("Invalid time {:?}, expected HH:MM", value)))
+    }
+
+    pub fn contains(&self, now: NaiveTime) -> bool {
+        let (Ok(start), Ok(end)) = (Self::parse(&self.start), Self::parse(&self.end)) else {
+            return true;
+        };
+        if start <= end {
+            now >= start && now < end
+        } else {
+            now >= start || now < end
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DownloadLimits {
+    /// None means unlimited
+    pub max_bytes_per_sec: Option<u64>,
+    pub window: Option<DownloadWindow>,
+}
+
+impl DownloadLimits {
+    fn validate(&self) -> Result<(), NodeError> {
+        if self.max_bytes_per_sec == Some(0) {
+            return Err(NodeError::InvalidInput("Rate limit must be greater than zero".to_string()));
+        }
+        if let Some(window) = &self.window {
+            DownloadWindow::parse(&window.start)?;
+            DownloadWindow::parse(&window.end)?;
+            if window.start == window.end {
+                return Err(NodeError::InvalidInput("Download window start and end must differ".to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    fn window_open(&self) -> bool {
+        self.window
+            .as_ref()
+            .map(|w| w.contains(chrono::Local::now().time()))
+            .unwrap_or(true)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadState {
+    pub active: bool,
+    pub paused: bool,
+    pub outside_window: bool,
+    pub limits: DownloadLimits,
+}
+
+// Token bucket with one second of burst. Tokens may go negative; the caller then
+// sleeps off the debt.
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+struct Inner {
+    path: PathBuf,
+    limits: Mutex<DownloadLimits>,
+    bucket: Mutex<Bucket>,
+    active: AtomicBool,
+    paused: AtomicBool,
+    cancelled: AtomicBool,
+    interrupted: AtomicBool,
+}
+
+/// Rate limit, schedule and pause/resume/cancel for snapshot downloads. Lives outside
+/// the node manager lock so commands can reach it while a download is running.
+#[derive(Clone)]
+pub struct DownloadControl {
+    inner: Arc<Inner>,
+}
+
+/// Marks a download as running until dropped
+pub struct ActiveDownload {
+    control: DownloadControl,
+}
+
+impl Drop for ActiveDownload {
+    fn drop(&mut self) {
+        self.control.inner.active.store(false, Ordering::SeqCst);
+        self.control.inner.paused.store(false, Ordering::SeqCst);
+    }
+}
+
+impl DownloadControl {
+    /// Limits are kept in `~/.koinos/download_limits.json`
+    pub fn load() -> Self {
+        let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
+        let path = home.join(".koinos").join("download_limits.json");
+        let limits = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+
+        Self {
+            inner: Arc::new(Inner {
+                path,
+                limits: Mutex::new(limits),
+                bucket: Mutex::new(Bucket { tokens: 0.0, last: Instant::now() }),
+                active: AtomicBool::new(false),
+                paused: AtomicBool::new(false),
+                cancelled: AtomicBool::new(false),
+                interrupted: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    pub fn limits(&self) -> DownloadLimits {
+        self.inner.limits.lock().unwrap().clone()
+    }
+
+    /// Takes effect immediately, including for a download that is already running
+    pub fn set_limits(&self, limits: DownloadLimits) -> Result<(), NodeError> {
+        limits.validate()?;
+
+        let json = serde_json::to_string_pretty(&limits)
+            .map_err(|e| NodeError::from(e).context("Failed to serialize download limits"))?;
+        write_atomic(&self.inner.path, json.as_bytes())
+            .map_err(|e| e.context("Failed to write download limits"))?;
+
+        *self.inner.limits.lock().unwrap() = limits;
+        log_info("Download limits updated", None);
+        Ok(())
+    }
+
+    pub fn state(&self) -> DownloadState {
+        let limits = self.limits();
+        DownloadState {
+            active: self.inner.active.load(Ordering::SeqCst),
+            paused: self.inner.paused.load(Ordering::SeqCst),
+            outside_window: !limits.window_open(),
+            limits,
+        }
+    }
+
+    /// Called when a download starts; clears a cancel or pause left from the previous one
+    pub fn begin(&self) -> Result<ActiveDownload, NodeError> {
+        if self.inner.active.swap(true, Ordering::SeqCst) {
+            return Err(NodeError::InvalidInput("A snapshot download is already running".to_string()));
+        }
+        self.inner.cancelled.store(false, Ordering::SeqCst);
+        self.inner.paused.store(false, Ordering::SeqCst);
+        self.inner.interrupted.store(false, Ordering::SeqCst);
+        Ok(ActiveDownload { control: self.clone() })
+    }
+
+    pub fn pause(&self) -> Result<(), NodeError> {
+        if !self.inner.active.load(Ordering::SeqCst) {
+            return Err(NodeError::InvalidInput("No snapshot download is running".to_string()));
+        }
+        self.inner.paused.store(true, Ordering::SeqCst);
+        log_info("Download pause requested", None);
+        Ok(())
+    }
+
+    pub fn resume(&self) -> Result<(), NodeError> {
+        if !self.inner.active.load(Ordering::SeqCst) {
+            return Err(NodeError::InvalidInput("No snapshot download is running".to_string()));
+        }
+        self.inner.paused.store(false, Ordering::SeqCst);
+        log_info("Download resume requested", None);
+        Ok(())
+    }
+
+    /// Stops the running download. What is on disk is kept, so the next download resumes.
+    pub fn cancel(&self) -> Result<(), NodeError> {
+        if !self.inner.active.load(Ordering::SeqCst) {
+            return Err(NodeError::InvalidInput("No snapshot download is running".to_string()));
+        }
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        log_info("Download cancel requested", None);
+        Ok(())
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Whether the download was paused since the last call. The server has likely
+    /// dropped the connection in the meantime, so the caller should reconnect.
+    pub fn take_interrupted(&self) -> bool {
+        self.inner.interrupted.swap(false, Ordering::SeqCst)
+    }
+
+    /// Call for every chunk received. Waits while the download is paused or outside its
+    /// window, applies the rate limit, and fails once the download is cancelled.
+    pub async fn checkpoint(&self, bytes: usize) -> Result<(), NodeError> {
+        let mut waited = false;
+        let mut announced = false;
+        loop {
+            if self.is_cancelled() {
+                return Err(NodeError::Cancelled("Snapshot download cancelled".to_string()));
+            }
+            let paused = self.inner.paused.load(Ordering::SeqCst);
+            let outside_window = !self.limits().window_open();
+            if !paused && !outside_window {
+                break;
+            }
+            if !waited {
+                waited = true;
+                // Parallel segments all wait here; only the first one logs
+                announced = !self.inner.interrupted.swap(true, Ordering::SeqCst);
+                if announced {
+                    let reason = if paused { "paused by user" } else { "outside download window" };
+                    log_info("Download paused", Some(reason));
+                }
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+        if waited {
+            if announced {
+                log_info("Download resumed", None);
+            }
+            // Don't let the idle time turn into a burst
+            let mut bucket = self.inner.bucket.lock().unwrap();
+            bucket.tokens = 0.0;
+            bucket.last = Instant::now();
+        }
+
+        self.throttle(bytes).await;
+        Ok(())
+    }
+
+    async fn throttle(&self, bytes: usize) {
+        let Some(rate) = self.limits().max_bytes_per_sec else {
+            return;
+        };
+        let rate = rate as f64;
+
+        let wait = {
+            let mut bucket = self.inner.bucket.lock().unwrap();
+            let now = Instant::now();
+            let refill = now.duration_since(bucket.last).as_secs_f64() * rate;
+            bucket.tokens = (bucket.tokens + refill).min(rate) - bytes as f64;
+            bucket.last = now;
+            if bucket.tokens < 0.0 {
+                Duration::from_secs_f64(-bucket.tokens / rate)
+            } else {
+                Duration::ZERO
+            }
+        };
+
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(start: &str, end: &str) -> DownloadWindow {
+        DownloadWindow { start: start.to_string(), end: end.to_string() }
+    }
+
+    fn at(value: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(value, "%H:%M").unwrap()
+    }
+
+    #[test]
+    fn same_day_window_includes_start_excludes_end() {
+        let w = window("09:00", "17:00");
+        assert!(w.contains(at("09:00")));
+        assert!(w.contains(at("12:30")));
+        assert!(!w.contains(at("17:00")));
+        assert!(!w.contains(at("08:59")));
+    }
+
+    #[test]
+    fn window_wraps_past_midnight() {
+        let w = window("23:00", "06:00");
+        assert!(w.contains(at("23:30")));
+        assert!(w.contains(at("00:00")));
+        assert!(w.contains(at("05:59")));
+        assert!(!w.contains(at("06:00")));
+        assert!(!w.contains(at("12:00")));
+    }
+
+    #[test]
+    fn unparsable_window_never_blocks() {
+        assert!(window("late", "06:00").contains(at("12:00")));
+    }
+}
@@ -0,0 +1,173 @@
+use std::fmt;
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+/// Errors returned by the backend. Serialized to the frontend as
+/// `{ code, message, hint }` so the UI can react to `code` instead of the message text.
+#[derive(Debug, Clone)]
+pub enum NodeError {
+    DockerMissing(String),
+    DaemonDown(String),
+    ComposeMissing(String),
+    NotInitialized(String),
+    NodeRunning(String),
+    DiskFull(String),
+    SnapshotCorrupt(String),
+    SnapshotUnavailable(String),
+    DownloadInterrupted(String),
+    Cancelled(String),
+    RpcUnavailable(String),
+    UnsupportedPlatform(String),
+    InvalidInput(String),
+    Io(String),
+    Network(String),
+    Serialization(String),
+    Process(String),
+    Other(String),
+}
+
+impl NodeError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            NodeError::DockerMissing(_) => "docker_missing",
+            NodeError::DaemonDown(_) => "daemon_down",
+            NodeError::ComposeMissing(_) => "compose_missing",
+            NodeError::NotInitialized(_) => "not_initialized",
+            NodeError::NodeRunning(_) => "node_running",
+            NodeError::DiskFull(_) => "disk_full",
+            NodeError::SnapshotCorrupt(_) => "snapshot_corrupt",
+            NodeError::SnapshotUnavailable(_) => "snapshot_unavailable",
+            NodeError::DownloadInterrupted(_) => "download_interrupted",
+            NodeError::Cancelled(_) => "cancelled",
+            NodeError::RpcUnavailable(_) => "rpc_unavailable",
+            NodeError::UnsupportedPlatform(_) => "unsupported_platform",
+            NodeError::InvalidInput(_) => "invalid_input",
+            NodeError::Io(_) => "io",
+            NodeError::Network(_) => "network",
+            NodeError::Serialization(_) => "serialization",
+            NodeError::Process(_) => "process",
+            NodeError::Other(_) => "other",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            NodeError::DockerMissing(m)
+            | NodeError::DaemonDown(m)
+            | NodeError::ComposeMissing(m)
+            | NodeError::NotInitialized(m)
+            | NodeError::NodeRunning(m)
+            | NodeError::DiskFull(m)
+            | NodeError::SnapshotCorrupt(m)
+            | NodeError::SnapshotUnavailable(m)
+            | NodeError::DownloadInterrupted(m)
+            | NodeError::Cancelled(m)
+            | NodeError::RpcUnavailable(m)
+            | NodeError::UnsupportedPlatform(m)
+            | NodeError::InvalidInput(m)
+            | NodeError::Io(m)
+            | NodeError::Network(m)
+            | NodeError::Serialization(m)
+            | NodeError::Process(m)
+            | NodeError::Other(m) => m,
+        }
+    }
+
+    /// What the user can do about it
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            NodeError::DockerMissing(_) => Some("Install Docker Desktop or Podman, then click 'Check Again'."),
+            NodeError::DaemonDown(_) => Some("Start Docker Desktop (or your container runtime) and wait until it is running."),
+            NodeError::ComposeMissing(_) => Some("Install the Compose plugin or choose another container runtime."),
+            NodeError::NotInitialized(_) => Some("Run the setup process first."),
+            NodeError::NodeRunning(_) => Some("Stop the node and try again."),
+            NodeError::DiskFull(_) => Some("Free up disk space. A full node needs at least 60GB."),
+            NodeError::SnapshotCorrupt(_) => Some("The damaged snapshot was set aside. Download it again."),
+            NodeError::SnapshotUnavailable(_) => Some("Check your internet connection or add another snapshot mirror."),
+            NodeError::DownloadInterrupted(_) => Some("Start the download again; it continues where it stopped."),
+            NodeError::RpcUnavailable(_) => Some("The node may still be starting. Check the logs if this persists."),
+            NodeError::UnsupportedPlatform(_) => Some("Use Docker mode on this platform."),
+            NodeError::Io(_) => Some("Check that the Koinos data directory exists and is writable."),
+            NodeError::Network(_) => Some("Check your internet connection and try again."),
+            NodeError::Serialization(_) => Some("A file in ~/.koinos may be damaged. Check the debug console for details."),
+            NodeError::Process(_) => Some("See the debug console for the command output."),
+            NodeError::Cancelled(_) | NodeError::InvalidInput(_) | NodeError::Other(_) => None,
+        }
+    }
+
+    /// Prefix the message with what was being done, keeping the kind of error
+    pub fn context(self, context: &str) -> Self {
+        let message = format!("{}: {}", context, self.message());
+        self.with_message(message)
+    }
+
+    fn with_message(self, message: String) -> Self {
+        match self {
+            NodeError::DockerMissing(_) => NodeError::DockerMissing(message),
+            NodeError::DaemonDown(_) => NodeError::DaemonDown(message),
+            NodeError::ComposeMissing(_) => NodeError::ComposeMissing(message),
+            NodeError::NotInitialized(_) => NodeError::NotInitialized(message),
+            NodeError::NodeRunning(_) => NodeError::NodeRunning(message),
+            NodeError::DiskFull(_) => NodeError::DiskFull(message),
+            NodeError::SnapshotCorrupt(_) => NodeError::SnapshotCorrupt(message),
+            NodeError::SnapshotUnavailable(_) => NodeError::SnapshotUnavailable(message),
+            NodeError::DownloadInterrupted(_) => NodeError::DownloadInterrupted(message),
+            NodeError::Cancelled(_) => NodeError::Cancelled(message),
+            NodeError::RpcUnavailable(_) => NodeError::RpcUnavailable(message),
+            NodeError::UnsupportedPlatform(_) => NodeError::UnsupportedPlatform(message),
+            NodeError::InvalidInput(_) => NodeError::InvalidInput(message),
+            NodeError::Io(_) => NodeError::Io(message),
+            NodeError::Network(_) => NodeError::Network(message),
+            NodeError::Serialization(_) => NodeError::Serialization(message),
+            NodeError::Process(_) => NodeError::Process(message),
+            NodeError::Other(_) => NodeError::Other(message),
+        }
+    }
+}
+
+impl fmt::Display for NodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for NodeError {}
+
+impl Serialize for NodeError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("NodeError", 3)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", self.message())?;
+        state.serialize_field("hint", &self.hint())?;
+        state.end()
+    }
+}
+
+impl From<std::io::Error> for NodeError {
+    fn from(e: std::io::Error) -> Self {
+        // ENOSPC; ErrorKind::StorageFull isn't stable on older toolchains
+        if e.raw_os_error() == Some(28) {
+            NodeError::DiskFull(e.to_string())
+        } else {
+            NodeError::Io(e.to_string())
+        }
+    }
+}
+
+impl From<reqwest::Error> for NodeError {
+    fn from(e: reqwest::Error) -> Self {
+        NodeError::Network(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for NodeError {
+    fn from(e: serde_json::Error) -> Self {
+        NodeError::Serialization(e.to_string())
+    }
+}
+
+impl From<tokio::task::JoinError> for NodeError {
+    fn from(e: tokio::task::JoinError) -> Self {
+        NodeError::Other(e.to_string())
+    }
+}
+
@@ -0,0 +1,281 @@
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::sync::CancellationToken;
+use crate::node_manager::NodeManager;
+use crate::node_lifecycle::NodePhase;
+use crate::download_control::DownloadControl;
+use crate::jobs::{JobRegistry, JobState};
+use crate::logger::{log_debug, log_info, log_warn};
+use crate::error::NodeError;
+
+const DEFAULT_BIND_ADDRESS: &str = "127.0.0.1:9465";
+// Requests are a single GET line plus headers
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+
+const PHASES: &[NodePhase] = &[
+    NodePhase::Stopped,
+    NodePhase::Starting,
+    NodePhase::Syncing,
+    NodePhase::Running,
+    NodePhase::Stopping,
+    NodePhase::Error,
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExporterSettings {
+    pub enabled: bool,
+    pub bind_address: String,
+}
+
+impl Default for ExporterSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind_address: DEFAULT_BIND_ADDRESS.to_string(),
+        }
+    }
+}
+
+impl ExporterSettings {
+    pub fn address(&self) -> Result<SocketAddr, NodeError> {
+        self.bind_address.trim().parse().map_err(|_| {
+            NodeError::InvalidInput(format!(
+                "Invalid bind address {:?}, expected ip:port",
+                self.bind_address
+            ))
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExporterStatus {
+    pub settings: ExporterSettings,
+    /// Address actually listening, if the exporter is on
+    pub listening: Option<String>,
+}
+
+struct Server {
+    address: SocketAddr,
+    stop: CancellationToken,
+}
+
+/// Serves the app's view of the node at `/metrics` in the Prometheus text format,
+/// so desktop nodes can be scraped like any other host.
+#[derive(Clone)]
+pub struct Exporter {
+    node_manager: Arc<tokio::sync::Mutex<NodeManager>>,
+    download: DownloadControl,
+    jobs: JobRegistry,
+    server: Arc<Mutex<Option<Server>>>,
+}
+
+impl Exporter {
+    pub fn new(
+        node_manager: Arc<tokio::sync::Mutex<NodeManager>>,
+        download: DownloadControl,
+        jobs: JobRegistry,
+    ) -> Self {
+        Self {
+            node_manager,
+            download,
+            jobs,
+            server: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn listening(&self) -> Option<SocketAddr> {
+        self.server.lock().unwrap().as_ref().map(|s| s.address)
+    }
+
+    /// Start, stop or rebind the listener to match `settings`
+    pub async fn apply(&self, settings: &ExporterSettings) -> Result<(), NodeError> {
+        let address = settings.address()?;
+        if settings.enabled && self.listening() == Some(address) {
+            return Ok(());
+        }
+        self.stop();
+        if !settings.enabled {
+            return Ok(());
+        }
+
+        let listener = TcpListener::bind(address)
+            .await
+            .map_err(|e| NodeError::from(e).context(&format!("Failed to bind metrics exporter to {}", address)))?;
+        if !address.ip().is_loopback() {
+            log_warn("Metrics exporter is reachable from other hosts", Some(&address.to_string()));
+        }
+        log_info("Metrics exporter listening", Some(&address.to_string()));
+
+        let stop = CancellationToken::new();
+        *self.server.lock().unwrap() = Some(Server { address, stop: stop.clone() });
+
+        let exporter = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let stream = tokio::select! {
+                    _ = stop.cancelled() => break,
+                    accepted = listener.accept() => accepted,
+                };
+                match stream {
+                    Ok((stream, _)) => {
+                        let exporter = exporter.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) = exporter.serve(stream).await {
+                                log_debug("Metrics exporter request failed", Some(&e.to_string()));
+                            }
+                        });
+                    }
+                    Err(e) => log_debug("Metrics exporter accept failed", Some(&e.to_string())),
+                }
+            }
+        });
+        Ok(())
+    }
+
+    fn stop(&self) {
+        if let Some(server) = self.server.lock().unwrap().take() {
+            server.stop.cancel();
+            log_info("Metrics exporter stopped", Some(&server.address.to_string()));
+        }
+    }
+
+    async fn serve(&self, mut stream: TcpStream) -> Result<(), NodeError> {
+        let mut request = Vec::new();
+        let mut buf = [0u8; 1024];
+        while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < MAX_REQUEST_BYTES {
+            let n = stream.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            request.extend_from_slice(&buf[..n]);
+        }
+
+        let request = String::from_utf8_lossy(&request);
+        let mut request_line = request.lines().next().unwrap_or_default().split_whitespace();
+        let (method, path) = (request_line.next(), request_line.next());
+
+        let (status, body) = match (method, path.map(|p| p.split('?').next().unwrap_or(p))) {
+            (Some("GET"), Some("/metrics")) => ("200 OK", self.render().await),
+            (Some("GET"), _) => ("404 Not Found", "Not found, try /metrics\n".to_string()),
+            _ => ("405 Method Not Allowed", "Only GET is supported\n".to_string()),
+        };
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).await?;
+        stream.shutdown().await.ok();
+        Ok(())
+    }
+
+    async fn render(&self) -> String {
+        let manager = self.node_manager.lock().await.clone();
+        let mut out = Exposition::default();
+
+        // Cached by the status loop, so a scrape doesn't probe the node again
+        let status = manager.status.lock().unwrap().clone();
+        let phase = manager.lifecycle.phase();
+        out.family("koinos_node_phase", "gauge", "Lifecycle phase of the node, 1 for the current one");
+        for p in PHASES {
+            out.sample("koinos_node_phase", &[("phase", p.as_str())], (*p == phase) as u8 as f64);
+        }
+        out.gauge("koinos_node_sync_progress_percent", "Sync progress", status.sync_progress as f64);
+        out.gauge("koinos_node_current_block", "Height of the local head block", status.current_block as f64);
+        out.gauge("koinos_node_target_block", "Estimated network head height", status.target_block as f64);
+        out.gauge("koinos_node_peers", "Connected p2p peers", status.peers_count as f64);
+        if let Some(rate) = status.blocks_per_sec {
+            out.gauge("koinos_node_blocks_per_second", "Blocks applied per second", rate);
+        }
+        if let Some(lag) = status.head_lag_seconds {
+            out.gauge("koinos_node_head_lag_seconds", "Age of the head block", lag as f64);
+        }
+        if let Some(eta) = status.eta_seconds {
+            out.gauge("koinos_node_sync_eta_seconds", "Estimated time until synced", eta as f64);
+        }
+
+        if let Ok(usage) = manager.get_resource_usage().await {
+            out.gauge("koinos_node_cpu_percent", "Share of host CPU used by the node", usage.cpu_percent as f64);
+            out.gauge("koinos_node_memory_used_bytes", "Memory used by the node", usage.memory_mb as f64 * MIB);
+            out.gauge("koinos_node_memory_total_bytes", "Host memory", usage.memory_total_mb as f64 * MIB);
+            out.gauge("koinos_node_disk_used_bytes", "Used space on the data volume", usage.disk_used_gb as f64 * GIB);
+            out.gauge("koinos_node_disk_total_bytes", "Size of the data volume", usage.disk_total_gb as f64 * GIB);
+
+            out.family("koinos_node_service_cpu_percent", "gauge", "Share of host CPU used by each service");
+            for s in &usage.services {
+                out.sample("koinos_node_service_cpu_percent", &[("service", &s.service)], s.cpu_percent as f64);
+            }
+            out.family("koinos_node_service_memory_bytes", "gauge", "Memory used by each service");
+            for s in &usage.services {
+                out.sample("koinos_node_service_memory_bytes", &[("service", &s.service)], s.memory_mb as f64 * MIB);
+            }
+        }
+
+        let totals = manager.state_manager.lock().unwrap().get_state().clone();
+        out.counter("koinos_node_uptime_seconds_total", "Time the node has been running", totals.total_uptime_seconds as f64);
+        out.counter("koinos_node_blocks_validated_total", "Blocks validated by this node", totals.blocks_validated as f64);
+
+        let download = self.download.state();
+        out.gauge("koinos_node_snapshot_download_active", "Whether a snapshot download is running", download.active as u8 as f64);
+        out.gauge("koinos_node_snapshot_download_paused", "Whether the snapshot download is paused", download.paused as u8 as f64);
+        let progress = self
+            .jobs
+            .list()
+            .into_iter()
+            .filter(|j| j.kind == "download_snapshot" && j.state == JobState::Running)
+            .filter_map(|j| j.progress)
+            .next_back();
+        if let Some(progress) = progress {
+            out.gauge("koinos_node_snapshot_download_progress_percent", "Snapshot download progress", progress as f64);
+        }
+
+        out.family("koinos_node_service_up", "gauge", "Whether each microservice is running");
+        for (service, up) in manager.service_statuses().await {
+            out.sample("koinos_node_service_up", &[("service", service)], up as u8 as f64);
+        }
+
+        out.text
+    }
+}
+
+const MIB: f64 = 1024.0 * 1024.0;
+const GIB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+#[derive(Default)]
+struct Exposition {
+    text: String,
+}
+
+impl Exposition {
+    fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.text, "# HELP {} {}", name, help);
+        let _ = writeln!(self.text, "# TYPE {} {}", name, kind);
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        if labels.is_empty() {
+            let _ = writeln!(self.text, "{} {}", name, value);
+            return;
+        }
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|(k, v)| format!("{}=\"{}\"", k, v.replace('\\', "\\\\").replace('"', "\\\"")))
+            .collect();
+        let _ = writeln!(self.text, "{}{{{}}} {}", name, labels.join(","), value);
+    }
+
+    fn gauge(&mut self, name: &str, help: &str, value: f64) {
+        self.family(name, "gauge", help);
+        self.sample(name, &[], value);
+    }
+
+    fn counter(&mut self, name: &str, help: &str, value: f64) {
+        self.family(name, "counter", help);
+        self.sample(name, &[], value);
+    }
+}
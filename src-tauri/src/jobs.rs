@@ -0,0 +1,260 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+use tokio_util::sync::CancellationToken;
+use crate::logger::{log_info, log_warn};
+use crate::error::NodeError;
+
+// Finished jobs kept around for list_jobs/get_job
+const MAX_FINISHED_JOBS: usize = 50;
+
+// Operations on the node's services and data. Only one of them runs at a time.
+const EXCLUSIVE: &[&str] = &[
+    "setup_node", "start_node", "stop_node", "restart_node", "migrate_data_dir", "download_snapshot",
+];
+
+// Jobs that can't stop halfway without leaving the node in an unknown state
+const UNCANCELLABLE: &[&str] = &["setup_node", "migrate_data_dir"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+/// Emitted as `job_updated` whenever a job changes
+#[derive(Debug, Clone, Serialize)]
+pub struct JobInfo {
+    pub id: u64,
+    pub kind: String,
+    pub state: JobState,
+    pub progress: Option<f32>,
+    pub message: Option<String>,
+    pub error: Option<NodeError>,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+}
+
+struct JobEntry {
+    info: JobInfo,
+    token: CancellationToken,
+}
+
+#[derive(Default)]
+struct Registry {
+    next_id: u64,
+    jobs: BTreeMap<u64, JobEntry>,
+    /// The exclusive operation in progress
+    operation: Option<String>,
+}
+
+/// Long-running operations (setup, snapshot download, start) run here in the background
+/// so commands can return a job id right away.
+#[derive(Clone)]
+pub struct JobRegistry {
+    app: AppHandle,
+    registry: Arc<Mutex<Registry>>,
+}
+
+/// Given to a job so it can report progress and notice when it is cancelled
+#[derive(Clone)]
+pub struct JobHandle {
+    id: u64,
+    jobs: JobRegistry,
+    token: CancellationToken,
+}
+
+/// Holds the exclusive operation slot until dropped
+pub struct OperationGuard {
+    jobs: JobRegistry,
+}
+
+impl Drop for OperationGuard {
+    fn drop(&mut self) {
+        self.jobs.registry.lock().unwrap().operation = None;
+    }
+}
+
+impl JobHandle {
+    /// Fires when the job is cancelled. The job is expected to clean up and return.
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    pub fn set_progress(&self, progress: f32, message: Option<String>) {
+        self.jobs.update(self.id, |info| {
+            info.progress = Some(progress);
+            if message.is_some() {
+                info.message = message;
+            }
+        });
+    }
+}
+
+impl JobRegistry {
+    pub fn new(app: AppHandle) -> Self {
+        Self {
+            app,
+            registry: Arc::new(Mutex::new(Registry::default())),
+        }
+    }
+
+    /// Run `job` in the background and return its id. If a job of the same kind is
+    /// already running, its id is returned instead of starting another one. Exclusive
+    /// jobs fail to start while another exclusive operation is in progress.
+    pub fn spawn<F, Fut>(&self, kind: &str, job: F) -> Result<u64, NodeError>
+    where
+        F: FnOnce(JobHandle) -> Fut,
+        Fut: Future<Output = Result<(), NodeError>> + Send + 'static,
+    {
+        let (handle, operation) = {
+            let mut registry = self.registry.lock().unwrap();
+            if let Some(running) = registry
+                .jobs
+                .values()
+                .find(|j| j.info.kind == kind && j.info.state == JobState::Running)
+            {
+                log_info("Job already running", Some(&format!("{} (job {})", kind, running.info.id)));
+                return Ok(running.info.id);
+            }
+            let operation = if EXCLUSIVE.contains(&kind) {
+                Some(self.claim(&mut registry, kind)?)
+            } else {
+                None
+            };
+
+            registry.next_id += 1;
+            let id = registry.next_id;
+            let token = CancellationToken::new();
+            registry.jobs.insert(id, JobEntry {
+                info: JobInfo {
+                    id,
+                    kind: kind.to_string(),
+                    state: JobState::Running,
+                    progress: None,
+                    message: None,
+                    error: None,
+                    started_at: chrono::Local::now().to_rfc3339(),
+                    finished_at: None,
+                },
+                token: token.clone(),
+            });
+            (JobHandle { id, jobs: self.clone(), token }, operation)
+        };
+
+        let id = handle.id;
+        log_info("Job started", Some(&format!("{} (job {})", kind, id)));
+        self.emit(id);
+
+        let token = handle.token.clone();
+        let jobs = self.clone();
+        let future = job(handle);
+        tauri::async_runtime::spawn(async move {
+            // A cancelled job still runs to the end, so its cleanup is done before it
+            // is reported as cancelled
+            let result = future.await;
+            drop(operation);
+
+            let cancelled = matches!(result, Err(NodeError::Cancelled(_)))
+                || (token.is_cancelled() && result.is_err());
+            jobs.update(id, |info| {
+                info.finished_at = Some(chrono::Local::now().to_rfc3339());
+                match &result {
+                    Ok(()) => {
+                        info.state = JobState::Completed;
+                        info.progress = Some(100.0);
+                    }
+                    Err(_) if cancelled => info.state = JobState::Cancelled,
+                    Err(e) => {
+                        info.state = JobState::Failed;
+                        info.error = Some(e.clone());
+                    }
+                }
+            });
+            match result {
+                Ok(()) => log_info("Job completed", Some(&format!("job {}", id))),
+                Err(e) => log_warn("Job did not complete", Some(&format!("job {}: {}", id, e))),
+            }
+            jobs.prune();
+        });
+
+        Ok(id)
+    }
+
+    /// Claim the exclusive operation slot for an operation that doesn't run as a job
+    pub fn exclusive(&self, operation: &str) -> Result<OperationGuard, NodeError> {
+        let mut registry = self.registry.lock().unwrap();
+        self.claim(&mut registry, operation)
+    }
+
+    fn claim(&self, registry: &mut Registry, operation: &str) -> Result<OperationGuard, NodeError> {
+        if let Some(current) = &registry.operation {
+            return Err(NodeError::InvalidInput(format!(
+                "Can't run {} while {} is in progress",
+                operation, current
+            )));
+        }
+        registry.operation = Some(operation.to_string());
+        Ok(OperationGuard { jobs: self.clone() })
+    }
+
+    pub fn list(&self) -> Vec<JobInfo> {
+        let registry = self.registry.lock().unwrap();
+        registry.jobs.values().map(|j| j.info.clone()).collect()
+    }
+
+    pub fn get(&self, id: u64) -> Option<JobInfo> {
+        let registry = self.registry.lock().unwrap();
+        registry.jobs.get(&id).map(|j| j.info.clone())
+    }
+
+    pub fn cancel(&self, id: u64) -> Result<(), NodeError> {
+        let registry = self.registry.lock().unwrap();
+        let job = registry.jobs.get(&id).ok_or_else(|| NodeError::InvalidInput(format!("No job with id {}", id)))?;
+        if job.info.state != JobState::Running {
+            return Err(NodeError::InvalidInput(format!("Job {} is not running", id)));
+        }
+        if UNCANCELLABLE.contains(&job.info.kind.as_str()) {
+            return Err(NodeError::InvalidInput(format!("{} can't be cancelled", job.info.kind)));
+        }
+        job.token.cancel();
+        log_info("Job cancel requested", Some(&format!("{} (job {})", job.info.kind, id)));
+        Ok(())
+    }
+
+    fn update(&self, id: u64, change: impl FnOnce(&mut JobInfo)) {
+        {
+            let mut registry = self.registry.lock().unwrap();
+            match registry.jobs.get_mut(&id) {
+                Some(job) => change(&mut job.info),
+                None => return,
+            }
+        }
+        self.emit(id);
+    }
+
+    fn emit(&self, id: u64) {
+        if let Some(info) = self.get(id) {
+            self.app.emit("job_updated", info).ok();
+        }
+    }
+
+    // Drop the oldest finished jobs beyond the limit
+    fn prune(&self) {
+        let mut registry = self.registry.lock().unwrap();
+        let finished: Vec<u64> = registry
+            .jobs
+            .values()
+            .filter(|j| j.info.state != JobState::Running)
+            .map(|j| j.info.id)
+            .collect();
+        for id in finished.iter().take(finished.len().saturating_sub(MAX_FINISHED_JOBS)) {
+            registry.jobs.remove(id);
+        }
+    }
+}
@@ -0,0 +1,503 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use crate::logger::log_debug;
+use crate::error::NodeError;
+
+/// Public mainnet API, used as the reference for the network head
+pub const MAINNET_ENDPOINT: &str = "https://api.koinos.io";
+
+#[derive(Debug, Clone)]
+pub enum RpcError {
+    /// The request didn't get an answer (connection refused, timeout, ...)
+    Transport(String),
+    Http(u16),
+    /// The service answered with a JSON-RPC error
+    Rpc { code: i64, message: String },
+    Decode(String),
+}
+
+impl RpcError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            RpcError::Transport(_) => true,
+            RpcError::Http(status) => *status >= 500 || *status == 429,
+            RpcError::Rpc { .. } | RpcError::Decode(_) => false,
+        }
+    }
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RpcError::Transport(e) => write!(f, "{}", e),
+            RpcError::Http(status) => write!(f, "HTTP {}", status),
+            RpcError::Rpc { code, message } => write!(f, "RPC error {}: {}", code, message),
+            RpcError::Decode(e) => write!(f, "Unexpected response: {}", e),
+        }
+    }
+}
+
+impl From<RpcError> for NodeError {
+    fn from(e: RpcError) -> Self {
+        match e {
+            RpcError::Decode(_) => NodeError::Serialization(e.to_string()),
+            _ => NodeError::RpcUnavailable(e.to_string()),
+        }
+    }
+}
+
+// Koinos encodes 64-bit integers as JSON strings
+fn u64_from_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Number {
+        Text(String),
+        Int(u64),
+    }
+    match Number::deserialize(deserializer)? {
+        Number::Text(text) => text.parse().map_err(serde::de::Error::custom),
+        Number::Int(value) => Ok(value),
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BlockTopology {
+    #[serde(default)]
+    pub id: String,
+    #[serde(default, deserialize_with = "u64_from_string")]
+    pub height: u64,
+    #[serde(default)]
+    pub previous: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeadInfo {
+    #[serde(default)]
+    pub head_topology: BlockTopology,
+    #[serde(default, deserialize_with = "u64_from_string")]
+    pub last_irreversible_block: u64,
+    /// Milliseconds since the Unix epoch
+    #[serde(default, deserialize_with = "u64_from_string")]
+    pub head_block_time: u64,
+    #[serde(default)]
+    pub head_state_merkle_root: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChainId {
+    pub chain_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GossipStatus {
+    #[serde(default)]
+    pub enabled: bool,
+}
+
+/// A connected peer as reported by the p2p service. Older p2p versions only
+/// return the multiaddress, so everything else is optional.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PeerEntry {
+    Address(String),
+    Detailed {
+        #[serde(default)]
+        id: Option<String>,
+        #[serde(default, alias = "addr")]
+        address: Option<String>,
+        /// "inbound" or "outbound"
+        #[serde(default)]
+        direction: Option<String>,
+        /// Unix seconds
+        #[serde(default)]
+        connected_since: Option<u64>,
+    },
+}
+
+/// Block and receipt are passed through as the node returns them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockItem {
+    pub block_id: String,
+    #[serde(deserialize_with = "u64_from_string")]
+    pub block_height: u64,
+    #[serde(default)]
+    pub block: Option<Value>,
+    #[serde(default)]
+    pub receipt: Option<Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingTransaction {
+    pub transaction: Value,
+    #[serde(default, deserialize_with = "u64_from_string")]
+    pub disk_storage_used: u64,
+    #[serde(default, deserialize_with = "u64_from_string")]
+    pub network_bandwidth_used: u64,
+    #[serde(default, deserialize_with = "u64_from_string")]
+    pub compute_bandwidth_used: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountHistoryEntry {
+    #[serde(default, deserialize_with = "u64_from_string")]
+    pub seq_num: u64,
+    #[serde(default)]
+    pub trx: Option<Value>,
+    #[serde(default)]
+    pub block: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct RpcErrorBody {
+    code: i64,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct RpcResponse {
+    #[serde(default)]
+    id: Option<u64>,
+    #[serde(default)]
+    result: Option<Value>,
+    #[serde(default)]
+    error: Option<RpcErrorBody>,
+}
+
+impl RpcResponse {
+    fn into_result(self) -> Result<Value, RpcError> {
+        match (self.result, self.error) {
+            (_, Some(error)) => Err(RpcError::Rpc { code: error.code, message: error.message }),
+            (Some(result), None) => Ok(result),
+            (None, None) => Err(RpcError::Decode("response has neither result nor error".to_string())),
+        }
+    }
+}
+
+// Servers may answer a batch in any order; put the answers back in request order
+fn match_batch(ids: &[u64], responses: Vec<RpcResponse>) -> Vec<Result<Value, RpcError>> {
+    let mut by_id: std::collections::HashMap<u64, RpcResponse> = responses
+        .into_iter()
+        .filter_map(|r| r.id.map(|id| (id, r)))
+        .collect();
+    ids.iter()
+        .map(|id| match by_id.remove(id) {
+            Some(response) => response.into_result(),
+            None => Err(RpcError::Decode(format!("no response for request {}", id))),
+        })
+        .collect()
+}
+
+fn decode<T: DeserializeOwned>(value: Value) -> Result<T, RpcError> {
+    serde_json::from_value(value).map_err(|e| RpcError::Decode(e.to_string()))
+}
+
+/// Client for a Koinos JSON-RPC endpoint
+#[derive(Clone)]
+pub struct KoinosRpc {
+    client: reqwest::Client,
+    endpoint: String,
+    timeout: Duration,
+    retries: u32,
+    next_id: Arc<AtomicU64>,
+}
+
+impl KoinosRpc {
+    pub fn new(endpoint: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            timeout: Duration::from_secs(5),
+            retries: 1,
+            next_id: Arc::new(AtomicU64::new(1)),
+        }
+    }
+
+    /// The node's JSON-RPC service on this machine
+    pub fn local(port: u16) -> Self {
+        Self::new(&format!("http://127.0.0.1:{}", port))
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Extra attempts after a transport error or a 5xx/429 answer
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    fn request(&self, method: &str, params: Value) -> (u64, Value) {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let body = serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params,
+            "id": id
+        });
+        (id, body)
+    }
+
+    async fn post(&self, body: &Value) -> Result<Value, RpcError> {
+        let mut attempt = 0;
+        loop {
+            let result = self.post_once(body).await;
+            match result {
+                Err(e) if e.is_retryable() && attempt < self.retries => {
+                    attempt += 1;
+                    log_debug("RPC request failed, retrying", Some(&format!("{}: {}", self.endpoint, e)));
+                    tokio::time::sleep(Duration::from_millis(500 * attempt as u64)).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn post_once(&self, body: &Value) -> Result<Value, RpcError> {
+        let response = self
+            .client
+            .post(&self.endpoint)
+            .timeout(self.timeout)
+            .header("Content-Type", "application/json")
+            .body(body.to_string())
+            .send()
+            .await
+            .map_err(|e| RpcError::Transport(e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(RpcError::Http(response.status().as_u16()));
+        }
+        let text = response
+            .text()
+            .await
+            .map_err(|e| RpcError::Transport(e.to_string()))?;
+        serde_json::from_str(&text).map_err(|e| RpcError::Decode(e.to_string()))
+    }
+
+    /// Call `method` and decode its result
+    pub async fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T, RpcError> {
+        let (_, body) = self.request(method, params);
+        let response: RpcResponse = decode(self.post(&body).await?)?;
+        decode(response.into_result()?)
+    }
+
+    /// Send several calls in one request. Results come back in the order of `calls`;
+    /// one failing call doesn't fail the others.
+    pub async fn batch(&self, calls: &[(&str, Value)]) -> Result<Vec<Result<Value, RpcError>>, RpcError> {
+        let (ids, bodies): (Vec<u64>, Vec<Value>) = calls
+            .iter()
+            .map(|(method, params)| self.request(method, params.clone()))
+            .unzip();
+        let responses: Vec<RpcResponse> = decode(self.post(&Value::Array(bodies)).await?)?;
+        Ok(match_batch(&ids, responses))
+    }
+
+    pub async fn head_info(&self) -> Result<HeadInfo, RpcError> {
+        self.call("chain.get_head_info", serde_json::json!({})).await
+    }
+
+    pub async fn chain_id(&self) -> Result<ChainId, RpcError> {
+        self.call("chain.get_chain_id", serde_json::json!({})).await
+    }
+
+    /// Up to `count` blocks starting at `height`, on the chain ending in `head_block_id`
+    pub async fn blocks_by_height(
+        &self,
+        head_block_id: &str,
+        height: u64,
+        count: u32,
+        with_contents: bool,
+    ) -> Result<Vec<BlockItem>, RpcError> {
+        #[derive(Deserialize)]
+        struct Blocks {
+            #[serde(default)]
+            block_items: Vec<BlockItem>,
+        }
+        let blocks: Blocks = self
+            .call(
+                "block_store.get_blocks_by_height",
+                serde_json::json!({
+                    "head_block_id": head_block_id,
+                    "ancestor_start_height": height.to_string(),
+                    "num_blocks": count,
+                    "return_block": with_contents,
+                    "return_receipt": with_contents
+                }),
+            )
+            .await?;
+        Ok(blocks.block_items)
+    }
+
+    pub async fn gossip_status(&self) -> Result<GossipStatus, RpcError> {
+        self.call("p2p.get_gossip_status", serde_json::json!({})).await
+    }
+
+    pub async fn peers(&self) -> Result<Vec<PeerEntry>, RpcError> {
+        #[derive(Deserialize)]
+        struct Peers {
+            #[serde(default)]
+            peers: Vec<PeerEntry>,
+        }
+        let peers: Peers = self.call("p2p.get_peers", serde_json::json!({})).await?;
+        Ok(peers.peers)
+    }
+
+    pub async fn pending_transactions(&self, limit: u32) -> Result<Vec<PendingTransaction>, RpcError> {
+        #[derive(Deserialize)]
+        struct Pending {
+            #[serde(default)]
+            pending_transactions: Vec<PendingTransaction>,
+        }
+        let pending: Pending = self
+            .call(
+                "mempool.get_pending_transactions",
+                serde_json::json!({ "limit": limit.to_string() }),
+            )
+            .await?;
+        Ok(pending.pending_transactions)
+    }
+
+    /// Most recent first unless `ascending`
+    pub async fn account_history(
+        &self,
+        address: &str,
+        limit: u32,
+        ascending: bool,
+    ) -> Result<Vec<AccountHistoryEntry>, RpcError> {
+        #[derive(Deserialize)]
+        struct History {
+            #[serde(default)]
+            values: Vec<AccountHistoryEntry>,
+        }
+        let history: History = self
+            .call(
+                "account_history.get_account_history",
+                serde_json::json!({
+                    "address": address,
+                    "limit": limit.to_string(),
+                    "ascending": ascending,
+                    "irreversible": false
+                }),
+            )
+            .await?;
+        Ok(history.values)
+    }
+}
+
+/// Head, chain id and gossip state of the local node, fetched in one batch
+#[derive(Debug, Clone, Serialize)]
+pub struct ChainInfo {
+    pub endpoint: String,
+    pub head: Option<HeadInfo>,
+    pub chain_id: Option<String>,
+    pub gossip_enabled: Option<bool>,
+}
+
+pub async fn chain_info(rpc: &KoinosRpc) -> Result<ChainInfo, RpcError> {
+    let batch = rpc
+        .batch(&[
+            ("chain.get_head_info", serde_json::json!({})),
+            ("chain.get_chain_id", serde_json::json!({})),
+            ("p2p.get_gossip_status", serde_json::json!({})),
+        ])
+        .await;
+
+    let results = match batch {
+        Ok(results) => results,
+        // Not every endpoint accepts batches; ask one at a time
+        Err(RpcError::Http(_)) | Err(RpcError::Rpc { .. }) | Err(RpcError::Decode(_)) => {
+            let head = rpc.head_info().await?;
+            return Ok(ChainInfo {
+                endpoint: rpc.endpoint().to_string(),
+                head: Some(head),
+                chain_id: rpc.chain_id().await.ok().map(|c| c.chain_id),
+                gossip_enabled: rpc.gossip_status().await.ok().map(|g| g.enabled),
+            });
+        }
+        Err(e) => return Err(e),
+    };
+
+    let mut results = results.into_iter();
+    let mut next = || results.next().and_then(|r| r.ok());
+    Ok(ChainInfo {
+        endpoint: rpc.endpoint().to_string(),
+        head: next().and_then(|v| decode(v).ok()),
+        chain_id: next().and_then(|v| decode::<ChainId>(v).ok()).map(|c| c.chain_id),
+        gossip_enabled: next().and_then(|v| decode::<GossipStatus>(v).ok()).map(|g| g.enabled),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn response(value: Value) -> RpcResponse {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn heights_decode_from_strings_and_numbers() {
+        let text: BlockTopology = serde_json::from_value(json!({ "id": "0x1", "height": "12345" })).unwrap();
+        assert_eq!(text.height, 12345);
+        let number: BlockTopology = serde_json::from_value(json!({ "id": "0x1", "height": 678 })).unwrap();
+        assert_eq!(number.height, 678);
+        let missing: BlockTopology = serde_json::from_value(json!({ "id": "0x1" })).unwrap();
+        assert_eq!(missing.height, 0);
+        assert!(serde_json::from_value::<BlockTopology>(json!({ "height": "12a" })).is_err());
+    }
+
+    #[test]
+    fn error_wins_over_result() {
+        let both = response(json!({ "id": 1, "result": {}, "error": { "code": -32601, "message": "no such method" } }));
+        match both.into_result() {
+            Err(RpcError::Rpc { code, message }) => {
+                assert_eq!(code, -32601);
+                assert_eq!(message, "no such method");
+            }
+            other => panic!("expected an RPC error, got {:?}", other),
+        }
+
+        let result = response(json!({ "id": 1, "result": { "ok": true } }));
+        assert_eq!(result.into_result().unwrap(), json!({ "ok": true }));
+
+        let empty = response(json!({ "id": 1 }));
+        assert!(matches!(empty.into_result(), Err(RpcError::Decode(_))));
+    }
+
+    #[test]
+    fn batch_answers_follow_request_order() {
+        let responses = vec![
+            response(json!({ "id": 7, "result": "seven" })),
+            response(json!({ "id": 5, "error": { "code": 1, "message": "five failed" } })),
+            response(json!({ "result": "no id" })),
+            response(json!({ "id": 6, "result": "six" })),
+        ];
+        let results = match_batch(&[5, 6, 7, 8], responses);
+
+        assert_eq!(results.len(), 4);
+        assert!(matches!(&results[0], Err(RpcError::Rpc { code: 1, .. })));
+        assert_eq!(results[1].as_ref().unwrap(), "six");
+        assert_eq!(results[2].as_ref().unwrap(), "seven");
+        assert!(matches!(&results[3], Err(RpcError::Decode(_))));
+    }
+
+    #[test]
+    fn only_transient_failures_are_retried() {
+        assert!(RpcError::Transport("connection refused".to_string()).is_retryable());
+        assert!(RpcError::Http(502).is_retryable());
+        assert!(RpcError::Http(429).is_retryable());
+        assert!(!RpcError::Http(404).is_retryable());
+        assert!(!RpcError::Rpc { code: -32601, message: String::new() }.is_retryable());
+        assert!(!RpcError::Decode(String::new()).is_retryable());
+    }
+}
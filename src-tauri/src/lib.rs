@@ -2,8 +2,48 @@ mod node_manager;
 mod state_manager;
 mod auto_installer;
 mod logger;
+mod container_runtime;
+mod native_installer;
+mod supervisor;
+mod snapshot_verify;
+mod snapshot_stream;
+mod snapshot_sources;
+mod snapshot_downloader;
+mod download_control;
+mod jobs;
+mod error;
+mod node_lifecycle;
+mod koinos_rpc;
+mod sync_tracker;
+mod consistency;
+mod peers;
+mod metrics;
+mod exporter;
+mod process_stats;
+mod telemetry;
+mod accounting;
+mod settings;
+mod data_migration;
+mod node_config;
+mod roles;
 
-use node_manager::{NodeManager, NodeStatus, SystemRequirements, ResourceUsage};
+use node_manager::{NodeManager, NodeMode, NodeStatus, SystemRequirements, ResourceUsage};
+use container_runtime::{RuntimeInfo, RuntimeKind};
+use jobs::{JobInfo, JobRegistry};
+use error::NodeError;
+use node_lifecycle::{NodePhase, NodeStateInfo, PhaseTransition};
+use peers::PeerReport;
+use exporter::{Exporter, ExporterSettings, ExporterStatus};
+use settings::{Settings, SettingsStore, SettingsUpdate};
+use node_config::{ConfigChange, NodeConfig};
+use roles::{NodeRole, NodeServices, RoleInfo};
+use accounting::{Accounting, AccountingReport};
+use telemetry::{History, Resolution, TelemetryStore};
+use metrics::{MetricSeries, MetricsEndpoint, MetricsStore, ScrapeStatus};
+use consistency::{ConsistencyChecker, ConsistencyReport};
+use koinos_rpc::{AccountHistoryEntry, BlockItem, ChainInfo, PendingTransaction};
+use download_control::{DownloadControl, DownloadLimits, DownloadState};
+use snapshot_sources::{IndexFormat, MirrorProbe, SnapshotMirror, SnapshotSources};
 use auto_installer::AutoInstaller;
 use std::sync::Arc;
 use tauri::{Emitter, Manager, State};
@@ -11,126 +51,493 @@ use tokio::sync::Mutex;
 
 struct AppState {
     node_manager: Arc<Mutex<NodeManager>>,
+    download: DownloadControl,
+    jobs: JobRegistry,
+    consistency: ConsistencyChecker,
+    metrics: MetricsStore,
+    exporter: Exporter,
+    telemetry: TelemetryStore,
+    accounting: Accounting,
 }
 
 #[tauri::command]
-async fn is_initialized(state: State<'_, AppState>) -> Result<bool, String> {
+async fn is_initialized(state: State<'_, AppState>) -> Result<bool, NodeError> {
     let manager = state.node_manager.lock().await;
     Ok(manager.is_initialized())
 }
 
 #[tauri::command]
-async fn check_system_requirements(state: State<'_, AppState>) -> Result<SystemRequirements, String> {
-    let manager = state.node_manager.lock().await;
+async fn check_system_requirements(state: State<'_, AppState>) -> Result<SystemRequirements, NodeError> {
+    let manager = state.node_manager.lock().await.clone();
     manager.check_system_requirements().await
 }
 
 #[tauri::command]
-async fn install_docker(state: State<'_, AppState>) -> Result<(), String> {
-    let manager = state.node_manager.lock().await;
+async fn install_docker(state: State<'_, AppState>) -> Result<(), NodeError> {
+    let manager = state.node_manager.lock().await.clone();
     manager.install_docker().await
 }
 
 #[tauri::command]
-async fn auto_install_requirements() -> Result<String, String> {
+async fn auto_install_requirements() -> Result<String, NodeError> {
     AutoInstaller::install_all_requirements().await
 }
 
+// Long operations run as jobs on a copy of the manager, so the lock is only held for
+// the clone and status polling keeps working while they run
+
 #[tauri::command]
-async fn setup_node(state: State<'_, AppState>) -> Result<(), String> {
-    let manager = state.node_manager.lock().await;
-    manager.setup_koinos().await
+async fn setup_node(state: State<'_, AppState>, data_path: Option<String>) -> Result<u64, NodeError> {
+    let manager = {
+        let mut manager = state.node_manager.lock().await;
+        if let Some(path) = data_path.map(std::path::PathBuf::from).filter(|p| *p != manager.data_path) {
+            // Existing data has to be moved along, which setup doesn't do
+            if manager.is_initialized() {
+                return Err(NodeError::InvalidInput(
+                    "The node is already set up, use migrate_data_dir to move its data".to_string(),
+                ));
+            }
+            manager.set_data_path(path)?;
+        }
+        manager.clone()
+    };
+    state.jobs.spawn("setup_node", |_job| async move {
+        manager.setup_koinos().await
+    })
 }
 
 #[tauri::command]
 async fn download_snapshot(
     state: State<'_, AppState>,
     window: tauri::Window,
-) -> Result<(), String> {
+) -> Result<u64, NodeError> {
+    let manager = state.node_manager.lock().await.clone();
+    let control = state.download.clone();
+    state.jobs.spawn("download_snapshot", |job| async move {
+        let _active = control.begin()?;
+        let token = job.token().clone();
+        let download = manager.download_snapshot(&control, move |progress| {
+            job.set_progress(progress.percent, None);
+            window.emit("download_progress", progress).ok();
+        });
+        tokio::pin!(download);
+        // Cancel through the control so the download stops its segments and keeps
+        // what is on disk
+        tokio::select! {
+            result = &mut download => result,
+            _ = token.cancelled() => {
+                control.cancel().ok();
+                download.await
+            }
+        }
+    })
+}
+
+#[tauri::command]
+async fn pause_download(state: State<'_, AppState>) -> Result<(), NodeError> {
+    state.download.pause()
+}
+
+#[tauri::command]
+async fn resume_download(state: State<'_, AppState>) -> Result<(), NodeError> {
+    state.download.resume()
+}
+
+#[tauri::command]
+async fn cancel_download(state: State<'_, AppState>) -> Result<(), NodeError> {
+    state.download.cancel()
+}
+
+#[tauri::command]
+async fn get_download_state(state: State<'_, AppState>) -> Result<DownloadState, NodeError> {
+    Ok(state.download.state())
+}
+
+#[tauri::command]
+async fn set_download_limits(state: State<'_, AppState>, limits: DownloadLimits) -> Result<(), NodeError> {
+    state.download.set_limits(limits)
+}
+
+#[tauri::command]
+async fn start_node(state: State<'_, AppState>) -> Result<u64, NodeError> {
+    let manager = state.node_manager.lock().await.clone();
+    state.jobs.spawn("start_node", |job| async move {
+        manager.start_node_until(job.token()).await
+    })
+}
+
+#[tauri::command]
+async fn stop_node(state: State<'_, AppState>) -> Result<(), NodeError> {
+    let _operation = state.jobs.exclusive("stop_node")?;
+    let manager = state.node_manager.lock().await.clone();
+    manager.stop_node().await
+}
+
+#[tauri::command]
+async fn restart_node(state: State<'_, AppState>) -> Result<u64, NodeError> {
+    let manager = state.node_manager.lock().await.clone();
+    state.jobs.spawn("restart_node", |job| async move {
+        manager.stop_node().await?;
+        tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+        manager.start_node_until(job.token()).await
+    })
+}
+
+#[tauri::command]
+async fn migrate_data_dir(state: State<'_, AppState>, new_path: String) -> Result<u64, NodeError> {
+    let node_manager = state.node_manager.clone();
+    state.jobs.spawn("migrate_data_dir", |job| async move {
+        data_migration::migrate_data_dir(node_manager, new_path.into(), move |progress, message| {
+            job.set_progress(progress, Some(message));
+        }).await
+    })
+}
+
+#[tauri::command]
+async fn list_jobs(state: State<'_, AppState>) -> Result<Vec<JobInfo>, NodeError> {
+    Ok(state.jobs.list())
+}
+
+#[tauri::command]
+async fn get_job(state: State<'_, AppState>, id: u64) -> Result<JobInfo, NodeError> {
+    state.jobs.get(id).ok_or_else(|| NodeError::InvalidInput(format!("No job with id {}", id)))
+}
+
+#[tauri::command]
+async fn cancel_job(state: State<'_, AppState>, id: u64) -> Result<(), NodeError> {
+    state.jobs.cancel(id)
+}
+
+#[tauri::command]
+async fn get_node_status(state: State<'_, AppState>) -> Result<NodeStatus, NodeError> {
+    let manager = state.node_manager.lock().await.clone();
+    Ok(manager.get_node_status().await)
+}
+
+#[tauri::command]
+async fn get_node_state(state: State<'_, AppState>) -> Result<NodeStateInfo, NodeError> {
     let manager = state.node_manager.lock().await;
-    
-    manager.download_snapshot(move |progress| {
-        window.emit("download_progress", progress).ok();
-    }).await
+    Ok(manager.lifecycle.info())
 }
 
 #[tauri::command]
-async fn start_node(state: State<'_, AppState>) -> Result<(), String> {
+async fn get_node_state_history(state: State<'_, AppState>) -> Result<Vec<PhaseTransition>, NodeError> {
     let manager = state.node_manager.lock().await;
-    manager.start_node().await
+    Ok(manager.lifecycle.history())
+}
+
+#[tauri::command]
+async fn get_chain_info(state: State<'_, AppState>) -> Result<ChainInfo, NodeError> {
+    let rpc = state.node_manager.lock().await.rpc();
+    Ok(koinos_rpc::chain_info(&rpc).await?)
+}
+
+#[tauri::command]
+async fn get_blocks(state: State<'_, AppState>, height: u64, count: u32) -> Result<Vec<BlockItem>, NodeError> {
+    let rpc = state.node_manager.lock().await.rpc();
+    let head = rpc.head_info().await?;
+    Ok(rpc.blocks_by_height(&head.head_topology.id, height, count.min(100), true).await?)
+}
+
+#[tauri::command]
+async fn get_pending_transactions(state: State<'_, AppState>, limit: u32) -> Result<Vec<PendingTransaction>, NodeError> {
+    let rpc = state.node_manager.lock().await.rpc();
+    Ok(rpc.pending_transactions(limit.min(500)).await?)
 }
 
 #[tauri::command]
-async fn stop_node(state: State<'_, AppState>) -> Result<(), String> {
+async fn get_account_history(
+    state: State<'_, AppState>,
+    address: String,
+    limit: u32,
+) -> Result<Vec<AccountHistoryEntry>, NodeError> {
+    let rpc = state.node_manager.lock().await.rpc();
+    Ok(rpc.account_history(&address, limit.min(500), false).await?)
+}
+
+#[tauri::command]
+async fn get_settings(state: State<'_, AppState>) -> Result<Settings, NodeError> {
     let manager = state.node_manager.lock().await;
-    manager.stop_node().await
+    Ok(manager.settings.get())
 }
 
 #[tauri::command]
-async fn restart_node(state: State<'_, AppState>) -> Result<(), String> {
+async fn get_settings_problems(state: State<'_, AppState>) -> Result<Vec<String>, NodeError> {
     let manager = state.node_manager.lock().await;
-    manager.stop_node().await?;
-    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-    manager.start_node().await
+    Ok(manager.settings.problems())
+}
+
+/// `changes` holds only the fields to change
+#[tauri::command]
+async fn update_settings(state: State<'_, AppState>, changes: serde_json::Value) -> Result<SettingsUpdate, NodeError> {
+    let manager = state.node_manager.lock().await.clone();
+    manager.update_settings(changes)
 }
 
 #[tauri::command]
-async fn get_node_status(state: State<'_, AppState>) -> Result<NodeStatus, String> {
+async fn list_node_roles() -> Result<Vec<RoleInfo>, NodeError> {
+    Ok(roles::roles())
+}
+
+#[tauri::command]
+async fn get_node_services(state: State<'_, AppState>) -> Result<NodeServices, NodeError> {
+    let manager = state.node_manager.lock().await.clone();
+    Ok(manager.node_services())
+}
+
+#[tauri::command]
+async fn set_node_role(state: State<'_, AppState>, role: NodeRole) -> Result<NodeServices, NodeError> {
+    let manager = state.node_manager.lock().await.clone();
+    manager.set_node_role(role)
+}
+
+#[tauri::command]
+async fn set_service_enabled(state: State<'_, AppState>, service: String, enabled: bool) -> Result<NodeServices, NodeError> {
+    let manager = state.node_manager.lock().await.clone();
+    manager.set_service_enabled(&service, enabled)
+}
+
+#[tauri::command]
+async fn get_node_config(state: State<'_, AppState>) -> Result<NodeConfig, NodeError> {
+    let manager = state.node_manager.lock().await.clone();
+    manager.node_config()
+}
+
+#[tauri::command]
+async fn preview_node_config(state: State<'_, AppState>, config: NodeConfig) -> Result<Vec<ConfigChange>, NodeError> {
+    let manager = state.node_manager.lock().await.clone();
+    manager.preview_node_config(&config)
+}
+
+#[tauri::command]
+async fn apply_node_config(state: State<'_, AppState>, config: NodeConfig) -> Result<Vec<ConfigChange>, NodeError> {
+    let manager = state.node_manager.lock().await.clone();
+    manager.apply_node_config(&config)
+}
+
+#[tauri::command]
+async fn get_peers(state: State<'_, AppState>) -> Result<PeerReport, NodeError> {
+    let manager = state.node_manager.lock().await.clone();
+    manager.get_peers().await
+}
+
+/// `range` is in seconds
+#[tauri::command]
+async fn get_metrics(state: State<'_, AppState>, series: Vec<String>, range: u64) -> Result<Vec<MetricSeries>, NodeError> {
+    Ok(state.metrics.query(&series, std::time::Duration::from_secs(range)))
+}
+
+#[tauri::command]
+async fn list_metrics(state: State<'_, AppState>) -> Result<Vec<String>, NodeError> {
+    Ok(state.metrics.names())
+}
+
+#[tauri::command]
+async fn get_metrics_scrape_status(state: State<'_, AppState>) -> Result<Vec<ScrapeStatus>, NodeError> {
+    Ok(state.metrics.scrape_status())
+}
+
+#[tauri::command]
+async fn get_metrics_endpoints(state: State<'_, AppState>) -> Result<Vec<MetricsEndpoint>, NodeError> {
     let manager = state.node_manager.lock().await;
-    Ok(manager.get_node_status().await)
+    Ok(manager.metrics_endpoints())
+}
+
+#[tauri::command]
+async fn set_metrics_endpoints(state: State<'_, AppState>, endpoints: Vec<MetricsEndpoint>) -> Result<(), NodeError> {
+    let manager = state.node_manager.lock().await;
+    manager.set_metrics_endpoints(endpoints)
+}
+
+#[tauri::command]
+async fn get_accounting(state: State<'_, AppState>) -> Result<AccountingReport, NodeError> {
+    Ok(state.accounting.report())
+}
+
+/// `from` and `to` are unix milliseconds; `resolution` defaults to one that suits the range
+#[tauri::command]
+async fn get_history(
+    state: State<'_, AppState>,
+    metric: String,
+    from: i64,
+    to: i64,
+    resolution: Option<Resolution>,
+) -> Result<History, NodeError> {
+    let telemetry = state.telemetry.clone();
+    tokio::task::spawn_blocking(move || telemetry.history(&metric, from, to, resolution)).await?
+}
+
+#[tauri::command]
+async fn get_exporter_settings(state: State<'_, AppState>) -> Result<ExporterStatus, NodeError> {
+    let manager = state.node_manager.lock().await.clone();
+    Ok(ExporterStatus {
+        settings: manager.exporter_settings(),
+        listening: state.exporter.listening().map(|a| a.to_string()),
+    })
+}
+
+/// Applied right away; only saved if the listener could be (re)started
+#[tauri::command]
+async fn set_exporter_settings(state: State<'_, AppState>, settings: ExporterSettings) -> Result<(), NodeError> {
+    let manager = state.node_manager.lock().await.clone();
+    state.exporter.apply(&settings).await?;
+    manager.save_exporter_settings(settings)
+}
+
+#[tauri::command]
+async fn get_consistency_report(state: State<'_, AppState>) -> Result<ConsistencyReport, NodeError> {
+    Ok(state.consistency.report())
+}
+
+#[tauri::command]
+async fn get_reference_endpoints(state: State<'_, AppState>) -> Result<Vec<String>, NodeError> {
+    let manager = state.node_manager.lock().await;
+    Ok(manager.reference_endpoints())
+}
+
+#[tauri::command]
+async fn set_reference_endpoints(state: State<'_, AppState>, endpoints: Vec<String>) -> Result<(), NodeError> {
+    let manager = state.node_manager.lock().await;
+    manager.set_reference_endpoints(endpoints)
 }
 
 #[tauri::command]
-async fn get_logs() -> Result<Vec<logger::LogEntry>, String> {
+async fn get_logs() -> Result<Vec<logger::LogEntry>, NodeError> {
     if let Ok(logger) = logger::LOGGER.lock() {
         Ok(logger.get_logs())
     } else {
-        Err("Failed to access logger".to_string())
+        Err(NodeError::Other("Failed to access logger".to_string()))
     }
 }
 
 #[tauri::command]
-async fn clear_logs() -> Result<(), String> {
+async fn clear_logs() -> Result<(), NodeError> {
     if let Ok(logger) = logger::LOGGER.lock() {
         logger.clear_logs();
         Ok(())
     } else {
-        Err("Failed to access logger".to_string())
+        Err(NodeError::Other("Failed to access logger".to_string()))
     }
 }
 
 #[tauri::command]
-async fn get_resource_usage(state: State<'_, AppState>) -> Result<ResourceUsage, String> {
-    let manager = state.node_manager.lock().await;
+async fn get_resource_usage(state: State<'_, AppState>) -> Result<ResourceUsage, NodeError> {
+    let manager = state.node_manager.lock().await.clone();
     manager.get_resource_usage().await
 }
 
 #[tauri::command]
-async fn check_docker_installed() -> Result<bool, String> {
-    let output = std::process::Command::new("docker")
-        .arg("--version")
-        .output();
-    
-    Ok(output.is_ok())
+async fn check_docker_installed(state: State<'_, AppState>) -> Result<bool, NodeError> {
+    let manager = state.node_manager.lock().await;
+    Ok(manager.runtime.detect())
+}
+
+#[tauri::command]
+async fn get_container_runtimes(state: State<'_, AppState>) -> Result<Vec<RuntimeInfo>, NodeError> {
+    let manager = state.node_manager.lock().await;
+    Ok(manager.list_container_runtimes())
+}
+
+#[tauri::command]
+async fn set_container_runtime(
+    state: State<'_, AppState>,
+    kind: RuntimeKind,
+) -> Result<RuntimeInfo, NodeError> {
+    let mut manager = state.node_manager.lock().await;
+    manager.set_container_runtime(kind)
+}
+
+#[tauri::command]
+async fn get_node_mode(state: State<'_, AppState>) -> Result<NodeMode, NodeError> {
+    let manager = state.node_manager.lock().await;
+    Ok(manager.mode)
+}
+
+#[tauri::command]
+async fn set_node_mode(state: State<'_, AppState>, mode: NodeMode) -> Result<(), NodeError> {
+    let mut manager = state.node_manager.lock().await;
+    manager.set_node_mode(mode)
+}
+
+#[tauri::command]
+async fn get_stream_snapshot(state: State<'_, AppState>) -> Result<bool, NodeError> {
+    let manager = state.node_manager.lock().await;
+    Ok(manager.stream_snapshot_enabled())
+}
+
+#[tauri::command]
+async fn set_stream_snapshot(state: State<'_, AppState>, enabled: bool) -> Result<(), NodeError> {
+    let manager = state.node_manager.lock().await;
+    manager.set_stream_snapshot(enabled)
+}
+
+#[tauri::command]
+async fn get_snapshot_connections(state: State<'_, AppState>) -> Result<u32, NodeError> {
+    let manager = state.node_manager.lock().await;
+    Ok(manager.snapshot_connections())
+}
+
+#[tauri::command]
+async fn set_snapshot_connections(state: State<'_, AppState>, connections: u32) -> Result<(), NodeError> {
+    let manager = state.node_manager.lock().await;
+    manager.set_snapshot_connections(connections)
+}
+
+#[tauri::command]
+async fn get_snapshot_mirrors() -> Result<Vec<SnapshotMirror>, NodeError> {
+    Ok(SnapshotSources::load().mirrors())
+}
+
+#[tauri::command]
+async fn probe_snapshot_mirrors() -> Result<Vec<MirrorProbe>, NodeError> {
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(30))
+        .build()
+        .map_err(|e| NodeError::from(e).context("Failed to create HTTP client"))?;
+    Ok(snapshot_sources::probe_mirrors(&client, SnapshotSources::load().mirrors()).await)
 }
 
 #[tauri::command]
-async fn get_detailed_status(state: State<'_, AppState>) -> Result<serde_json::Value, String> {
+async fn add_snapshot_mirror(
+    name: String,
+    url: String,
+    format: IndexFormat,
+    pattern: Option<String>,
+) -> Result<SnapshotMirror, NodeError> {
+    SnapshotSources::load().add(name, url, format, pattern)
+}
+
+#[tauri::command]
+async fn remove_snapshot_mirror(name: String) -> Result<(), NodeError> {
+    SnapshotSources::load().remove(&name)
+}
+
+#[tauri::command]
+async fn get_native_services(state: State<'_, AppState>) -> Result<Vec<supervisor::ServiceState>, NodeError> {
     let manager = state.node_manager.lock().await;
+    Ok(manager.native.service_states())
+}
+
+#[tauri::command]
+async fn get_detailed_status(state: State<'_, AppState>) -> Result<serde_json::Value, NodeError> {
+    let manager = state.node_manager.lock().await.clone();
     manager.get_detailed_status().await
 }
 
 #[tauri::command]
-async fn open_logs_folder(state: State<'_, AppState>) -> Result<(), String> {
+async fn open_logs_folder(state: State<'_, AppState>) -> Result<(), NodeError> {
     let manager = state.node_manager.lock().await;
-    let logs_path = manager.koinos_path.join("logs");
+    let logs_path = match manager.mode {
+        NodeMode::Docker => manager.koinos_path.join("logs"),
+        NodeMode::Native => manager.native.logs_dir(),
+    };
     
     #[cfg(target_os = "macos")]
     {
         std::process::Command::new("open")
             .arg(logs_path)
             .spawn()
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| NodeError::from(e).context("Failed to open logs folder"))?;
     }
     
     #[cfg(target_os = "windows")]
@@ -138,7 +545,7 @@ async fn open_logs_folder(state: State<'_, AppState>) -> Result<(), String> {
         std::process::Command::new("explorer")
             .arg(logs_path)
             .spawn()
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| NodeError::from(e).context("Failed to open logs folder"))?;
     }
     
     #[cfg(target_os = "linux")]
@@ -146,7 +553,7 @@ async fn open_logs_folder(state: State<'_, AppState>) -> Result<(), String> {
         std::process::Command::new("xdg-open")
             .arg(logs_path)
             .spawn()
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| NodeError::from(e).context("Failed to open logs folder"))?;
     }
     
     Ok(())
@@ -157,10 +564,82 @@ pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .setup(|app| {
-            let node_manager = Arc::new(Mutex::new(NodeManager::new()));
+            let node_manager = NodeManager::new(SettingsStore::load());
+            let mut transitions = node_manager.lifecycle.subscribe();
+            let accounting = Accounting::new(node_manager.state_manager.clone());
+            let node_manager = Arc::new(Mutex::new(node_manager));
+            let consistency = ConsistencyChecker::new();
+            let metrics = MetricsStore::new();
+            let download = DownloadControl::load();
+            let jobs = JobRegistry::new(app.handle().clone());
+            let exporter = Exporter::new(node_manager.clone(), download.clone(), jobs.clone());
+            let telemetry = TelemetryStore::new();
             
             app.manage(AppState {
                 node_manager: node_manager.clone(),
+                download,
+                jobs,
+                consistency: consistency.clone(),
+                metrics: metrics.clone(),
+                exporter: exporter.clone(),
+                telemetry: telemetry.clone(),
+                accounting: accounting.clone(),
+            });
+            
+            // Start the metrics exporter if it was left on
+            let manager = node_manager.clone();
+            tauri::async_runtime::spawn(async move {
+                let settings = manager.lock().await.exporter_settings();
+                if let Err(e) = exporter.apply(&settings).await {
+                    logger::log_error("Failed to start metrics exporter", Some(&e.to_string()));
+                }
+            });
+            
+            // Forward node state transitions to the frontend
+            let app_handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                loop {
+                    match transitions.recv().await {
+                        Ok(change) => {
+                            app_handle.emit("node_state_changed", &change).ok();
+                        }
+                        Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+            });
+            
+            // Compare the local chain with the reference endpoints while the node runs
+            let app_handle = app.handle().clone();
+            let manager = node_manager.clone();
+            tauri::async_runtime::spawn(async move {
+                loop {
+                    tokio::time::sleep(consistency::CHECK_INTERVAL).await;
+
+                    let manager = manager.lock().await.clone();
+                    if !matches!(manager.lifecycle.phase(), NodePhase::Syncing | NodePhase::Running) {
+                        consistency.reset();
+                        continue;
+                    }
+                    let references = manager.reference_endpoints();
+                    for alert in consistency.check(&manager.rpc(), &references).await {
+                        app_handle.emit("consistency_alert", &alert).ok();
+                    }
+                }
+            });
+            
+            // Scrape the microservices' metrics while the node runs
+            let manager = node_manager.clone();
+            tauri::async_runtime::spawn(async move {
+                loop {
+                    tokio::time::sleep(metrics::SCRAPE_INTERVAL).await;
+
+                    let manager = manager.lock().await.clone();
+                    if !matches!(manager.lifecycle.phase(), NodePhase::Syncing | NodePhase::Running) {
+                        continue;
+                    }
+                    metrics.scrape(&manager.metrics_endpoints()).await;
+                }
             });
             
             // Start background task to monitor node status
@@ -168,14 +647,20 @@ pub fn run() {
             let manager = node_manager.clone();
             
             tauri::async_runtime::spawn(async move {
+                manager.lock().await.clone().reconcile_phase().await;
                 loop {
-                    tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+                    let interval = manager.lock().await.settings.get().poll_interval();
+                    tokio::time::sleep(interval).await;
                     
-                    let manager = manager.lock().await;
+                    let manager = manager.lock().await.clone();
                     let status = manager.get_node_status().await;
                     
                     // Emit status update to frontend
                     app_handle.emit("node_status_update", &status).ok();
+                    
+                    let usage = manager.get_resource_usage().await.ok();
+                    telemetry.record(&status, usage.as_ref());
+                    accounting.tick(manager.lifecycle.phase(), &status, usage.as_ref());
                 }
             });
             
@@ -188,12 +673,63 @@ pub fn run() {
             auto_install_requirements,
             setup_node,
             download_snapshot,
+            pause_download,
+            resume_download,
+            cancel_download,
+            get_download_state,
+            set_download_limits,
             start_node,
             stop_node,
             restart_node,
+            migrate_data_dir,
+            list_jobs,
+            get_job,
+            cancel_job,
             get_node_status,
+            get_node_state,
+            get_node_state_history,
+            get_chain_info,
+            get_blocks,
+            get_pending_transactions,
+            get_account_history,
+            get_peers,
+            get_metrics,
+            list_metrics,
+            get_metrics_scrape_status,
+            get_metrics_endpoints,
+            set_metrics_endpoints,
+            get_history,
+            get_settings,
+            get_settings_problems,
+            update_settings,
+            list_node_roles,
+            get_node_services,
+            set_node_role,
+            set_service_enabled,
+            get_node_config,
+            preview_node_config,
+            apply_node_config,
+            get_accounting,
+            get_exporter_settings,
+            set_exporter_settings,
+            get_consistency_report,
+            get_reference_endpoints,
+            set_reference_endpoints,
             get_resource_usage,
             check_docker_installed,
+            get_container_runtimes,
+            set_container_runtime,
+            get_node_mode,
+            set_node_mode,
+            get_stream_snapshot,
+            set_stream_snapshot,
+            get_snapshot_connections,
+            set_snapshot_connections,
+            get_snapshot_mirrors,
+            probe_snapshot_mirrors,
+            add_snapshot_mirror,
+            remove_snapshot_mirror,
+            get_native_services,
             get_detailed_status,
             open_logs_folder,
             get_logs,
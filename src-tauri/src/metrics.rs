@@ -0,0 +1,379 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use futures_util::future::join_all;
+use crate::logger::log_debug;
+use crate::error::NodeError;
+
+pub const SCRAPE_INTERVAL: Duration = Duration::from_secs(15);
+// Two hours at the scrape interval
+const POINTS_PER_SERIES: usize = 480;
+// Upper bound on distinct series, in case a service exports high-cardinality labels
+const MAX_SERIES: usize = 4000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MetricsEndpoint {
+    /// Microservice name, used to prefix series keys
+    pub service: String,
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetricKind {
+    Counter,
+    Gauge,
+    Histogram,
+    Summary,
+    Untyped,
+}
+
+impl MetricKind {
+    fn parse(text: &str) -> Self {
+        match text {
+            "counter" => MetricKind::Counter,
+            "gauge" => MetricKind::Gauge,
+            "histogram" => MetricKind::Histogram,
+            "summary" => MetricKind::Summary,
+            _ => MetricKind::Untyped,
+        }
+    }
+}
+
+/// One line of the text exposition format
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub name: String,
+    pub labels: BTreeMap<String, String>,
+    pub value: f64,
+    pub kind: MetricKind,
+}
+
+/// Parse the Prometheus text exposition format. Malformed lines are skipped.
+pub fn parse_exposition(text: &str) -> Vec<Sample> {
+    let mut kinds: HashMap<String, MetricKind> = HashMap::new();
+    let mut samples = Vec::new();
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(comment) = line.strip_prefix('#') {
+            let mut parts = comment.split_whitespace();
+            if parts.next() == Some("TYPE") {
+                if let (Some(name), Some(kind)) = (parts.next(), parts.next()) {
+                    kinds.insert(name.to_string(), MetricKind::parse(kind));
+                }
+            }
+            continue;
+        }
+        if let Some((name, labels, value)) = parse_sample_line(line) {
+            let kind = kind_of(&kinds, &name);
+            samples.push(Sample { name, labels, value, kind });
+        }
+    }
+    samples
+}
+
+// Histogram and summary samples carry a suffix that isn't part of the TYPE name
+fn kind_of(kinds: &HashMap<String, MetricKind>, name: &str) -> MetricKind {
+    if let Some(kind) = kinds.get(name) {
+        return *kind;
+    }
+    ["_bucket", "_sum", "_count"]
+        .iter()
+        .filter_map(|suffix| name.strip_suffix(suffix))
+        .find_map(|base| kinds.get(base).copied())
+        .unwrap_or(MetricKind::Untyped)
+}
+
+fn parse_sample_line(line: &str) -> Option<(String, BTreeMap<String, String>, f64)> {
+    let name_end = line.find(|c: char| c == '{' || c.is_whitespace())?;
+    let name = &line[..name_end];
+    let mut rest = &line[name_end..];
+    let mut labels = BTreeMap::new();
+
+    if let Some(body) = rest.strip_prefix('{') {
+        let (parsed, after) = parse_labels(body)?;
+        labels = parsed;
+        rest = after;
+    }
+
+    // The optional timestamp after the value is ignored; samples are stamped at scrape time
+    let value = rest.split_whitespace().next()?.parse::<f64>().ok()?;
+    Some((name.to_string(), labels, value))
+}
+
+// Parses `a="x",b="y\"z"}` and returns the labels and what follows the closing brace
+fn parse_labels(body: &str) -> Option<(BTreeMap<String, String>, &str)> {
+    let mut labels = BTreeMap::new();
+    let mut chars = body.char_indices().peekable();
+
+    loop {
+        while chars.next_if(|(_, c)| c.is_whitespace() || *c == ',').is_some() {}
+        let (start, c) = chars.next()?;
+        if c == '}' {
+            return Some((labels, &body[start + 1..]));
+        }
+
+        let mut key = String::from(c);
+        loop {
+            let (_, c) = chars.next()?;
+            if c == '=' {
+                break;
+            }
+            key.push(c);
+        }
+        if chars.next()?.1 != '"' {
+            return None;
+        }
+
+        let mut value = String::new();
+        loop {
+            match chars.next()?.1 {
+                '"' => break,
+                '\\' => match chars.next()?.1 {
+                    'n' => value.push('\n'),
+                    other => value.push(other),
+                },
+                other => value.push(other),
+            }
+        }
+        labels.insert(key.trim().to_string(), value);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct MetricPoint {
+    /// Unix milliseconds
+    pub at: i64,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricSeries {
+    /// `service:name{labels}`, unique per series
+    pub key: String,
+    pub service: String,
+    pub name: String,
+    pub labels: BTreeMap<String, String>,
+    pub kind: MetricKind,
+    pub points: Vec<MetricPoint>,
+}
+
+struct Series {
+    service: String,
+    name: String,
+    labels: BTreeMap<String, String>,
+    kind: MetricKind,
+    points: VecDeque<MetricPoint>,
+}
+
+impl Series {
+    fn matches(&self, selector: &str) -> bool {
+        selector == self.name
+            || selector
+                .split_once(':')
+                .is_some_and(|(service, name)| service == self.service && name == self.name)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScrapeStatus {
+    pub service: String,
+    pub url: String,
+    pub last_scrape: Option<String>,
+    pub samples: usize,
+    pub error: Option<String>,
+}
+
+#[derive(Default)]
+struct Inner {
+    series: HashMap<String, Series>,
+    scrapes: Vec<ScrapeStatus>,
+}
+
+/// Scrapes the microservices' Prometheus endpoints and keeps a ring buffer of
+/// recent values for every series.
+#[derive(Clone, Default)]
+pub struct MetricsStore {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl MetricsStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Scrape every endpoint once and record the results
+    pub async fn scrape(&self, endpoints: &[MetricsEndpoint]) {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(5))
+            .build()
+            .unwrap_or_default();
+        let results = join_all(endpoints.iter().map(|endpoint| fetch(&client, endpoint))).await;
+        let at = chrono::Utc::now().timestamp_millis();
+
+        let mut inner = self.inner.lock().unwrap();
+        inner.scrapes.clear();
+        for (endpoint, result) in endpoints.iter().zip(results) {
+            let mut status = ScrapeStatus {
+                service: endpoint.service.clone(),
+                url: endpoint.url.clone(),
+                last_scrape: Some(chrono::Local::now().to_rfc3339()),
+                samples: 0,
+                error: None,
+            };
+            match result {
+                Ok(samples) => {
+                    status.samples = samples.len();
+                    for sample in samples {
+                        inner.record(&endpoint.service, sample, at);
+                    }
+                }
+                Err(e) => {
+                    log_debug("Metrics scrape failed", Some(&format!("{}: {}", endpoint.url, e)));
+                    status.error = Some(e.to_string());
+                }
+            }
+            inner.scrapes.push(status);
+        }
+    }
+
+    /// Points from the last `range` for every series matching one of `selectors`.
+    /// A selector is a metric name, or `service:name` to pick one service's copy.
+    pub fn query(&self, selectors: &[String], range: Duration) -> Vec<MetricSeries> {
+        let since = chrono::Utc::now().timestamp_millis() - range.as_millis() as i64;
+        let inner = self.inner.lock().unwrap();
+
+        let mut result: Vec<MetricSeries> = inner
+            .series
+            .iter()
+            .filter(|(_, s)| selectors.iter().any(|sel| s.matches(sel)))
+            .map(|(key, s)| MetricSeries {
+                key: key.clone(),
+                service: s.service.clone(),
+                name: s.name.clone(),
+                labels: s.labels.clone(),
+                kind: s.kind,
+                points: s.points.iter().filter(|p| p.at >= since).copied().collect(),
+            })
+            .collect();
+        result.sort_by(|a, b| a.key.cmp(&b.key));
+        result
+    }
+
+    /// Distinct `service:name` pairs currently held
+    pub fn names(&self) -> Vec<String> {
+        let inner = self.inner.lock().unwrap();
+        let mut names: Vec<String> = inner
+            .series
+            .values()
+            .map(|s| format!("{}:{}", s.service, s.name))
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    pub fn scrape_status(&self) -> Vec<ScrapeStatus> {
+        self.inner.lock().unwrap().scrapes.clone()
+    }
+}
+
+impl Inner {
+    fn record(&mut self, service: &str, sample: Sample, at: i64) {
+        // NaN and infinities don't survive JSON serialization
+        if !sample.value.is_finite() {
+            return;
+        }
+        let key = series_key(service, &sample.name, &sample.labels);
+        if !self.series.contains_key(&key) && self.series.len() >= MAX_SERIES {
+            return;
+        }
+        let series = self.series.entry(key).or_insert_with(|| Series {
+            service: service.to_string(),
+            name: sample.name.clone(),
+            labels: sample.labels.clone(),
+            kind: sample.kind,
+            points: VecDeque::new(),
+        });
+        series.kind = sample.kind;
+        series.points.push_back(MetricPoint { at, value: sample.value });
+        while series.points.len() > POINTS_PER_SERIES {
+            series.points.pop_front();
+        }
+    }
+}
+
+fn series_key(service: &str, name: &str, labels: &BTreeMap<String, String>) -> String {
+    if labels.is_empty() {
+        return format!("{}:{}", service, name);
+    }
+    let labels: Vec<String> = labels.iter().map(|(k, v)| format!("{}={:?}", k, v)).collect();
+    format!("{}:{}{{{}}}", service, name, labels.join(","))
+}
+
+async fn fetch(client: &reqwest::Client, endpoint: &MetricsEndpoint) -> Result<Vec<Sample>, NodeError> {
+    let response = client.get(&endpoint.url).send().await?;
+    if !response.status().is_success() {
+        return Err(NodeError::Network(format!(
+            "{} returned HTTP {}",
+            endpoint.url,
+            response.status().as_u16()
+        )));
+    }
+    Ok(parse_exposition(&response.text().await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn parses_typed_samples() {
+        let text = "# HELP blocks Blocks applied\n\
+                    # TYPE blocks counter\n\
+                    blocks 42\n\
+                    \n\
+                    # TYPE latency histogram\n\
+                    latency_bucket{le=\"0.5\"} 3 1700000000000\n\
+                    latency_sum 1.5\n\
+                    peers{direction=\"in\"} 7\n";
+        let samples = parse_exposition(text);
+        assert_eq!(samples.len(), 4);
+        assert_eq!(samples[0], Sample { name: "blocks".into(), labels: BTreeMap::new(), value: 42.0, kind: MetricKind::Counter });
+        assert_eq!(samples[1].kind, MetricKind::Histogram);
+        assert_eq!(samples[1].labels, labels(&[("le", "0.5")]));
+        assert_eq!(samples[1].value, 3.0);
+        assert_eq!(samples[2].kind, MetricKind::Histogram);
+        assert_eq!(samples[3].kind, MetricKind::Untyped);
+    }
+
+    #[test]
+    fn skips_malformed_lines() {
+        let samples = parse_exposition("good 1\nbad{a=\"x\" 2\nnovalue\nnan_value abc\n");
+        assert_eq!(samples.len(), 1);
+        assert_eq!(samples[0].name, "good");
+    }
+
+    #[test]
+    fn labels_handle_escapes_and_spacing() {
+        let (parsed, rest) = parse_labels(r#"a="x", b="say \"hi\"\n",}  5"#).unwrap();
+        assert_eq!(parsed, labels(&[("a", "x"), ("b", "say \"hi\"\n")]));
+        assert_eq!(rest.trim(), "5");
+    }
+
+    #[test]
+    fn labels_need_quoted_values_and_a_closing_brace() {
+        assert!(parse_labels(r#"a=x}"#).is_none());
+        assert!(parse_labels(r#"a="x""#).is_none());
+        assert_eq!(parse_labels("} 1").unwrap().0, BTreeMap::new());
+    }
+}
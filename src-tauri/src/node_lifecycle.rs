@@ -0,0 +1,220 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use crate::logger::{log_info, log_warn};
+use crate::error::NodeError;
+
+// Transitions kept for get_node_state_history
+const MAX_HISTORY: usize = 50;
+
+// Consecutive status checks that must find the services down before a running node
+// is treated as failed, so one flaky `compose ps` doesn't flip the state
+pub const MISSED_PROBES_BEFORE_ERROR: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodePhase {
+    Stopped,
+    Starting,
+    Syncing,
+    Running,
+    Stopping,
+    Error,
+}
+
+impl NodePhase {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NodePhase::Stopped => "stopped",
+            NodePhase::Starting => "starting",
+            NodePhase::Syncing => "syncing",
+            NodePhase::Running => "running",
+            NodePhase::Stopping => "stopping",
+            NodePhase::Error => "error",
+        }
+    }
+
+    /// Whether the services are up or on their way up or down
+    pub fn is_active(&self) -> bool {
+        matches!(self, NodePhase::Starting | NodePhase::Syncing | NodePhase::Running | NodePhase::Stopping)
+    }
+
+    fn can_become(&self, next: NodePhase) -> bool {
+        use NodePhase::*;
+        matches!(
+            (self, next),
+            (Stopped, Starting)
+                | (Starting, Syncing | Running | Stopping | Stopped | Error)
+                | (Syncing, Running | Stopping | Error)
+                | (Running, Syncing | Stopping | Error)
+                | (Stopping, Stopped | Error)
+                // Services can come back on their own, e.g. restarted by the runtime
+                | (Error, Starting | Syncing | Running | Stopping | Stopped)
+        )
+    }
+}
+
+/// Emitted as `node_state_changed`
+#[derive(Debug, Clone, Serialize)]
+pub struct PhaseTransition {
+    pub from: NodePhase,
+    pub to: NodePhase,
+    pub reason: String,
+    pub at: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeStateInfo {
+    pub phase: NodePhase,
+    pub since: String,
+    pub reason: String,
+}
+
+struct Inner {
+    current: NodeStateInfo,
+    history: VecDeque<PhaseTransition>,
+    missed_probes: u32,
+}
+
+/// Lifecycle of the node. Every change goes through `transition`, which rejects
+/// illegal moves and records when and why the state changed.
+#[derive(Clone)]
+pub struct NodeLifecycle {
+    inner: Arc<Mutex<Inner>>,
+    events: broadcast::Sender<PhaseTransition>,
+}
+
+impl NodeLifecycle {
+    pub fn new() -> Self {
+        let (events, _) = broadcast::channel(32);
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                current: NodeStateInfo {
+                    phase: NodePhase::Stopped,
+                    since: chrono::Local::now().to_rfc3339(),
+                    reason: "App started".to_string(),
+                },
+                history: VecDeque::new(),
+                missed_probes: 0,
+            })),
+            events,
+        }
+    }
+
+    pub fn phase(&self) -> NodePhase {
+        self.inner.lock().unwrap().current.phase
+    }
+
+    pub fn info(&self) -> NodeStateInfo {
+        self.inner.lock().unwrap().current.clone()
+    }
+
+    /// Oldest first
+    pub fn history(&self) -> Vec<PhaseTransition> {
+        self.inner.lock().unwrap().history.iter().cloned().collect()
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<PhaseTransition> {
+        self.events.subscribe()
+    }
+
+    /// Move to `next`. Staying in the same phase is a no-op.
+    pub fn transition(&self, next: NodePhase, reason: &str) -> Result<(), NodeError> {
+        let change = {
+            let mut inner = self.inner.lock().unwrap();
+            let from = inner.current.phase;
+            if from == next {
+                return Ok(());
+            }
+            if !from.can_become(next) {
+                return Err(NodeError::InvalidInput(format!(
+                    "Node can't go from {} to {}",
+                    from.as_str(),
+                    next.as_str()
+                )));
+            }
+
+            let at = chrono::Local::now().to_rfc3339();
+            inner.current = NodeStateInfo { phase: next, since: at.clone(), reason: reason.to_string() };
+            inner.missed_probes = 0;
+            let change = PhaseTransition { from, to: next, reason: reason.to_string(), at };
+            inner.history.push_back(change.clone());
+            while inner.history.len() > MAX_HISTORY {
+                inner.history.pop_front();
+            }
+            change
+        };
+
+        log_info(
+            &format!("Node {} -> {}", change.from.as_str(), change.to.as_str()),
+            Some(&change.reason),
+        );
+        // No receivers is fine
+        self.events.send(change).ok();
+        Ok(())
+    }
+
+    /// For transitions driven by observation, where an illegal move is logged rather
+    /// than reported to anyone
+    pub fn observe(&self, next: NodePhase, reason: &str) {
+        if let Err(e) = self.transition(next, reason) {
+            log_warn("Ignored node state change", Some(&e.to_string()));
+        }
+    }
+
+    /// Record a status check that found the services down; returns how many in a row
+    pub fn probe_missed(&self) -> u32 {
+        let mut inner = self.inner.lock().unwrap();
+        inner.missed_probes += 1;
+        inner.missed_probes
+    }
+
+    pub fn probe_ok(&self) {
+        self.inner.lock().unwrap().missed_probes = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use NodePhase::*;
+
+    #[test]
+    fn normal_start_and_stop() {
+        assert!(Stopped.can_become(Starting));
+        assert!(Starting.can_become(Syncing));
+        assert!(Syncing.can_become(Running));
+        assert!(Running.can_become(Stopping));
+        assert!(Stopping.can_become(Stopped));
+    }
+
+    #[test]
+    fn running_node_must_stop_first() {
+        assert!(!Stopped.can_become(Running));
+        assert!(!Stopped.can_become(Stopping));
+        assert!(!Running.can_become(Starting));
+        assert!(!Running.can_become(Stopped));
+        assert!(!Stopping.can_become(Starting));
+    }
+
+    #[test]
+    fn error_can_recover_anywhere() {
+        for next in [Starting, Syncing, Running, Stopping, Stopped] {
+            assert!(Error.can_become(next));
+        }
+        assert!(!Stopped.can_become(Error));
+    }
+
+    #[test]
+    fn transition_rejects_illegal_moves_and_records_legal_ones() {
+        let lifecycle = NodeLifecycle::new();
+        assert!(lifecycle.transition(Running, "test").is_err());
+        assert_eq!(lifecycle.phase(), Stopped);
+
+        lifecycle.transition(Starting, "test").unwrap();
+        lifecycle.observe(Error, "failed");
+        assert_eq!(lifecycle.phase(), Error);
+        assert_eq!(lifecycle.history().len(), 2);
+    }
+}
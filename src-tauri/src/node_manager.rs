@@ -1,11 +1,36 @@
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
 use serde::{Deserialize, Serialize};
 use std::sync::{Arc, Mutex};
 use tokio::process::Command as AsyncCommand;
-use crate::state_manager::StateManager;
+use tokio_util::sync::CancellationToken;
+use crate::container_runtime::{self, ContainerRuntime, RuntimeInfo, RuntimeKind};
+use crate::native_installer::{self, NativeInstaller};
+use crate::snapshot_sources::{self, SnapshotSources};
+use crate::download_control::DownloadControl;
+use crate::snapshot_downloader::{self, DownloadProgress, DownloadTarget};
+use crate::snapshot_stream;
+use crate::process_stats;
+use crate::snapshot_verify::{self, RemoteSnapshotInfo, SnapshotManifest};
+use sha2::{Digest, Sha256};
+use crate::state_manager::{write_atomic, StateManager};
+use crate::node_config::{ConfigChange, ConfigFiles, EnvFile, NodeConfig};
 use crate::logger::{log_debug, log_info, log_warn, log_error};
+use crate::error::NodeError;
+use crate::koinos_rpc::KoinosRpc;
+use crate::sync_tracker::{self, SyncTracker};
+use crate::peers::{PeerReport, PeerTracker};
+use crate::metrics::MetricsEndpoint;
+use crate::exporter::ExporterSettings;
+use crate::settings::{Settings, SettingsStore, SettingsUpdate};
+use crate::roles::{self, NodeRole, NodeServices};
+use crate::node_lifecycle::{NodeLifecycle, NodePhase, MISSED_PROBES_BEFORE_ERROR};
+
+/// Every microservice a node can run; the compose profiles select which are active
+pub const SERVICES: &[&str] = &[
+    "chain", "p2p", "block_store", "mempool", "jsonrpc", "grpc", "rest",
+    "account_history", "transaction_store", "contract_meta_store", "block_producer", "amqp",
+];
 
 // Helper function to get directory size
 fn get_dir_size(path: &Path) -> u64 {
@@ -24,14 +49,28 @@ fn get_dir_size(path: &Path) -> u64 {
     size
 }
 
+/// How the Koinos microservices are run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeMode {
+    #[default]
+    Docker,
+    Native,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NodeStatus {
-    pub status: String, // "stopped", "starting", "syncing", "running", "error"
+    pub status: NodePhase,
     pub sync_progress: f32,
     pub current_block: u64,
     pub target_block: u64,
     pub peers_count: u32,
     pub error_message: Option<String>,
+    /// Blocks applied per second, averaged over the last few minutes
+    pub blocks_per_sec: Option<f64>,
+    /// How far the head block's timestamp is behind the clock
+    pub head_lag_seconds: Option<u64>,
+    pub eta_seconds: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -44,56 +83,339 @@ pub struct SystemRequirements {
     pub missing_requirements: Vec<String>,
 }
 
+/// What the node uses, summed over its services. CPU is percent of the whole host.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResourceUsage {
     pub cpu_percent: f32,
     pub memory_mb: u32,
+    /// Host memory, for scale
     pub memory_total_mb: u32,
     pub disk_used_gb: f32,
     pub disk_total_gb: f32,
+    pub net_rx_bytes: u64,
+    pub net_tx_bytes: u64,
+    pub block_read_bytes: u64,
+    pub block_write_bytes: u64,
+    pub services: Vec<ServiceUsage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceUsage {
+    pub service: String,
+    pub cpu_percent: f32,
+    pub memory_mb: u32,
+    /// None for native processes, whose traffic can't be told apart from the host's
+    pub net_rx_bytes: Option<u64>,
+    pub net_tx_bytes: Option<u64>,
+    pub block_read_bytes: Option<u64>,
+    pub block_write_bytes: Option<u64>,
 }
 
+#[derive(Clone)]
 pub struct NodeManager {
     pub status: Arc<Mutex<NodeStatus>>,
+    pub lifecycle: NodeLifecycle,
+    pub sync: SyncTracker,
+    pub peers: PeerTracker,
     pub koinos_path: PathBuf,
     pub data_path: PathBuf,
     pub state_manager: Arc<Mutex<StateManager>>,
+    pub runtime: Arc<dyn ContainerRuntime>,
+    pub mode: NodeMode,
+    pub native: NativeInstaller,
+    pub settings: SettingsStore,
 }
 
 impl NodeManager {
-    pub fn new() -> Self {
-        let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
-        let koinos_path = home.join("koinos");
-        let data_path = home.join(".koinos");
+    pub fn new(settings: SettingsStore) -> Self {
+        let koinos_path = settings.get().koinos_path;
+        let data_path = settings.get().data_path;
         
-        let mut state_manager = StateManager::new();
-        let _ = state_manager.load();
+        let state_manager = StateManager::new();
         
         // Initialize status from saved state
         let saved_state = state_manager.get_state();
+        let runtime = container_runtime::detect_runtime(saved_state.container_runtime);
+        let mode = saved_state.node_mode;
         let initial_status = NodeStatus {
-            status: "stopped".to_string(),
+            status: NodePhase::Stopped,
             sync_progress: saved_state.last_sync_progress,
             current_block: saved_state.last_block,
             target_block: 0,
             peers_count: 0,
             error_message: None,
+            blocks_per_sec: None,
+            head_lag_seconds: None,
+            eta_seconds: None,
         };
 
         Self {
             status: Arc::new(Mutex::new(initial_status)),
+            lifecycle: NodeLifecycle::new(),
+            sync: SyncTracker::new(),
+            peers: PeerTracker::new(),
             koinos_path,
-            data_path,
+            data_path: data_path.clone(),
             state_manager: Arc::new(Mutex::new(state_manager)),
+            runtime,
+            mode,
+            native: NativeInstaller::new(data_path.clone(), settings.clone()),
+            settings,
         }
     }
 
+    // Compose project name, which prefixes every container name
+    fn compose_project(&self) -> String {
+        self.koinos_path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "koinos".to_string())
+    }
+
+    fn compose_profiles(&self) -> Vec<String> {
+        self.settings.get().compose_profiles
+    }
+
+    /// Services the current selection runs
+    pub fn active_services(&self) -> Vec<&'static str> {
+        roles::active_services(&self.compose_profiles())
+    }
+
+    pub fn node_services(&self) -> NodeServices {
+        NodeServices::from_profiles(self.compose_profiles())
+    }
+
+    pub fn set_node_role(&self, role: NodeRole) -> Result<NodeServices, NodeError> {
+        self.set_compose_profiles(role.profiles())
+    }
+
+    pub fn set_service_enabled(&self, service: &str, enabled: bool) -> Result<NodeServices, NodeError> {
+        if !SERVICES.contains(&service) {
+            return Err(NodeError::InvalidInput(format!("Unknown service {:?}", service)));
+        }
+        if roles::CORE_SERVICES.contains(&service) {
+            if !enabled {
+                return Err(NodeError::InvalidInput(format!("{} is required and can't be disabled", service)));
+            }
+            return Ok(self.node_services());
+        }
+        self.set_compose_profiles(roles::toggle(&self.compose_profiles(), service, enabled))
+    }
+
+    fn set_compose_profiles(&self, profiles: Vec<String>) -> Result<NodeServices, NodeError> {
+        self.update_settings(serde_json::json!({ "compose_profiles": profiles }))?;
+
+        let services = self.node_services();
+        log_info("Node services changed", Some(&services.profiles.join(",")));
+        Ok(services)
+    }
+
+    /// Apply `changes` to the settings. Ports and profiles also go into `.env`, so
+    /// compose publishes the same ports and running it by hand starts the same services.
+    /// In native mode the ports go into the service configs instead.
+    pub fn update_settings(&self, changes: serde_json::Value) -> Result<SettingsUpdate, NodeError> {
+        let update = self.settings.update(changes)?;
+        let changed = |keys: &[&str]| update.restart_node.iter().any(|k| keys.contains(&k.as_str()));
+        match self.mode {
+            NodeMode::Docker if changed(&["jsonrpc_port", "grpc_port", "p2p_port", "compose_profiles"]) => {
+                self.sync_env(&update.settings)?;
+            }
+            NodeMode::Native if changed(&["jsonrpc_port", "grpc_port", "p2p_port"]) => {
+                self.native.sync_ports()?;
+            }
+            _ => {}
+        }
+        Ok(update)
+    }
+
+    fn sync_env(&self, settings: &Settings) -> Result<(), NodeError> {
+        let env_file = self.koinos_path.join(".env");
+        if !env_file.exists() {
+            return Ok(());
+        }
+        let content = fs::read_to_string(&env_file)
+            .map_err(|e| NodeError::from(e).context("Failed to read env file"))?;
+        let mut env = EnvFile::parse(&content);
+        env.set("JSONRPC_PORT", &settings.jsonrpc_port.to_string());
+        env.set("GRPC_PORT", &settings.grpc_port.to_string());
+        env.set("P2P_PORT", &settings.p2p_port.to_string());
+        if settings.compose_profiles.is_empty() {
+            env.unset("COMPOSE_PROFILES");
+        } else {
+            env.set("COMPOSE_PROFILES", &settings.compose_profiles.join(","));
+        }
+        let updated = env.to_string();
+        if updated != content {
+            write_atomic(&env_file, updated.as_bytes())
+                .map_err(|e| e.context("Failed to write env file"))?;
+            log_info("Updated .env from settings", None);
+        }
+        Ok(())
+    }
+
+    pub fn list_container_runtimes(&self) -> Vec<RuntimeInfo> {
+        RuntimeKind::ALL
+            .iter()
+            .map(|kind| container_runtime::runtime_for(*kind).info())
+            .collect()
+    }
+
+    pub fn set_container_runtime(&mut self, kind: RuntimeKind) -> Result<RuntimeInfo, NodeError> {
+        let runtime = container_runtime::runtime_for(kind);
+        if !runtime.detect() {
+            return Err(NodeError::DockerMissing(format!("{} is not installed", kind.display_name())));
+        }
+
+        let info = runtime.info();
+        self.runtime = runtime;
+        self.state_manager.lock().unwrap().set_container_runtime(kind)?;
+        log_info("Container runtime selected", Some(kind.display_name()));
+        Ok(info)
+    }
+
+    pub fn set_node_mode(&mut self, mode: NodeMode) -> Result<(), NodeError> {
+        if mode == NodeMode::Native && native_installer::platform().is_none() {
+            return Err(NodeError::UnsupportedPlatform("Native mode is not available on this platform".to_string()));
+        }
+        if self.mode != mode && self.is_running() {
+            return Err(NodeError::NodeRunning("Stop the node before switching modes".to_string()));
+        }
+
+        self.mode = mode;
+        self.state_manager.lock().unwrap().set_node_mode(mode)?;
+        log_info("Node mode selected", Some(&format!("{:?}", mode)));
+        Ok(())
+    }
+
+    pub fn snapshot_connections(&self) -> u32 {
+        self.settings.get().snapshot_connections
+    }
+
+    pub fn set_snapshot_connections(&self, connections: u32) -> Result<(), NodeError> {
+        self.settings.update(serde_json::json!({ "snapshot_connections": connections }))?;
+        Ok(())
+    }
+
+    pub fn stream_snapshot_enabled(&self) -> bool {
+        self.settings.get().stream_snapshot
+    }
+
+    /// Streaming saves the disk space of the archive, but only survives dropped
+    /// connections: a failed or cancelled download starts over from byte 0
+    pub fn set_stream_snapshot(&self, enabled: bool) -> Result<(), NodeError> {
+        self.settings.update(serde_json::json!({ "stream_snapshot": enabled }))?;
+        Ok(())
+    }
+
+    pub fn reference_endpoints(&self) -> Vec<String> {
+        self.settings.get().reference_endpoints
+    }
+
+    /// An empty list turns the fork check off
+    pub fn set_reference_endpoints(&self, endpoints: Vec<String>) -> Result<(), NodeError> {
+        let mut cleaned: Vec<String> = Vec::new();
+        for endpoint in endpoints {
+            let endpoint = endpoint.trim().trim_end_matches('/').to_string();
+            if !cleaned.contains(&endpoint) {
+                cleaned.push(endpoint);
+            }
+        }
+        self.settings.update(serde_json::json!({ "reference_endpoints": cleaned }))?;
+        Ok(())
+    }
+
+    pub fn metrics_endpoints(&self) -> Vec<MetricsEndpoint> {
+        self.settings.get().metrics_endpoints
+    }
+
+    pub fn set_metrics_endpoints(&self, endpoints: Vec<MetricsEndpoint>) -> Result<(), NodeError> {
+        let cleaned: Vec<MetricsEndpoint> = endpoints
+            .into_iter()
+            .map(|e| MetricsEndpoint { service: e.service.trim().to_string(), url: e.url.trim().to_string() })
+            .collect();
+        self.settings.update(serde_json::json!({ "metrics_endpoints": cleaned }))?;
+        Ok(())
+    }
+
+    pub fn exporter_settings(&self) -> ExporterSettings {
+        self.settings.get().exporter
+    }
+
+    pub fn save_exporter_settings(&self, settings: ExporterSettings) -> Result<(), NodeError> {
+        self.settings.update(serde_json::json!({ "exporter": settings }))?;
+        Ok(())
+    }
+
+    fn is_running(&self) -> bool {
+        self.lifecycle.phase().is_active()
+    }
+
+    /// Switch to another data directory; the data itself has to be there already
+    pub fn set_data_path(&mut self, path: PathBuf) -> Result<(), NodeError> {
+        self.settings.set_data_path(path.clone())?;
+        self.native.data_path = path.clone();
+        self.data_path = path;
+        Ok(())
+    }
+
+    fn config_files(&self) -> Result<ConfigFiles, NodeError> {
+        if self.mode != NodeMode::Docker {
+            return Err(NodeError::InvalidInput("The node config editor is only available in Docker mode".to_string()));
+        }
+        ConfigFiles::load(&self.koinos_path)
+    }
+
+    pub fn node_config(&self) -> Result<NodeConfig, NodeError> {
+        Ok(self.config_files()?.config())
+    }
+
+    /// What `apply_node_config` would change, without writing anything
+    pub fn preview_node_config(&self, config: &NodeConfig) -> Result<Vec<ConfigChange>, NodeError> {
+        config.validate()?;
+        Ok(self.config_files()?.diff(config))
+    }
+
+    /// Write the changed fields. Ports and profiles are mirrored into the settings so
+    /// the app talks to the node the way it is configured.
+    pub fn apply_node_config(&self, config: &NodeConfig) -> Result<Vec<ConfigChange>, NodeError> {
+        let mut files = self.config_files()?;
+        config.validate()?;
+        let changes = files.diff(config);
+
+        let previous = self.settings.get();
+        // An unset port falls back to the compose default, which the settings defaults match
+        let defaults = serde_json::to_value(Settings::default())?;
+        let mut mirrored = serde_json::Map::new();
+        for change in &changes {
+            let value = match change.key.as_str() {
+                "jsonrpc_port" | "grpc_port" | "p2p_port" if change.new.is_null() => defaults[&change.key].clone(),
+                "jsonrpc_port" | "grpc_port" | "p2p_port" | "compose_profiles" => change.new.clone(),
+                _ => continue,
+            };
+            mirrored.insert(change.key.clone(), value);
+        }
+        if !mirrored.is_empty() {
+            self.settings.update(serde_json::Value::Object(mirrored))?;
+        }
+
+        files.apply(config).inspect_err(|_| {
+            if let Ok(previous) = serde_json::to_value(&previous) {
+                self.settings.update(previous).ok();
+            }
+        })
+    }
+
     pub fn is_initialized(&self) -> bool {
-        self.koinos_path.exists() && 
-        self.koinos_path.join("docker-compose.yml").exists()
+        match self.mode {
+            NodeMode::Docker => {
+                self.koinos_path.exists() &&
+                self.koinos_path.join("docker-compose.yml").exists()
+            }
+            NodeMode::Native => self.native.is_installed(),
+        }
     }
 
-    pub async fn check_system_requirements(&self) -> Result<SystemRequirements, String> {
+    pub async fn check_system_requirements(&self) -> Result<SystemRequirements, NodeError> {
         log_info("Starting system requirements check", None);
         
         let mut requirements = SystemRequirements {
@@ -105,95 +427,60 @@ impl NodeManager {
             missing_requirements: Vec::new(),
         };
 
-        // Check Docker - try multiple methods
-        log_debug("Checking for Docker installation", None);
-        
-        // First try the docker command directly
-        let docker_check = Command::new("docker")
-            .arg("--version")
-            .output();
+        if self.mode == NodeMode::Native {
+            // Native mode needs no container runtime, only a supported platform
+            if native_installer::platform().is_none() {
+                requirements.missing_requirements.push("Native mode is not available on this platform".to_string());
+            }
+        } else {
+            // Check the container runtime
+            let runtime_name = self.runtime.kind().display_name();
+            log_debug(&format!("Checking for {} installation", runtime_name), None);
+        
+            let info = self.runtime.info();
+            requirements.has_docker = info.installed;
+            if let Some(version) = &info.version {
+                log_info(&format!("{} found", runtime_name), Some(version));
+            }
         
-        if let Ok(output) = docker_check {
-            if output.status.success() {
+            // Also check if Docker Desktop is installed on macOS
+            #[cfg(target_os = "macos")]
+            if !requirements.has_docker
+                && self.runtime.kind() == RuntimeKind::Docker
+                && std::path::Path::new("/Applications/Docker.app").exists()
+            {
                 requirements.has_docker = true;
-                let version = String::from_utf8_lossy(&output.stdout);
-                log_info("Docker found", Some(&version.trim()));
-            } else {
-                // Command exists but failed - try with full path
-                let docker_paths = vec![
-                    "/usr/local/bin/docker",
-                    "/opt/homebrew/bin/docker",
-                    "/usr/bin/docker",
-                ];
-                
-                for path in docker_paths {
-                    if let Ok(output) = Command::new(path).arg("--version").output() {
-                        if output.status.success() {
-                            requirements.has_docker = true;
-                            let version = String::from_utf8_lossy(&output.stdout);
-                            log_info("Docker found at", Some(&format!("{}: {}", path, version.trim())));
-                            break;
-                        }
-                    }
-                }
+                log_info("Docker Desktop found", Some("Located at /Applications/Docker.app"));
             }
-        }
-        
-        // Also check if Docker Desktop is installed on macOS
-        #[cfg(target_os = "macos")]
-        if !requirements.has_docker && std::path::Path::new("/Applications/Docker.app").exists() {
-            requirements.has_docker = true;
-            log_info("Docker Desktop found", Some("Located at /Applications/Docker.app"));
-        }
-        
-        if !requirements.has_docker {
-            log_warn("Docker not found", Some("Docker is not installed"));
-        }
         
-        if requirements.has_docker {
-            log_debug("Checking if Docker daemon is running", None);
-            
-            // Try docker info with different paths
-            let docker_paths = vec![
-                "docker",
-                "/usr/local/bin/docker", 
-                "/opt/homebrew/bin/docker",
-                "/usr/bin/docker",
-            ];
-            
-            let mut docker_found = false;
-            for path in docker_paths {
-                if let Ok(output) = Command::new(path).arg("info").output() {
-                    docker_found = true;
-                    if output.status.success() {
-                        requirements.docker_running = true;
-                        log_info("Docker daemon is running", None);
-                        break;
-                    } else {
-                        let stderr = String::from_utf8_lossy(&output.stderr);
-                        log_warn("Docker daemon not running", Some(&stderr));
+            if requirements.has_docker {
+                requirements.docker_running = info.daemon_running;
+                if info.daemon_running {
+                    log_info(&format!("{} daemon is running", runtime_name), None);
+                    if !info.compose_available {
+                        requirements.missing_requirements.push(format!("Compose is not available for {}", runtime_name));
                     }
+                } else {
+                    log_warn(&format!("{} daemon not running", runtime_name), info.error.as_deref());
+                    requirements.missing_requirements.push(format!("{} is not running", runtime_name));
                 }
+            } else {
+                requirements.missing_requirements.push(format!("{} is not installed", runtime_name));
+                log_error(&format!("{} is not installed", runtime_name), Some("Please install a container runtime"));
             }
-            
-            if !docker_found {
-                requirements.docker_running = false;
-                log_error("Failed to check Docker daemon status", None);
-                requirements.missing_requirements.push("Docker is not running".to_string());
-            } else if !requirements.docker_running {
-                requirements.missing_requirements.push("Docker is not running".to_string());
-            }
-        } else {
-            requirements.missing_requirements.push("Docker is not installed".to_string());
-            log_error("Docker is not installed", Some("Please install Docker Desktop"));
         }
 
+        let settings = self.settings.get();
+
         // Check RAM
-        let ram_info = sys_info::mem_info().map_err(|e| e.to_string())?;
+        let ram_info = sys_info::mem_info().map_err(|e| NodeError::Io(format!("Failed to read memory info: {}", e)))?;
         requirements.ram_gb = (ram_info.total / 1024 / 1024) as u32; // Convert KB to GB
         
-        if requirements.ram_gb < 4 {
-            requirements.missing_requirements.push(format!("Insufficient RAM: {}GB (minimum 4GB required)", requirements.ram_gb));
+        if requirements.ram_gb < settings.min_ram_gb {
+            requirements.missing_requirements.push(format!(
+                "Insufficient RAM: {}GB (minimum {}GB required)",
+                requirements.ram_gb, settings.min_ram_gb
+            ));
         }
 
         // Check disk space
@@ -202,10 +489,10 @@ impl NodeManager {
         
         requirements.available_disk_gb = available_space;
         
-        if requirements.available_disk_gb < 60 {
+        if requirements.available_disk_gb < settings.min_disk_gb {
             requirements.missing_requirements.push(format!(
-                "Insufficient disk space: {}GB (minimum 60GB required)", 
-                requirements.available_disk_gb
+                "Insufficient disk space: {}GB (minimum {}GB required)", 
+                requirements.available_disk_gb, settings.min_disk_gb
             ));
         }
 
@@ -219,17 +506,17 @@ impl NodeManager {
         Ok(requirements)
     }
 
-    pub async fn install_docker(&self) -> Result<(), String> {
+    pub async fn install_docker(&self) -> Result<(), NodeError> {
         #[cfg(target_os = "macos")]
         {
             // Check if Homebrew is installed
             let brew_check = std::process::Command::new("which")
                 .arg("brew")
                 .output()
-                .map_err(|e| format!("Failed to check for Homebrew: {}", e))?;
+                .map_err(|e| NodeError::from(e).context("Failed to check for Homebrew"))?;
             
             if !brew_check.status.success() {
-                return Err("Homebrew not installed. Please install Homebrew first or download Docker Desktop manually.".to_string());
+                return Err(NodeError::DockerMissing("Homebrew not installed. Please install Homebrew first or download Docker Desktop manually.".to_string()));
             }
             
             // Install Docker using Homebrew
@@ -237,7 +524,7 @@ impl NodeManager {
                 .args(&["install", "--cask", "docker"])
                 .output()
                 .await
-                .map_err(|e| format!("Failed to install Docker: {}", e))?;
+                .map_err(|e| NodeError::from(e).context("Failed to install Docker"))?;
             
             if !output.status.success() {
                 let error = String::from_utf8_lossy(&output.stderr);
@@ -249,7 +536,7 @@ impl NodeManager {
                         .ok();
                     return Ok(());
                 }
-                return Err(format!("Docker installation failed: {}", error));
+                return Err(NodeError::Process(format!("Docker installation failed: {}", error)));
             }
             
             // Try to open Docker after installation
@@ -275,10 +562,10 @@ impl NodeManager {
                 .arg(script)
                 .output()
                 .await
-                .map_err(|e| e.to_string())?;
+                .map_err(|e| NodeError::from(e).context("Failed to install Docker"))?;
             
             if !output.status.success() {
-                Err("Failed to install Docker".to_string())
+                Err(NodeError::Process("Failed to install Docker".to_string()))
             } else {
                 Ok(())
             }
@@ -286,17 +573,23 @@ impl NodeManager {
 
         #[cfg(target_os = "windows")]
         {
-            Err("Please install Docker Desktop from https://www.docker.com/products/docker-desktop/".to_string())
+            Err(NodeError::DockerMissing("Please install Docker Desktop from https://www.docker.com/products/docker-desktop/".to_string()))
         }
     }
 
-    pub async fn setup_koinos(&self) -> Result<(), String> {
+    pub async fn setup_koinos(&self) -> Result<(), NodeError> {
         log_info("Starting Koinos setup", None);
         
+        if self.mode == NodeMode::Native {
+            return self.native.install_native_binaries(|progress| {
+                log_debug("Native install progress", Some(&format!("{:.0}%", progress)));
+            }).await;
+        }
+        
         // Create koinos directory
         log_debug("Creating koinos directory", Some(self.koinos_path.to_str().unwrap_or("unknown")));
         fs::create_dir_all(&self.koinos_path)
-            .map_err(|e| format!("Failed to create koinos directory: {}", e))?;
+            .map_err(|e| NodeError::from(e).context("Failed to create koinos directory"))?;
 
         // Clone Koinos repository if not exists
         if !self.koinos_path.join("docker-compose.yml").exists() {
@@ -317,13 +610,13 @@ impl NodeManager {
                 .await
                 .map_err(|e| {
                     log_error("Failed to execute git clone", Some(&e.to_string()));
-                    format!("Failed to clone repository: {}", e)
+                    NodeError::from(e).context("Failed to clone repository")
                 })?;
 
             if !output.status.success() {
                 let error = String::from_utf8_lossy(&output.stderr);
                 log_error("Git clone failed", Some(&error));
-                return Err(format!("Failed to clone Koinos repository: {}", error));
+                return Err(NodeError::Process(format!("Failed to clone Koinos repository: {}", error)));
             }
             log_info("Repository cloned successfully", None);
         } else {
@@ -333,102 +626,20 @@ impl NodeManager {
         // Setup configuration
         self.setup_configuration().await?;
         
-        // Pre-pull Docker images for smoother startup
-        println!("Pulling Docker images (this may take a few minutes)...");
-        let pull_output = AsyncCommand::new("docker")
-            .arg("compose")
-            .arg("pull")
-            .current_dir(&self.koinos_path)
-            .output()
-            .await;
-        
-        if let Ok(output) = pull_output {
-            if !output.status.success() {
-                println!("Warning: Could not pre-pull Docker images. They will be downloaded on first start.");
-            } else {
-                println!("Docker images ready");
-            }
+        // Pre-pull container images for smoother startup
+        log_info("Pulling container images (this may take a few minutes)", None);
+        match self.runtime.compose_pull(&self.koinos_path).await {
+            Ok(()) => log_info("Container images ready", None),
+            Err(e) => log_warn(
+                "Could not pre-pull container images, they will be downloaded on first start",
+                Some(&e.to_string()),
+            ),
         }
 
         Ok(())
     }
 
-    // Resolve a working docker binary path (handles PATH issues on macOS)
-    fn find_docker_path(&self) -> Option<String> {
-        let candidates = vec![
-            "/usr/local/bin/docker",
-            "/opt/homebrew/bin/docker",
-            "/Applications/Docker.app/Contents/Resources/bin/docker",
-            "/usr/bin/docker",
-            "docker",
-        ];
-        for c in candidates {
-            if let Ok(output) = Command::new(c).arg("--version").output() {
-                if output.status.success() {
-                    log_debug(&format!("Found Docker at: {}", c), None);
-                    return Some(c.to_string());
-                }
-            }
-        }
-        log_error("Docker not found in any known location", None);
-        None
-    }
-
-    fn docker_info_ok(&self) -> bool {
-        if let Some(docker) = self.find_docker_path() {
-            if let Ok(output) = Command::new(&docker).arg("info").output() {
-                if output.status.success() {
-                    return true;
-                }
-                // Check if Docker Desktop is starting
-                let stderr = String::from_utf8_lossy(&output.stderr);
-                if stderr.contains("Docker Desktop is starting") {
-                    log_info("Docker Desktop is starting, waiting...", None);
-                    // Return false but don't treat as error
-                    return false;
-                }
-            }
-        }
-        false
-    }
-
-    fn compose_invocation(&self) -> Option<(String, Vec<String>)> {
-        if let Some(docker) = self.find_docker_path() {
-            // Prefer 'docker compose' if supported
-            if Command::new(&docker)
-                .arg("compose")
-                .arg("version")
-                .stdout(Stdio::null())
-                .stderr(Stdio::null())
-                .status()
-                .map(|s| s.success())
-                .unwrap_or(false)
-            {
-                return Some((docker, vec!["compose".into()]));
-            }
-        }
-        // Fallback to docker-compose binary
-        for c in [
-            "docker-compose",
-            "/opt/homebrew/bin/docker-compose",
-            "/usr/local/bin/docker-compose",
-            "/usr/bin/docker-compose",
-        ] {
-            if Command::new(c)
-                .arg("--version")
-                .stdout(Stdio::null())
-                .stderr(Stdio::null())
-                .status()
-                .map(|s| s.success())
-                .unwrap_or(false)
-            {
-                return Some((c.to_string(), vec![]));
-            }
-        }
-        None
-    }
-
-    async fn setup_configuration(&self) -> Result<(), String> {
+    async fn setup_configuration(&self) -> Result<(), NodeError> {
         log_info("Setting up Koinos configuration", None);
         
         let config_path = self.koinos_path.join("config");
@@ -445,11 +656,11 @@ impl NodeManager {
                 
                 // Create the config directory first
                 fs::create_dir_all(&config_path)
-                    .map_err(|e| format!("Failed to create config directory: {}", e))?;
+                    .map_err(|e| NodeError::from(e).context("Failed to create config directory"))?;
                 
                 // Copy all files from config-example to config
                 let entries = fs::read_dir(&config_example)
-                    .map_err(|e| format!("Failed to read config-example: {}", e))?;
+                    .map_err(|e| NodeError::from(e).context("Failed to read config-example"))?;
                 
                 for entry in entries {
                     if let Ok(entry) = entry {
@@ -459,7 +670,7 @@ impl NodeManager {
                         
                         if from.is_file() {
                             fs::copy(&from, &to)
-                                .map_err(|e| format!("Failed to copy config file: {}", e))?;
+                                .map_err(|e| NodeError::from(e).context("Failed to copy config file"))?;
                         }
                     }
                 }
@@ -477,46 +688,48 @@ impl NodeManager {
         
         if !env_file.exists() && env_example.exists() {
             fs::copy(&env_example, &env_file)
-                .map_err(|e| format!("Failed to copy env file: {}", e))?;
+                .map_err(|e| NodeError::from(e).context("Failed to copy env file"))?;
         }
 
-        // Configure .env for desktop app (keep APIs on localhost for security)
+        // Configure .env for desktop app
         if env_file.exists() {
-            let env_content = fs::read_to_string(&env_file)
-                .map_err(|e| format!("Failed to read env file: {}", e))?;
-            
-            let mut new_content = env_content;
-            
+            let content = fs::read_to_string(&env_file)
+                .map_err(|e| NodeError::from(e).context("Failed to read env file"))?;
+            let mut env = EnvFile::parse(&content);
+            let settings = self.settings.get();
+
             // Keep interfaces on localhost for desktop app security
-            new_content = new_content.replace("JSONRPC_INTERFACE=127.0.0.1", "JSONRPC_INTERFACE=127.0.0.1");
-            new_content = new_content.replace("GRPC_INTERFACE=127.0.0.1", "GRPC_INTERFACE=127.0.0.1");
-            new_content = new_content.replace("REST_INTERFACE=127.0.0.1", "REST_INTERFACE=127.0.0.1");
-            
-            // Enable required profiles (ensure COMPOSE_PROFILES is set)
-            if !new_content.contains("COMPOSE_PROFILES=") {
-                new_content.push_str("\nCOMPOSE_PROFILES=all\n");
-            } else {
-                // Un-comment if present as commented key
-                new_content = new_content.replace("#COMPOSE_PROFILES", "COMPOSE_PROFILES");
+            for key in ["JSONRPC_INTERFACE", "GRPC_INTERFACE", "REST_INTERFACE"] {
+                env.set_default(key, "127.0.0.1");
             }
-            
-            // Add performance optimizations and restart policy
-            if !new_content.contains("KOINOS_LOG_LEVEL") {
-                new_content.push_str("\n# Desktop Node Optimizations\n");
-                new_content.push_str("KOINOS_LOG_LEVEL=warn\n");
-                new_content.push_str("KOINOS_LOG_JSON=false\n");
-                new_content.push_str("# Auto-restart on system reboot\n");
-                new_content.push_str("COMPOSE_RESTART_POLICY=unless-stopped\n");
+            // Ports, profiles and data location the app expects
+            env.set("JSONRPC_PORT", &settings.jsonrpc_port.to_string());
+            env.set("GRPC_PORT", &settings.grpc_port.to_string());
+            env.set("P2P_PORT", &settings.p2p_port.to_string());
+            if settings.compose_profiles.is_empty() {
+                env.unset("COMPOSE_PROFILES");
+            } else {
+                env.set("COMPOSE_PROFILES", &settings.compose_profiles.join(","));
             }
-            
-            fs::write(&env_file, new_content)
-                .map_err(|e| format!("Failed to write env file: {}", e))?;
+            env.set("BASEDIR", &self.data_path.to_string_lossy());
+
+            // Desktop defaults, including auto-restart on system reboot
+            env.set_default("KOINOS_LOG_LEVEL", "warn");
+            env.set_default("KOINOS_LOG_JSON", "false");
+            env.set_default("COMPOSE_RESTART_POLICY", "unless-stopped");
+
+            write_atomic(&env_file, env.to_string().as_bytes())
+                .map_err(|e| e.context("Failed to write env file"))?;
         }
 
         Ok(())
     }
 
-    pub async fn download_snapshot(&self, progress_callback: impl Fn(f32)) -> Result<(), String> {
+    pub async fn download_snapshot(
+        &self,
+        control: &DownloadControl,
+        progress_callback: impl Fn(DownloadProgress),
+    ) -> Result<(), NodeError> {
         log_info("Starting snapshot download with resume support", None);
         
         // Check if blockchain data already exists and is valid
@@ -533,49 +746,125 @@ impl NodeManager {
                     Some(&format!("Chain: {}GB, BlockStore: {}GB", 
                         chain_size / 1_000_000_000, 
                         block_size / 1_000_000_000)));
-                progress_callback(100.0);
+                progress_callback(DownloadProgress::complete(chain_size + block_size));
                 return Ok(());
             }
         }
 
-        // Get latest snapshot URL
-        let snapshot_url = self.get_latest_snapshot_url().await?;
-        let snapshot_name = snapshot_url.split('/').last().unwrap_or("snapshot.tar.gz");
+        // Download with resume support - very long timeout for large downloads
+        let client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(86400)) // 24 hour timeout for 30GB download
+            .connect_timeout(std::time::Duration::from_secs(30)) // 30 second connection timeout
+            .build()
+            .map_err(|e| NodeError::from(e).context("Failed to create HTTP client"))?;
+        
+        // Pick the freshest snapshot across the configured mirrors
+        let sources = SnapshotSources::load();
+        let snapshot = snapshot_sources::resolve_latest(&client, &sources).await?;
+        if snapshot.local {
+            self.install_local_snapshot(Path::new(&snapshot.entry.url)).await?;
+            progress_callback(DownloadProgress::complete(snapshot.entry.size.unwrap_or_default()));
+            return Ok(());
+        }
+        
+        let snapshot_url = snapshot.entry.url.clone();
+        let snapshot_name = snapshot.entry.name.as_str();
+        log_info("Downloading snapshot", 
+            Some(&format!("{} from {} ({} fallback mirrors)", 
+                snapshot_name, snapshot.mirror, snapshot.fallbacks.len())));
         
         let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
         let snapshot_path = home.join(snapshot_name);
         
+        // Size and validators of the file on the server, to check partial downloads against
+        let remote = snapshot_verify::probe_remote(&client, &snapshot_url).await;
+        
+        // Unpack while downloading instead of keeping the whole archive on disk
+        if self.stream_snapshot_enabled() {
+            return snapshot_stream::stream_snapshot(
+                &client,
+                &snapshot_url,
+                &remote,
+                &self.data_path,
+                control,
+                progress_callback,
+            ).await;
+        }
+        
         // Also check for common snapshot filename
         let common_snapshot_path = home.join("koinos_snapshot.tar.gz");
         let actual_snapshot_path = if common_snapshot_path.exists() && !snapshot_path.exists() {
-            // Rename to expected name
-            fs::rename(&common_snapshot_path, &snapshot_path)
-                .map_err(|e| format!("Failed to rename snapshot: {}", e))?;
-            log_info("Renamed existing snapshot to expected filename", 
-                Some(&format!("koinos_snapshot.tar.gz -> {}", snapshot_name)));
+            // Only adopt it if it is a complete copy of the current snapshot - it will
+            // still be verified before extraction
+            let common_size = fs::metadata(&common_snapshot_path).map(|m| m.len()).unwrap_or(0);
+            if remote.size == Some(common_size) {
+                fs::rename(&common_snapshot_path, &snapshot_path)
+                    .map_err(|e| NodeError::from(e).context("Failed to rename snapshot"))?;
+                SnapshotManifest {
+                    url: snapshot_url.clone(),
+                    remote: remote.clone(),
+                    ..Default::default()
+                }.save(&snapshot_path)?;
+                log_info("Renamed existing snapshot to expected filename", 
+                    Some(&format!("koinos_snapshot.tar.gz -> {}", snapshot_name)));
+            } else {
+                log_warn("Ignoring koinos_snapshot.tar.gz", 
+                    Some("Its size does not match the current snapshot"));
+            }
             snapshot_path.clone()
         } else {
             snapshot_path.clone()
         };
         
+        // Fetch over several ranged connections when the server allows it; a download that
+        // was started that way is always continued that way. Its file is preallocated, so
+        // it can't be continued as a single stream - without the size it starts over.
+        let connections = self.snapshot_connections();
+        let has_segments = snapshot_downloader::has_segments(&actual_snapshot_path);
+        if has_segments && remote.size.is_none() {
+            log_warn("Can't continue the segmented download without the snapshot size, starting over", None);
+            fs::remove_file(&actual_snapshot_path).ok();
+            snapshot_downloader::remove_segments(&actual_snapshot_path);
+        }
+        let segmented = remote.size.is_some()
+            && (has_segments || (remote.accept_ranges && connections > 1));
+        
         // Check for existing partial download
+        let mut manifest = SnapshotManifest::load(&actual_snapshot_path);
         let mut resume_from = 0u64;
         if actual_snapshot_path.exists() {
             let existing_size = fs::metadata(&actual_snapshot_path)
                 .map(|m| m.len())
                 .unwrap_or(0);
+            let same_snapshot = manifest
+                .as_ref()
+                .map(|m| m.same_snapshot(&snapshot_url, &remote))
+                .unwrap_or(false);
             
-            if existing_size > 100_000_000 { // More than 100MB
+            if !same_snapshot {
+                // Never append to a file we can't prove is the same snapshot
+                log_warn("Discarding partial download from a different snapshot", 
+                    Some(&format!("{}MB", existing_size / 1_000_000)));
+                fs::remove_file(&actual_snapshot_path).ok();
+                snapshot_downloader::remove_segments(&actual_snapshot_path);
+                manifest = None;
+            } else if existing_size > 100_000_000 { // More than 100MB
                 resume_from = existing_size;
                 log_info("Found partial download", 
                     Some(&format!("Resuming from {:.1}GB ({}MB)", 
                         existing_size as f64 / 1_000_000_000.0, 
                         existing_size / 1_000_000)));
                 
-                // Report initial progress
-                let estimated_total = 36_872_000_000u64; // ~36.8GB
-                let initial_progress = (existing_size as f32 / estimated_total as f32) * 100.0;
-                progress_callback(initial_progress);
+                // Report initial progress; the segmented downloader reports its own
+                if !segmented {
+                    let estimated_total = remote.size.unwrap_or(36_872_000_000u64); // ~36.8GB
+                    progress_callback(DownloadProgress::measure(
+                        existing_size,
+                        estimated_total,
+                        0,
+                        std::time::Duration::ZERO,
+                    ));
+                }
             } else if existing_size > 0 {
                 // Small partial file, delete and start fresh
                 fs::remove_file(&actual_snapshot_path).ok();
@@ -584,158 +873,226 @@ impl NodeManager {
             }
         }
         
-        // Download with resume support - very long timeout for large downloads
-        let client = reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(86400)) // 24 hour timeout for 30GB download
-            .connect_timeout(std::time::Duration::from_secs(30)) // 30 second connection timeout
-            .build()
-            .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
-        
-        let mut request = client.get(&snapshot_url);
-        
-        // Add Range header for resume
-        if resume_from > 0 {
-            request = request.header("Range", format!("bytes={}-", resume_from));
-        }
-        
-        let response = request.send()
-            .await
-            .map_err(|e| format!("Failed to download snapshot: {}", e))?;
-        
-        // Check if server supports resume
-        let status = response.status();
-        if resume_from > 0 && status != reqwest::StatusCode::PARTIAL_CONTENT {
-            log_warn("Server doesn't support resume, starting fresh download", None);
-            resume_from = 0;
-            fs::remove_file(&snapshot_path).ok();
+        let mut manifest = manifest.unwrap_or_else(|| SnapshotManifest {
+            url: snapshot_url.clone(),
+            remote: remote.clone(),
+            ..Default::default()
+        });
+        if manifest.expected_sha256.is_none() {
+            manifest.expected_sha256 = snapshot.entry.sha256.clone();
         }
-        
-        let total_size = response.content_length()
-            .map(|s| s + resume_from)
-            .unwrap_or(30_000_000_000); // ~30GB
-        
-        // Open file for append if resuming, create if new
-        let mut file = if resume_from > 0 {
-            tokio::fs::OpenOptions::new()
-                .append(true)
-                .open(&actual_snapshot_path)
-                .await
-                .map_err(|e| format!("Failed to open snapshot file for resume: {}", e))?
+        // A partial file from another mirror continues from this one
+        manifest.url = snapshot_url.clone();
+        manifest.save(&actual_snapshot_path)?;
+        
+        let already_complete = resume_from > 0 && remote.size == Some(resume_from);
+        let mut hasher = None;
+        
+        if segmented {
+            let target = DownloadTarget {
+                url: snapshot_url.clone(),
+                fallbacks: snapshot.fallbacks.clone(),
+                path: actual_snapshot_path.clone(),
+                total_size: remote.size.unwrap_or_default(),
+                validator: manifest.remote.validator(),
+            };
+            snapshot_downloader::download_segmented(
+                &client,
+                &target,
+                connections,
+                control,
+                &progress_callback,
+            ).await?;
+        } else if already_complete {
+            log_info("Snapshot already fully downloaded", None);
         } else {
-            tokio::fs::File::create(&actual_snapshot_path)
-                .await
-                .map_err(|e| format!("Failed to create snapshot file: {}", e))?
-        };
-        
-        let mut downloaded = resume_from;
-        let mut stream = response.bytes_stream();
-        let mut last_checkpoint = downloaded;
-        let checkpoint_interval = 100_000_000; // Save progress every 100MB
-        
-        use tokio::io::AsyncWriteExt;
-        use futures_util::StreamExt;
-        
-        // Download with periodic checkpoints
-        let start_time = std::time::Instant::now();
-        let mut last_progress_time = std::time::Instant::now();
-        
-        while let Some(chunk_result) = stream.next().await {
-            let chunk = match chunk_result {
-                Ok(chunk) => chunk,
-                Err(e) => {
-                    // Save progress before failing
-                    file.flush().await.ok();
-                    log_warn("Download interrupted - will resume on retry", 
-                        Some(&format!("Downloaded {:.1}GB so far. Error: {}", 
-                            downloaded as f64 / 1_000_000_000.0, e)));
-                    
-                    return Err(format!(
-                        "Download interrupted at {:.1}GB of {:.1}GB. Will resume on next attempt. Error: {}", 
-                        downloaded as f64 / 1_000_000_000.0,
-                        total_size as f64 / 1_000_000_000.0,
-                        e
-                    ));
+            let mut request = client.get(&snapshot_url);
+            
+            // Add Range header for resume; If-Range makes the server send the whole file
+            // instead if it changed since the partial download was started
+            if resume_from > 0 {
+                request = request.header("Range", format!("bytes={}-", resume_from));
+                if let Some(validator) = manifest.remote.validator() {
+                    request = request.header("If-Range", validator);
                 }
-            };
+            }
             
-            file.write_all(&chunk)
+            let response = request.send()
                 .await
-                .map_err(|e| format!("Write error: {}", e))?;
+                .map_err(|e| NodeError::from(e).context("Failed to download snapshot"))?;
             
-            downloaded += chunk.len() as u64;
+            // Check if server supports resume
+            let status = response.status();
+            if resume_from > 0 && status != reqwest::StatusCode::PARTIAL_CONTENT {
+                log_warn("Server doesn't support resume or the snapshot changed, starting fresh download", None);
+                resume_from = 0;
+                fs::remove_file(&actual_snapshot_path).ok();
+                manifest = SnapshotManifest {
+                    url: snapshot_url.clone(),
+                    remote: RemoteSnapshotInfo::from_headers(response.headers(), response.content_length()),
+                    expected_sha256: snapshot.entry.sha256.clone(),
+                    ..Default::default()
+                };
+                manifest.save(&actual_snapshot_path)?;
+            }
             
-            // Save checkpoint periodically
-            if downloaded - last_checkpoint >= checkpoint_interval {
-                file.flush().await.ok();
-                last_checkpoint = downloaded;
-                log_debug("Download checkpoint saved", 
-                    Some(&format!("{:.1}GB of {:.1}GB", 
-                        downloaded as f64 / 1_000_000_000.0, 
-                        total_size as f64 / 1_000_000_000.0)));
+            // Hash while streaming; a resumed download is hashed from disk afterwards
+            if resume_from == 0 {
+                hasher = Some(Sha256::new());
             }
             
-            // Report progress every 5 seconds to avoid UI spam
-            if last_progress_time.elapsed() >= std::time::Duration::from_secs(5) {
-                let progress = (downloaded as f32 / total_size as f32) * 100.0;
-                let elapsed = start_time.elapsed().as_secs_f64();
-                let mb_per_sec = ((downloaded - resume_from) as f64 / 1_000_000.0) / elapsed;
-                let remaining_bytes = total_size - downloaded;
-                let eta_seconds = (remaining_bytes as f64 / 1_000_000.0) / mb_per_sec;
+            let known_size = response.content_length().map(|s| s + resume_from);
+            let total_size = known_size.unwrap_or(30_000_000_000); // ~30GB
+            
+            // Open file for append if resuming, create if new
+            let mut file = if resume_from > 0 {
+                tokio::fs::OpenOptions::new()
+                    .append(true)
+                    .open(&actual_snapshot_path)
+                    .await
+                    .map_err(|e| NodeError::from(e).context("Failed to open snapshot file for resume"))?
+            } else {
+                tokio::fs::File::create(&actual_snapshot_path)
+                    .await
+                    .map_err(|e| NodeError::from(e).context("Failed to create snapshot file"))?
+            };
+            
+            let mut downloaded = resume_from;
+            let mut stream = response.bytes_stream();
+            let mut fallbacks = snapshot.fallbacks.iter();
+            let mut last_checkpoint = downloaded;
+            let checkpoint_interval = 100_000_000; // Save progress every 100MB
+            
+            use tokio::io::AsyncWriteExt;
+            use futures_util::StreamExt;
+            
+            // Download with periodic checkpoints
+            let start_time = std::time::Instant::now();
+            let mut last_progress_time = std::time::Instant::now();
+            
+            while let Some(chunk_result) = stream.next().await {
+                let chunk = match chunk_result {
+                    Ok(chunk) => chunk,
+                    Err(e) => {
+                        // Save progress before failing
+                        file.flush().await.ok();
+                        
+                        // After a pause the server has most likely dropped us; reconnect to
+                        // it first. Otherwise continue the same range from another mirror.
+                        let reconnect = control.take_interrupted().then_some(&snapshot_url);
+                        let mut resumed = None;
+                        for url in reconnect.into_iter().chain(fallbacks.by_ref()) {
+                            match snapshot_sources::continue_from_mirror(&client, url, downloaded, known_size).await {
+                                Ok(response) => {
+                                    resumed = Some(response);
+                                    break;
+                                }
+                                Err(err) => log_warn("Mirror can't continue download", Some(&err.to_string())),
+                            }
+                        }
+                        if let Some(response) = resumed {
+                            log_warn("Download interrupted, reconnected", 
+                                Some(&format!("{:.1}GB so far. Error: {}", 
+                                    downloaded as f64 / 1_000_000_000.0, e)));
+                            stream = response.bytes_stream();
+                            continue;
+                        }
+                        
+                        log_warn("Download interrupted - will resume on retry", 
+                            Some(&format!("Downloaded {:.1}GB so far. Error: {}", 
+                                downloaded as f64 / 1_000_000_000.0, e)));
+                        
+                        return Err(NodeError::DownloadInterrupted(format!(
+                            "Download interrupted at {:.1}GB of {:.1}GB. Will resume on next attempt. Error: {}", 
+                            downloaded as f64 / 1_000_000_000.0,
+                            total_size as f64 / 1_000_000_000.0,
+                            e
+                        )));
+                    }
+                };
+                
+                file.write_all(&chunk)
+                    .await
+                    .map_err(|e| NodeError::from(e).context("Write error"))?;
+                if let Some(hasher) = hasher.as_mut() {
+                    hasher.update(&chunk);
+                }
+                
+                downloaded += chunk.len() as u64;
+                
+                // Pause, schedule window, rate limit and cancel
+                if let Err(e) = control.checkpoint(chunk.len()).await {
+                    file.flush().await.ok();
+                    return Err(e);
+                }
                 
-                log_info("Download progress", 
-                    Some(&format!("{:.1}% - {:.1}GB/{:.1}GB - {:.1} MB/s - ETA: {} min", 
-                        progress,
-                        downloaded as f64 / 1_000_000_000.0,
-                        total_size as f64 / 1_000_000_000.0,
-                        mb_per_sec,
-                        (eta_seconds / 60.0) as u32)));
+                // Save checkpoint periodically
+                if downloaded - last_checkpoint >= checkpoint_interval {
+                    file.flush().await.ok();
+                    last_checkpoint = downloaded;
+                    log_debug("Download checkpoint saved", 
+                        Some(&format!("{:.1}GB of {:.1}GB", 
+                            downloaded as f64 / 1_000_000_000.0, 
+                            total_size as f64 / 1_000_000_000.0)));
+                }
                 
-                progress_callback(progress);
-                last_progress_time = std::time::Instant::now();
+                // Report progress every 5 seconds to avoid UI spam
+                if last_progress_time.elapsed() >= std::time::Duration::from_secs(5) {
+                    let progress = DownloadProgress::measure(
+                        downloaded,
+                        total_size,
+                        downloaded - resume_from,
+                        start_time.elapsed(),
+                    );
+                    progress.log();
+                    progress_callback(progress);
+                    last_progress_time = std::time::Instant::now();
+                }
             }
+            
+            // Final flush
+            file.flush().await
+                .map_err(|e| NodeError::from(e).context("Failed to flush file"))?;
+            
+            log_info("Download completed", 
+                Some(&format!("Total: {}GB", downloaded / 1_000_000_000)));
         }
         
-        // Final flush
-        file.flush().await
-            .map_err(|e| format!("Failed to flush file: {}", e))?;
-        
-        log_info("Download completed", 
-            Some(&format!("Total: {}GB", downloaded / 1_000_000_000)));
+        // Verify before extracting; a previously verified file only needs a size check
+        let size_on_disk = fs::metadata(&actual_snapshot_path).map(|m| m.len()).ok();
+        let previously_verified = manifest.verified && size_on_disk == manifest.remote.size;
+        if !previously_verified {
+            let computed = match hasher {
+                Some(hasher) => snapshot_verify::hex_digest(hasher),
+                None => snapshot_verify::sha256_file(&actual_snapshot_path).await?,
+            };
+            snapshot_verify::verify_snapshot(
+                &client,
+                &actual_snapshot_path,
+                &mut manifest,
+                computed,
+            ).await?;
+        }
 
         // Extract snapshot
         self.extract_snapshot(&actual_snapshot_path).await?;
         
         // Clean up
         fs::remove_file(&actual_snapshot_path).ok();
+        SnapshotManifest::remove(&actual_snapshot_path);
         
         Ok(())
     }
 
-    async fn get_latest_snapshot_url(&self) -> Result<String, String> {
-        let response = reqwest::get("https://backup.koinosblocks.com/")
-            .await
-            .map_err(|e| format!("Failed to fetch snapshot list: {}", e))?
-            .text()
-            .await
-            .map_err(|e| format!("Failed to read snapshot list: {}", e))?;
-        
-        // Parse HTML to find latest backup file
-        let re = regex::Regex::new(r"backup_\d{4}-\d{2}-\d{2}\.tar\.gz")
-            .map_err(|e| format!("Regex error: {}", e))?;
-        
-        let mut snapshots: Vec<String> = re.find_iter(&response)
-            .map(|m| m.as_str().to_string())
-            .collect();
-        
-        snapshots.sort();
-        
-        let latest = snapshots.last()
-            .ok_or_else(|| "No snapshots found".to_string())?;
-        
-        Ok(format!("https://backup.koinosblocks.com/{}", latest))
+    // A snapshot from a local mirror is used in place; it belongs to the user, so it is
+    // neither quarantined nor deleted
+    async fn install_local_snapshot(&self, snapshot_path: &Path) -> Result<(), NodeError> {
+        log_info("Using local snapshot", Some(&snapshot_path.display().to_string()));
+        snapshot_verify::verify_gzip(snapshot_path).await?;
+        self.extract_snapshot(snapshot_path).await
     }
 
-    async fn extract_snapshot(&self, snapshot_path: &Path) -> Result<(), String> {
+    async fn extract_snapshot(&self, snapshot_path: &Path) -> Result<(), NodeError> {
         log_info("Starting snapshot extraction", 
             Some(&format!("File: {}", snapshot_path.display())));
         
@@ -746,35 +1103,25 @@ impl NodeManager {
             .arg(dirs::home_dir().unwrap_or_else(|| PathBuf::from(".")))
             .output()
             .await
-            .map_err(|e| format!("Failed to extract snapshot: {}", e))?;
+            .map_err(|e| NodeError::from(e).context("Failed to extract snapshot"))?;
         
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
             log_error("Snapshot extraction failed", Some(&stderr));
-            return Err(format!("Failed to extract snapshot: {}", stderr));
+            return Err(NodeError::SnapshotCorrupt(format!("Failed to extract snapshot: {}", stderr)));
         }
         
         log_info("Snapshot extracted successfully", None);
 
         // Move extracted data to .koinos
         fs::create_dir_all(&self.data_path)
-            .map_err(|e| format!("Failed to create data directory: {}", e))?;
+            .map_err(|e| NodeError::from(e).context("Failed to create data directory"))?;
         
         let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
         log_info("Moving extracted directories to koinos data path", 
             Some(&format!("From: {} To: {}", home.display(), self.data_path.display())));
         
-        for dir in &[
-            "chain",
-            "block_store",
-            "account_history",
-            "contract_meta_store",
-            "transaction_store",
-            "mempool",
-            "p2p",
-            "grpc",
-            "jsonrpc",
-        ] {
+        for dir in snapshot_stream::SNAPSHOT_DIRS {
             let src = home.join(dir);
             let dst = self.data_path.join(dir);
             if src.exists() {
@@ -786,7 +1133,7 @@ impl NodeManager {
                 }
                 
                 fs::rename(&src, &dst)
-                    .map_err(|e| format!("Failed to move {}: {}", dir, e))?;
+                    .map_err(|e| NodeError::from(e).context(&format!("Failed to move {}", dir)))?;
                 log_info("Moved directory", Some(dir));
             } else {
                 log_warn("Directory not found in extracted data", Some(dir));
@@ -797,7 +1144,7 @@ impl NodeManager {
         Ok(())
     }
 
-    pub async fn resume_sync_if_needed(&self) -> Result<(), String> {
+    pub async fn resume_sync_if_needed(&self) -> Result<(), NodeError> {
         // Load saved state to resume from last position
         let state_manager = self.state_manager.lock().unwrap();
         let saved_state = state_manager.get_state();
@@ -812,34 +1159,93 @@ impl NodeManager {
             let mut status = self.status.lock().unwrap();
             status.current_block = saved_state.last_block;
             status.sync_progress = saved_state.last_sync_progress;
-            
-            // If sync was incomplete, mark as syncing
-            if !saved_state.first_sync_completed {
-                status.status = "syncing".to_string();
-            }
         }
         
         Ok(())
     }
     
-    pub async fn start_node(&self) -> Result<(), String> {
+    pub async fn start_node(&self) -> Result<(), NodeError> {
+        if matches!(self.lifecycle.phase(), NodePhase::Syncing | NodePhase::Running) {
+            return Err(NodeError::NodeRunning("The node is already running".to_string()));
+        }
+
+        self.lifecycle.transition(NodePhase::Starting, "Start requested")?;
+        let result = match self.mode {
+            NodeMode::Docker => self.start_containers().await,
+            NodeMode::Native => self.native.start_node().await,
+        };
+        if let Err(e) = result {
+            // Nothing was started if a prerequisite is missing
+            let next = match e {
+                NodeError::NotInitialized(_)
+                | NodeError::DockerMissing(_)
+                | NodeError::DaemonDown(_)
+                | NodeError::ComposeMissing(_) => NodePhase::Stopped,
+                _ => NodePhase::Error,
+            };
+            self.lifecycle.observe(next, &format!("Failed to start: {}", e));
+            return Err(e);
+        }
+
+        // Resume from saved checkpoint
+        if let Err(e) = self.resume_sync_if_needed().await {
+            self.lifecycle.observe(NodePhase::Error, &format!("Failed to resume sync: {}", e));
+            return Err(e);
+        }
+        
+        let (first_sync_completed, last_block) = {
+            let state_manager = self.state_manager.lock().unwrap();
+            let saved_state = state_manager.get_state();
+            (saved_state.first_sync_completed, saved_state.last_block)
+        };
+        if first_sync_completed {
+            self.lifecycle.observe(NodePhase::Running, "Services started");
+        } else {
+            self.lifecycle.observe(NodePhase::Syncing, "Services started, initial sync incomplete");
+        }
+        log_info("Node started", Some(&format!("Resuming from block {}", last_block)));
+
+        Ok(())
+    }
+
+    /// `start_node`, stopping whatever came up and settling the lifecycle if `cancel`
+    /// fires before it finishes
+    pub async fn start_node_until(&self, cancel: &CancellationToken) -> Result<(), NodeError> {
+        if cancel.is_cancelled() {
+            return Err(NodeError::Cancelled("Node start cancelled".to_string()));
+        }
+        tokio::select! {
+            result = self.start_node() => result,
+            _ = cancel.cancelled() => {
+                log_warn("Node start cancelled, stopping services", None);
+                if let Err(e) = self.stop_node().await {
+                    log_error("Failed to stop services after cancelled start", Some(&e.to_string()));
+                }
+                Err(NodeError::Cancelled("Node start cancelled".to_string()))
+            }
+        }
+    }
+
+    async fn start_containers(&self) -> Result<(), NodeError> {
         // Check if koinos directory exists
         if !self.koinos_path.exists() {
-            return Err("Koinos not initialized. Please run setup first.".to_string());
+            return Err(NodeError::NotInitialized("Koinos not initialized. Please run setup first.".to_string()));
         }
 
         // Check if docker-compose.yml exists
         if !self.koinos_path.join("docker-compose.yml").exists() {
-            return Err("docker-compose.yml not found. Please run setup first.".to_string());
+            return Err(NodeError::NotInitialized("docker-compose.yml not found. Please run setup first.".to_string()));
         }
 
-        // Check if Docker daemon is running (resolve docker path robustly)
-        if !self.docker_info_ok() {
+        // Check if the container daemon is running
+        if !self.runtime.info().daemon_running {
             // Try to start Docker Desktop on macOS
             #[cfg(target_os = "macos")]
             {
-                if std::path::Path::new("/Applications/Docker.app").exists() {
-                    Command::new("open")
+                if self.runtime.kind() == RuntimeKind::Docker
+                    && std::path::Path::new("/Applications/Docker.app").exists()
+                {
+                    std::process::Command::new("open")
                         .arg("/Applications/Docker.app")
                         .spawn()
                         .ok();
@@ -850,104 +1256,104 @@ impl NodeManager {
                         tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
                         
                         // Check Docker status
-                        if let Some(docker) = self.find_docker_path() {
-                            if let Ok(output) = Command::new(&docker).arg("info").output() {
-                                if output.status.success() {
-                                    log_info("Docker Desktop started successfully", None);
-                                    break;
-                                }
-                                let stderr = String::from_utf8_lossy(&output.stderr);
-                                if stderr.contains("Docker Desktop is starting") {
-                                    log_debug(&format!("Docker Desktop still starting... ({}/30)", i + 1), None);
-                                    continue;
-                                }
-                            }
+                        let info = self.runtime.info();
+                        if info.daemon_running {
+                            log_info("Docker Desktop started successfully", None);
+                            break;
+                        }
+                        if info.error.as_deref().unwrap_or("").contains("Docker Desktop is starting") {
+                            log_debug(&format!("Docker Desktop still starting... ({}/30)", i + 1), None);
+                            continue;
                         }
                         
                         if i == 29 {
-                            return Err("Docker Desktop is taking too long to start. Please ensure Docker is fully started and try again.".to_string());
+                            return Err(NodeError::DaemonDown("Docker Desktop is taking too long to start. Please ensure Docker is fully started and try again.".to_string()));
                         }
                     }
                 } else {
-                    return Err("Docker is not running. Please start Docker Desktop and try again.".to_string());
+                    return Err(NodeError::DaemonDown(format!(
+                        "{} is not running. Please start it and try again.",
+                        self.runtime.kind().display_name()
+                    )));
                 }
             }
             
             #[cfg(not(target_os = "macos"))]
-            return Err("Docker daemon is not running. Please start Docker and try again.".to_string());
-        }
-
-        // Update status
-        {
-            let mut status = self.status.lock().unwrap();
-            status.status = "starting".to_string();
+            return Err(NodeError::DaemonDown(format!(
+                "{} daemon is not running. Please start it and try again.",
+                self.runtime.kind().display_name()
+            )));
         }
 
-        // Start Docker containers using the 'all' profile with robust compose detection
-        let (program, mut base_args) = self
-            .compose_invocation()
-            .ok_or_else(|| "Neither 'docker compose' nor 'docker-compose' is available".to_string())?;
-        base_args.extend(vec!["--profile".into(), "all".into(), "up".into(), "-d".into()]);
-        let output = AsyncCommand::new(program)
-            .args(base_args)
-            .current_dir(&self.koinos_path)
-            .output()
+        // Start the containers of the selected services
+        self.runtime
+            .compose_up(&self.koinos_path, &self.compose_profiles())
             .await
-            .map_err(|e| format!("Failed to start node: {}", e))?;
+    }
 
-        if !output.status.success() {
-            let error = String::from_utf8_lossy(&output.stderr);
-            return Err(format!("Failed to start node: {}", error));
+    pub async fn stop_node(&self) -> Result<(), NodeError> {
+        // Stopping an already stopped node still runs compose down, which cleans up
+        // containers left over from a previous session
+        if self.lifecycle.phase() != NodePhase::Stopped {
+            self.lifecycle.transition(NodePhase::Stopping, "Stop requested")?;
+        }
+
+        let result = match self.mode {
+            // Every profile, so services switched off since they were started stop too
+            NodeMode::Docker => {
+                self.runtime
+                    .compose_down(&self.koinos_path, &NodeRole::All.profiles())
+                    .await
+            }
+            NodeMode::Native => self.native.stop_node().await,
+        };
+        if let Err(e) = result {
+            self.lifecycle.observe(NodePhase::Error, &format!("Failed to stop: {}", e));
+            return Err(e);
         }
 
-        // Resume from saved checkpoint
-        self.resume_sync_if_needed().await?;
-        
         // Update status
         {
             let mut status = self.status.lock().unwrap();
-            let state_manager = self.state_manager.lock().unwrap();
-            let saved_state = state_manager.get_state();
-            
-            status.status = if saved_state.first_sync_completed {
-                "running".to_string()
-            } else {
-                "syncing".to_string()
-            };
-            
-            log_info("Node started", 
-                Some(&format!("Resuming from block {}", saved_state.last_block)));
+            status.sync_progress = 0.0;
+            status.peers_count = 0;
         }
+        self.sync.reset();
+        self.peers.reset();
+        self.lifecycle.observe(NodePhase::Stopped, "Services stopped");
 
         Ok(())
     }
 
-    pub async fn stop_node(&self) -> Result<(), String> {
-        let (program, mut base_args) = self
-            .compose_invocation()
-            .ok_or_else(|| "Neither 'docker compose' nor 'docker-compose' is available".to_string())?;
-        base_args.extend(vec!["--profile".into(), "all".into(), "down".into()]);
-        let output = AsyncCommand::new(program)
-            .args(base_args)
-            .current_dir(&self.koinos_path)
-            .output()
-            .await
-            .map_err(|e| format!("Failed to stop node: {}", e))?;
-
-        if !output.status.success() {
-            let error = String::from_utf8_lossy(&output.stderr);
-            return Err(format!("Failed to stop node: {}", error));
+    // Whether the node's services are up; None if that couldn't be checked
+    async fn services_running(&self) -> Option<bool> {
+        match self.mode {
+            NodeMode::Docker => match self.runtime.compose_ps(&self.koinos_path).await {
+                // Check if koinos containers are running
+                Ok(output_str) => Some(output_str.contains("koinos") && output_str.contains("running")),
+                // A failed compose command says nothing about the containers
+                Err(e) => {
+                    log_debug("Compose status check failed", Some(&e.to_string()));
+                    None
+                }
+            },
+            NodeMode::Native => Some(self.native.is_running()),
         }
+    }
 
-        // Update status
-        {
-            let mut status = self.status.lock().unwrap();
-            status.status = "stopped".to_string();
-            status.sync_progress = 0.0;
-            status.peers_count = 0;
+    /// Pick up a node still running from a previous session. The lifecycle starts out
+    /// stopped, and status checks only probe a node they know to be up.
+    pub async fn reconcile_phase(&self) {
+        if self.lifecycle.phase() != NodePhase::Stopped || !self.is_initialized() {
+            return;
         }
-
-        Ok(())
+        if self.services_running().await != Some(true) {
+            return;
+        }
+        self.lifecycle.observe(NodePhase::Starting, "Found the node running from a previous session");
+        // Syncing until the first status check has compared the head with the network
+        self.lifecycle.observe(NodePhase::Syncing, "Checking sync state of the running node");
+        self.get_node_status().await;
     }
 
     pub async fn get_node_status(&self) -> NodeStatus {
@@ -955,291 +1361,179 @@ impl NodeManager {
         
         log_debug("Getting node status...", None);
         
-        // Check if containers are actually running
-        if status.status != "stopped" {
-            let compose = self.compose_invocation();
-            let check = if let Some((program, mut args)) = compose {
-                args.extend(vec!["ps".into(), "--format".into(), "json".into()]);
-                Command::new(program)
-                    .args(args)
-                    .current_dir(&self.koinos_path)
-                    .output()
-            } else {
-                // Fallback attempt with default docker compose
-                Command::new("docker")
-                    .arg("compose")
-                    .arg("ps")
-                    .arg("--format")
-                    .arg("json")
-                    .current_dir(&self.koinos_path)
-                    .output()
-            };
+        // Check if the services are actually running. Not while stopping, and not while
+        // starting either, since start_node settles that itself.
+        let phase = self.lifecycle.phase();
+        if matches!(phase, NodePhase::Syncing | NodePhase::Running | NodePhase::Error) {
+            let services_running = self.services_running().await;
             
-            if let Ok(output) = check {
-                if output.status.success() {
-                    let output_str = String::from_utf8_lossy(&output.stdout);
-                    // Check if koinos containers are running
-                    if output_str.contains("koinos") && output_str.contains("running") {
-                        // Try to get actual blockchain height from JSON-RPC
-                        if let Ok(height) = self.get_blockchain_height().await {
-                            status.current_block = height;
-                            
-                            // Get actual target height from Koinos mainnet API
-                            let mut target_block = 43_000_000u64; // Fallback estimate
-                            
-                            // Try to get real mainnet height from Koinos API
-                            if let Ok(mainnet_height) = self.get_mainnet_height().await {
-                                target_block = mainnet_height;
-                                log_debug(&format!("Got mainnet height from API: {}", mainnet_height), None);
-                            } else {
-                                // Fallback: Try to estimate from sync logs
-                                if let Ok(logs_output) = AsyncCommand::new("docker")
-                                    .arg("logs")
-                                    .arg("--tail")
-                                    .arg("5")
-                                    .arg("koinos-chain-1")
-                                    .output()
-                                    .await
-                                {
-                                    let chain_logs = String::from_utf8_lossy(&logs_output.stdout);
-                                    if let Some(line) = chain_logs.lines().filter(|l| l.contains("block time remaining")).last() {
-                                        // Parse days remaining like "122d, 09h, 25m, 09s"
-                                        if let Some(start) = line.find("(") {
-                                            if let Some(end) = line.find("d,") {
-                                                if let Ok(days) = line[start + 1..end].trim().parse::<f32>() {
-                                                    // Koinos averages ~1000 blocks per day
-                                                    let blocks_remaining = (days * 1000.0) as u64;
-                                                    target_block = height + blocks_remaining;
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                            
-                            status.target_block = target_block;
-                            
-                            if height > 0 {
-                                status.sync_progress = if status.target_block > 0 {
-                                    ((height as f32 / status.target_block as f32) * 100.0).min(100.0)
-                                } else {
-                                    0.0
-                                };
-                                
-                                status.status = if status.sync_progress >= 99.9 {
-                                    "running".to_string()
-                                } else {
-                                    "syncing".to_string()
-                                };
-                            }
-                            
-                            // Save state
-                            let mut state_manager = self.state_manager.lock().unwrap();
-                            state_manager.update_sync_progress(height, status.sync_progress);
+            if services_running == Some(true) {
+                self.lifecycle.probe_ok();
+                let rpc = self.local_rpc();
+                if let Ok(head) = rpc.head_info().await {
+                    let height = head.head_topology.height;
+                    if height > 0 && self.sync.genesis_time().is_none() {
+                        if let Some(genesis) = sync_tracker::fetch_genesis_time(&rpc, &head).await {
+                            self.sync.set_genesis_time(genesis);
+                        }
+                    }
+
+                    let estimate = self.sync.record(&head);
+                    status.current_block = height;
+                    status.target_block = estimate.target_block;
+                    status.blocks_per_sec = estimate.blocks_per_sec;
+                    status.head_lag_seconds = Some(estimate.head_lag_seconds);
+                    status.eta_seconds = estimate.eta_seconds;
+                    
+                    if height > 0 {
+                        status.sync_progress = estimate.progress;
+                        
+                        if estimate.synced {
+                            self.lifecycle.observe(NodePhase::Running, "Caught up with the network");
+                        } else {
+                            self.lifecycle.observe(
+                                NodePhase::Syncing,
+                                &format!("Syncing, {:.1}% complete", status.sync_progress),
+                            );
                         }
-                    } else {
-                        status.status = "stopped".to_string();
                     }
+                    
+                    // Save state
+                    let mut state_manager = self.state_manager.lock().unwrap();
+                    state_manager.update_sync_progress(height, status.sync_progress);
+                }
+
+                match self.peers.report(&rpc).await {
+                    Ok(report) => status.peers_count = report.peers.len() as u32,
+                    Err(e) => log_debug("Failed to get peers", Some(&e.to_string())),
+                }
+            } else if services_running == Some(false) && phase != NodePhase::Error {
+                let missed = self.lifecycle.probe_missed();
+                if missed >= MISSED_PROBES_BEFORE_ERROR {
+                    self.lifecycle.observe(NodePhase::Error, "Services are no longer running");
                 } else {
-                    // Docker compose command failed - likely containers not running
-                    status.status = "stopped".to_string();
+                    log_debug("Services not running", Some(&format!("check {}/{}", missed, MISSED_PROBES_BEFORE_ERROR)));
                 }
             }
         }
         
+        let state = self.lifecycle.info();
+        status.status = state.phase;
+        status.error_message = (state.phase == NodePhase::Error).then_some(state.reason);
         status
     }
     
-    async fn get_mainnet_height(&self) -> Result<u64, String> {
-        // Get current mainnet height from public Koinos API
-        let client = reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(5))
-            .build()
-            .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
-        
-        let body = serde_json::json!({
-            "jsonrpc": "2.0",
-            "method": "chain.get_head_info",
-            "params": {},
-            "id": 1
-        });
-        
-        let response = client
-            .post("https://api.koinos.io")
-            .header("Content-Type", "application/json")
-            .body(body.to_string())
-            .send()
-            .await;
-        
-        if let Ok(resp) = response {
-            if let Ok(text) = resp.text().await {
-                if let Ok(json) = serde_json::from_str::<serde_json::Value>(&text) {
-                    if let Some(result) = json.get("result") {
-                        if let Some(head_topology) = result.get("head_topology") {
-                            if let Some(height) = head_topology.get("height") {
-                                if let Some(height_str) = height.as_str() {
-                                    return height_str.parse::<u64>()
-                                        .map_err(|e| format!("Failed to parse mainnet height: {}", e));
-                                }
-                            }
-                        }
-                    }
-                }
+    // Recent log output of a service, from its container or its native log file
+    async fn service_logs(&self, service: &str, tail: u32) -> Option<String> {
+        match self.mode {
+            NodeMode::Docker => {
+                let container = self
+                    .runtime
+                    .find_container(&self.compose_project(), service)
+                    .await?;
+                self.runtime.logs(&container, tail).await.ok()
             }
+            NodeMode::Native => self.native.tail_log(service, tail as usize),
         }
-        
-        Err("Failed to get mainnet height".to_string())
     }
     
-    async fn get_blockchain_height(&self) -> Result<u64, String> {
-        // Call Koinos JSON-RPC to get current height  
-        let client = reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(2))
-            .build()
-            .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
-        
-        let body = serde_json::json!({
-            "jsonrpc": "2.0",
-            "method": "chain.get_head_info",
-            "params": {},
-            "id": 1
-        });
-        
-        let response = client
-            .post("http://127.0.0.1:8080")
-            .header("Content-Type", "application/json")
-            .body(body.to_string())
-            .send()
-            .await;
-        
-        if let Ok(resp) = response {
-            if let Ok(text) = resp.text().await {
-                if let Ok(json) = serde_json::from_str::<serde_json::Value>(&text) {
-                if let Some(result) = json.get("result") {
-                    if let Some(head_topology) = result.get("head_topology") {
-                        if let Some(height) = head_topology.get("height") {
-                            if let Some(height_str) = height.as_str() {
-                                return height_str.parse::<u64>()
-                                    .map_err(|e| format!("Failed to parse height: {}", e));
-                            }
-                        }
-                    }
-                }
+    pub async fn get_peers(&self) -> Result<PeerReport, NodeError> {
+        self.peers.report(&self.local_rpc()).await
+    }
+
+    /// Client for the local node's JSON-RPC port
+    pub fn rpc(&self) -> KoinosRpc {
+        KoinosRpc::local(self.settings.get().jsonrpc_port)
+    }
+
+    // The status loop polls again shortly, so don't retry
+    fn local_rpc(&self) -> KoinosRpc {
+        self.rpc()
+            .with_timeout(std::time::Duration::from_secs(2))
+            .with_retries(0)
+    }
+
+    /// Whether each of the active services is running, as a container or a native
+    /// process
+    pub async fn service_statuses(&self) -> Vec<(&'static str, bool)> {
+        let project = self.compose_project();
+        let active = self.active_services();
+        match self.mode {
+            NodeMode::Docker => {
+                let running = self.runtime.running_containers().await.unwrap_or_default();
+                active
+                    .into_iter()
+                    .map(|service| {
+                        let up = running
+                            .iter()
+                            .any(|name| container_runtime::container_matches(name, &project, service));
+                        (service, up)
+                    })
+                    .collect()
             }
+            NodeMode::Native => {
+                // Services without a native build aren't reported
+                let running = self.native.running_services();
+                active
+                    .into_iter()
+                    .filter_map(|service| Some((service, *running.get(service)?)))
+                    .collect()
             }
         }
-        
-        Err("Failed to get blockchain height".to_string())
     }
 
-    pub async fn get_detailed_status(&self) -> Result<serde_json::Value, String> {
-        // Run docker compose ps to get container status
-        let ps_output = AsyncCommand::new("docker")
-            .arg("compose")
-            .arg("ps")
-            .current_dir(&self.koinos_path)
-            .output()
-            .await
-            .map_err(|e| format!("Failed to get container status: {}", e))?;
-        
-        let containers_status = String::from_utf8_lossy(&ps_output.stdout);
-        
-        // Get current block height from the node's JSON-RPC (same as main status)
-        let mut current_block = 0u64;
-        let mut sync_time_remaining = String::from("Unknown");
-        
-        // Try to get actual blockchain height from local node
-        if let Ok(height) = self.get_blockchain_height().await {
-            current_block = height;
-        }
-        
-        // Get chain logs for sync time remaining
-        let logs_output = AsyncCommand::new("docker")
-            .arg("logs")
-            .arg("--tail")
-            .arg("10")
-            .arg("koinos-chain-1")
-            .output()
-            .await
-            .map_err(|e| format!("Failed to get chain logs: {}", e))?;
-        
-        let chain_logs = String::from_utf8_lossy(&logs_output.stdout);
-        
-        // Parse time remaining from logs
-        for line in chain_logs.lines().rev() {
-            if line.contains("Sync progress") && line.contains("block time remaining") {
-                // Parse time remaining
-                if let Some(start) = line.find("(") {
-                    if let Some(end) = line.find(" block time remaining") {
-                        sync_time_remaining = line[start + 1..end].to_string();
-                        break;
-                    }
-                }
-            }
-        }
+    pub async fn get_detailed_status(&self) -> Result<serde_json::Value, NodeError> {
+        let project = self.compose_project();
+        
+        // Sync figures come from the same tracker as the main status
+        let head = self.local_rpc().head_info().await.ok();
+        let estimate = head.as_ref().map(|head| self.sync.record(head));
+        let current_block = head.map(|head| head.head_topology.height).unwrap_or(0);
+        
+        // Get actual running containers (or native processes)
+        let running_containers = match self.mode {
+            NodeMode::Docker => self.runtime.running_containers().await.unwrap_or_default(),
+            NodeMode::Native => Vec::new(),
+        };
+        let find_container = |service: &str| {
+            running_containers
+                .iter()
+                .find(|name| container_runtime::container_matches(name, &project, service))
+                .cloned()
+        };
+        let chain_container = find_container("chain");
         
         // Check P2P peers
-        let p2p_logs = AsyncCommand::new("docker")
-            .arg("logs")
-            .arg("--tail")
-            .arg("20")
-            .arg("koinos-p2p-1")
-            .output()
-            .await
-            .map_err(|e| format!("Failed to get P2P logs: {}", e))?;
-        
-        let p2p_status = String::from_utf8_lossy(&p2p_logs.stdout);
-        let peer_count = p2p_status.matches("Connected to peer").count();
+        let peer_count = match self.get_peers().await {
+            Ok(report) => report.peers.len(),
+            Err(_) => 0,
+        };
         
         // Get disk usage
-        let disk_usage = AsyncCommand::new("docker")
-            .arg("exec")
-            .arg("koinos-chain-1")
-            .arg("du")
-            .arg("-sh")
-            .arg("/koinos")
-            .output()
-            .await
-            .map_err(|e| format!("Failed to get disk usage: {}", e))?;
-        
-        let disk_size = String::from_utf8_lossy(&disk_usage.stdout);
-        
-        // Get mainnet height for comparison
-        let mainnet_height = self.get_mainnet_height().await.unwrap_or(0);
-        let sync_percentage = if mainnet_height > 0 && current_block > 0 {
-            ((current_block as f32 / mainnet_height as f32) * 100.0).min(100.0)
-        } else {
-            0.0
+        let disk_size = match (&self.mode, &chain_container) {
+            (NodeMode::Docker, Some(container)) => self
+                .runtime
+                .exec(container, &["du", "-sh", "/koinos"])
+                .await
+                .unwrap_or_default(),
+            (NodeMode::Native, _) => {
+                format!("{:.1}G", get_dir_size(&self.data_path) as f64 / 1_000_000_000.0)
+            }
+            _ => String::new(),
         };
         
-        // Check each container status individually using docker ps
-        let services = vec![
-            "chain", "p2p", "block_store", "mempool", "jsonrpc", "grpc", "rest",
-            "account_history", "transaction_store", "contract_meta_store", "block_producer", "amqp"
-        ];
-        
-        // Get actual running containers
-        let running_containers = AsyncCommand::new("docker")
-            .arg("ps")
-            .arg("--format")
-            .arg("{{.Names}}")
-            .output()
-            .await
-            .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
-            .unwrap_or_default();
-        
+        // Check each container status individually
         let mut container_statuses = serde_json::Map::new();
-        for service in services {
-            let container_name = format!("koinos-{}-1", service);
-            let is_running = running_containers.contains(&container_name);
+        for (service, is_running) in self.service_statuses().await {
             container_statuses.insert(service.to_string(), serde_json::Value::Bool(is_running));
         }
         
         // Check network ports
+        let settings = self.settings.get();
         let jsonrpc_available = AsyncCommand::new("nc")
             .arg("-z")
             .arg("localhost")
-            .arg("8080")
+            .arg(settings.jsonrpc_port.to_string())
             .output()
             .await
             .map(|o| o.status.success())
@@ -1248,7 +1542,7 @@ impl NodeManager {
         let grpc_available = AsyncCommand::new("nc")
             .arg("-z")
             .arg("localhost")
-            .arg("50051")
+            .arg(settings.grpc_port.to_string())
             .output()
             .await
             .map(|o| o.status.success())
@@ -1257,25 +1551,31 @@ impl NodeManager {
         let p2p_available = AsyncCommand::new("nc")
             .arg("-z")
             .arg("localhost")
-            .arg("8888")
+            .arg(settings.p2p_port.to_string())
             .output()
             .await
             .map(|o| o.status.success())
             .unwrap_or(false);
         
         // Get recent errors
-        let error_logs = AsyncCommand::new("docker")
-            .arg("compose")
-            .arg("logs")
-            .arg("--tail")
-            .arg("100")
-            .current_dir(&self.koinos_path)
-            .output()
-            .await
-            .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
-            .unwrap_or_default();
+        let error_logs = match self.mode {
+            NodeMode::Docker => self.runtime.compose_logs(&self.koinos_path, 100).await.unwrap_or_default(),
+            NodeMode::Native => self.service_logs("chain", 100).await.unwrap_or_default(),
+        };
         
         let error_count = error_logs.matches("error").count();
+        
+        // Restart count of the chain container, a good hint for crash loops
+        let chain_restarts = match &chain_container {
+            Some(container) => self
+                .runtime
+                .inspect(container)
+                .await
+                .ok()
+                .and_then(|v| v.get(0).and_then(|c| c.get("RestartCount")).and_then(|r| r.as_u64()))
+                .unwrap_or(0),
+            None => 0,
+        };
         let last_error = error_logs
             .lines()
             .filter(|l| l.to_lowercase().contains("error"))
@@ -1288,9 +1588,10 @@ impl NodeManager {
             "containers": container_statuses,
             "sync": {
                 "current_block": current_block,
-                "target_block": mainnet_height,
-                "percentage": sync_percentage,
-                "time_remaining": sync_time_remaining,
+                "target_block": estimate.as_ref().map(|e| e.target_block).unwrap_or(current_block),
+                "percentage": estimate.as_ref().map(|e| e.progress).unwrap_or(0.0),
+                "synced": estimate.as_ref().is_some_and(|e| e.synced),
+                "eta_seconds": estimate.and_then(|e| e.eta_seconds),
             },
             "network": {
                 "connected_peers": peer_count,
@@ -1304,15 +1605,15 @@ impl NodeManager {
             "activity": {
                 "error_count": error_count,
                 "last_error": last_error,
+                "chain_restarts": chain_restarts,
             },
         });
         
         Ok(status_report)
     }
 
-    pub async fn get_resource_usage(&self) -> Result<ResourceUsage, String> {
-        let mem_info = sys_info::mem_info().map_err(|e| e.to_string())?;
-        let load_avg = sys_info::loadavg().map_err(|e| e.to_string())?;
+    pub async fn get_resource_usage(&self) -> Result<ResourceUsage, NodeError> {
+        let mem_info = sys_info::mem_info().map_err(|e| NodeError::Io(format!("Failed to read memory info: {}", e)))?;
         
         let disk_usage = fs2::available_space(&self.data_path)
             .unwrap_or(0) / (1024 * 1024 * 1024);
@@ -1320,17 +1621,84 @@ impl NodeManager {
         let total_disk = fs2::total_space(&self.data_path)
             .unwrap_or(0) / (1024 * 1024 * 1024);
 
+        let services = match self.mode {
+            NodeMode::Docker => self.container_usage().await,
+            NodeMode::Native => self.process_usage().await,
+        };
+
         Ok(ResourceUsage {
-            // Approximate CPU % normalized by number of cores
-            cpu_percent: {
-                let cores = num_cpus::get() as f64;
-                let val = (load_avg.one / cores) * 100.0;
-                val.clamp(0.0, 100.0) as f32
-            },
-            memory_mb: ((mem_info.total - mem_info.avail) / 1024) as u32,
+            cpu_percent: services.iter().map(|s| s.cpu_percent).sum::<f32>().min(100.0),
+            memory_mb: services.iter().map(|s| s.memory_mb).sum(),
             memory_total_mb: (mem_info.total / 1024) as u32,
             disk_used_gb: (total_disk - disk_usage) as f32,
             disk_total_gb: total_disk as f32,
+            net_rx_bytes: services.iter().filter_map(|s| s.net_rx_bytes).sum(),
+            net_tx_bytes: services.iter().filter_map(|s| s.net_tx_bytes).sum(),
+            block_read_bytes: services.iter().filter_map(|s| s.block_read_bytes).sum(),
+            block_write_bytes: services.iter().filter_map(|s| s.block_write_bytes).sum(),
+            services,
         })
     }
+
+    // Stats report CPU per core; the dashboard shows share of the host
+    fn host_cpu_percent(per_core: f64) -> f32 {
+        (per_core / num_cpus::get() as f64) as f32
+    }
+
+    async fn container_usage(&self) -> Vec<ServiceUsage> {
+        let project = self.compose_project();
+        let running = self.runtime.running_containers().await.unwrap_or_default();
+        let containers: Vec<(&str, String)> = SERVICES
+            .iter()
+            .filter_map(|service| {
+                running
+                    .iter()
+                    .find(|name| container_runtime::container_matches(name, &project, service))
+                    .map(|name| (*service, name.clone()))
+            })
+            .collect();
+        let names: Vec<String> = containers.iter().map(|(_, name)| name.clone()).collect();
+
+        let stats = match self.runtime.stats(&names).await {
+            Ok(stats) => stats,
+            Err(e) => {
+                log_debug("Failed to read container stats", Some(&e.to_string()));
+                return Vec::new();
+            }
+        };
+        containers
+            .iter()
+            .filter_map(|(service, name)| {
+                let stats = stats.iter().find(|s| &s.name == name)?;
+                Some(ServiceUsage {
+                    service: service.to_string(),
+                    cpu_percent: Self::host_cpu_percent(stats.cpu_percent),
+                    memory_mb: (stats.memory_bytes / (1024 * 1024)) as u32,
+                    net_rx_bytes: Some(stats.net_rx_bytes),
+                    net_tx_bytes: Some(stats.net_tx_bytes),
+                    block_read_bytes: Some(stats.block_read_bytes),
+                    block_write_bytes: Some(stats.block_write_bytes),
+                })
+            })
+            .collect()
+    }
+
+    async fn process_usage(&self) -> Vec<ServiceUsage> {
+        let pids = self.native.service_pids();
+        let stats = process_stats::sample(&pids.iter().map(|(_, pid)| *pid).collect::<Vec<_>>()).await;
+        pids.iter()
+            .filter_map(|(service, pid)| {
+                let stats = stats.iter().find(|s| s.pid == *pid)?;
+                Some(ServiceUsage {
+                    service: service.clone(),
+                    cpu_percent: Self::host_cpu_percent(stats.cpu_percent),
+                    memory_mb: (stats.memory_bytes / (1024 * 1024)) as u32,
+                    net_rx_bytes: None,
+                    net_tx_bytes: None,
+                    block_read_bytes: stats.block_read_bytes,
+                    block_write_bytes: stats.block_write_bytes,
+                })
+            })
+            .collect()
+    }
 }
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use serde::Serialize;
+use crate::koinos_rpc::{KoinosRpc, PeerEntry};
+use crate::error::NodeError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PeerDirection {
+    Inbound,
+    Outbound,
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PeerInfo {
+    /// None if the p2p service listed only an address without a peer id
+    pub id: Option<String>,
+    pub address: String,
+    pub direction: PeerDirection,
+    /// How long the peer has been connected, or since the app first saw it
+    pub connected_seconds: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PeerReport {
+    /// None if the p2p service didn't answer
+    pub gossip_enabled: Option<bool>,
+    pub peers: Vec<PeerInfo>,
+}
+
+// Peer id is the last component of a multiaddress: /ip4/1.2.3.4/tcp/8888/p2p/<id>
+fn peer_id_from_address(address: &str) -> Option<&str> {
+    address
+        .rsplit_once("/p2p/")
+        .map(|(_, id)| id)
+        .filter(|id| !id.is_empty() && !id.contains('/'))
+}
+
+/// Reads the peer list from the p2p service. Remembers when each peer was first seen
+/// so a connection duration can be shown when the service doesn't report one.
+#[derive(Clone, Default)]
+pub struct PeerTracker {
+    // Keyed by peer id, or by address for peers without one
+    first_seen: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+impl PeerTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn reset(&self) {
+        self.first_seen.lock().unwrap().clear();
+    }
+
+    pub async fn report(&self, rpc: &KoinosRpc) -> Result<PeerReport, NodeError> {
+        let (peers, gossip) = tokio::join!(rpc.peers(), rpc.gossip_status());
+        let peers = self.track(peers?);
+        Ok(PeerReport {
+            gossip_enabled: gossip.ok().map(|g| g.enabled),
+            peers,
+        })
+    }
+
+    fn track(&self, entries: Vec<PeerEntry>) -> Vec<PeerInfo> {
+        let now = Instant::now();
+        let unix_now = chrono::Utc::now().timestamp().max(0) as u64;
+        let mut first_seen = self.first_seen.lock().unwrap();
+
+        let mut keys = Vec::new();
+        let peers: Vec<PeerInfo> = entries
+            .into_iter()
+            .filter_map(|entry| {
+                let (id, address, direction, connected_since) = match entry {
+                    PeerEntry::Address(address) => (None, address, None, None),
+                    PeerEntry::Detailed { id, address, direction, connected_since } => {
+                        (id, address.unwrap_or_default(), direction, connected_since)
+                    }
+                };
+                let id = id.or_else(|| peer_id_from_address(&address).map(str::to_string));
+                let key = id.clone().or_else(|| Some(address.clone()).filter(|a| !a.is_empty()))?;
+                let direction = match direction.as_deref() {
+                    Some("inbound") => PeerDirection::Inbound,
+                    Some("outbound") => PeerDirection::Outbound,
+                    _ => PeerDirection::Unknown,
+                };
+                let seen = *first_seen.entry(key.clone()).or_insert(now);
+                keys.push(key);
+                let connected_seconds = match connected_since {
+                    Some(since) => unix_now.saturating_sub(since),
+                    None => now.duration_since(seen).as_secs(),
+                };
+                Some(PeerInfo { id, address, direction, connected_seconds })
+            })
+            .collect();
+
+        // Forget peers that disconnected, so a reconnect starts from zero
+        first_seen.retain(|key, _| keys.contains(key));
+        peers
+    }
+}
@@ -0,0 +1,113 @@
+/// Usage of one native process. CPU is percent of one core; network traffic isn't
+/// attributable to a single process, so it is not reported.
+#[derive(Debug, Clone, Default)]
+pub struct ProcessStats {
+    pub pid: u32,
+    pub cpu_percent: f64,
+    pub memory_bytes: u64,
+    pub block_read_bytes: Option<u64>,
+    pub block_write_bytes: Option<u64>,
+}
+
+/// Sample the given processes. Processes that exited in the meantime are left out.
+pub async fn sample(pids: &[u32]) -> Vec<ProcessStats> {
+    let pids = pids.to_vec();
+    tokio::task::spawn_blocking(move || sample_blocking(&pids))
+        .await
+        .unwrap_or_default()
+}
+
+#[cfg(target_os = "linux")]
+fn sample_blocking(pids: &[u32]) -> Vec<ProcessStats> {
+    use std::fs;
+    use std::time::{Duration, Instant};
+
+    // Window over which CPU time is measured
+    const CPU_SAMPLE: Duration = Duration::from_millis(500);
+
+    // USER_HZ, 100 on every mainstream Linux build
+    const TICKS_PER_SEC: f64 = 100.0;
+
+    fn cpu_ticks(pid: u32) -> Option<u64> {
+        let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+        // The command name may contain spaces, so count fields from the closing paren
+        let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+        let utime: u64 = fields.get(11)?.parse().ok()?;
+        let stime: u64 = fields.get(12)?.parse().ok()?;
+        Some(utime + stime)
+    }
+
+    fn status_kb(pid: u32, key: &str) -> Option<u64> {
+        let status = fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix(key))?
+            .trim_start_matches(':')
+            .split_whitespace()
+            .next()?
+            .parse()
+            .ok()
+    }
+
+    fn io_bytes(pid: u32, key: &str) -> Option<u64> {
+        // Only readable for our own processes
+        let io = fs::read_to_string(format!("/proc/{}/io", pid)).ok()?;
+        io.lines().find_map(|line| line.strip_prefix(key))?.trim_start_matches(':').trim().parse().ok()
+    }
+
+    let start = Instant::now();
+    let before: Vec<Option<u64>> = pids.iter().map(|pid| cpu_ticks(*pid)).collect();
+    std::thread::sleep(CPU_SAMPLE);
+    let elapsed = start.elapsed().as_secs_f64();
+
+    pids.iter()
+        .zip(before)
+        .filter_map(|(pid, before)| {
+            let after = cpu_ticks(*pid)?;
+            let ticks = after.saturating_sub(before?) as f64;
+            Some(ProcessStats {
+                pid: *pid,
+                cpu_percent: ticks / TICKS_PER_SEC / elapsed * 100.0,
+                memory_bytes: status_kb(*pid, "VmRSS")? * 1024,
+                block_read_bytes: io_bytes(*pid, "read_bytes"),
+                block_write_bytes: io_bytes(*pid, "write_bytes"),
+            })
+        })
+        .collect()
+}
+
+// Elsewhere `ps` gives CPU and resident memory, but no I/O counters
+#[cfg(all(unix, not(target_os = "linux")))]
+fn sample_blocking(pids: &[u32]) -> Vec<ProcessStats> {
+    use std::process::Command;
+
+    if pids.is_empty() {
+        return Vec::new();
+    }
+    let list: Vec<String> = pids.iter().map(|p| p.to_string()).collect();
+    let Ok(output) = Command::new("ps")
+        .args(["-o", "pid=,%cpu=,rss=", "-p", &list.join(",")])
+        .output()
+    else {
+        return Vec::new();
+    };
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            Some(ProcessStats {
+                pid: fields.next()?.parse().ok()?,
+                cpu_percent: fields.next()?.parse().ok()?,
+                memory_bytes: fields.next()?.parse::<u64>().ok()? * 1024,
+                block_read_bytes: None,
+                block_write_bytes: None,
+            })
+        })
+        .collect()
+}
+
+#[cfg(windows)]
+fn sample_blocking(_pids: &[u32]) -> Vec<ProcessStats> {
+    Vec::new()
+}
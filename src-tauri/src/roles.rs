@@ -0,0 +1,199 @@
+use serde::{Deserialize, Serialize};
+use crate::node_manager::SERVICES;
+
+/// Services every node runs. They have no compose profile, so compose always
+/// starts them.
+pub const CORE_SERVICES: &[&str] = &["amqp", "chain", "block_store", "mempool", "p2p"];
+
+/// Services that can be switched on and off. Each has a compose profile of the same
+/// name, and the `all` profile covers them all.
+pub const OPTIONAL_SERVICES: &[&str] = &[
+    "jsonrpc", "grpc", "rest", "account_history", "transaction_store", "contract_meta_store", "block_producer",
+];
+
+const ALL_PROFILE: &str = "all";
+
+/// What the node is for, as a preset selection of optional services
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeRole {
+    /// Follows and relays the chain. Keeps JSON-RPC, published on localhost, since
+    /// the app reads status, sync progress and peers through it.
+    Minimal,
+    /// Minimal plus the gRPC and REST APIs
+    Api,
+    /// APIs plus the account history, transaction and contract metadata indexers
+    FullIndexer,
+    /// Minimal plus the block producer
+    Producer,
+    /// Every service
+    All,
+}
+
+impl NodeRole {
+    pub const ALL: [NodeRole; 5] = [
+        NodeRole::Minimal,
+        NodeRole::Api,
+        NodeRole::FullIndexer,
+        NodeRole::Producer,
+        NodeRole::All,
+    ];
+
+    pub fn optional_services(&self) -> &'static [&'static str] {
+        match self {
+            NodeRole::Minimal => &["jsonrpc"],
+            NodeRole::Api => &["jsonrpc", "grpc", "rest"],
+            NodeRole::FullIndexer => &[
+                "jsonrpc", "grpc", "rest", "account_history", "transaction_store", "contract_meta_store",
+            ],
+            NodeRole::Producer => &["jsonrpc", "block_producer"],
+            NodeRole::All => OPTIONAL_SERVICES,
+        }
+    }
+
+    pub fn profiles(&self) -> Vec<String> {
+        profiles_for(self.optional_services())
+    }
+
+    /// The role whose selection `profiles` amounts to, if any
+    pub fn detect(profiles: &[String]) -> Option<NodeRole> {
+        let enabled = enabled_optional(profiles);
+        NodeRole::ALL.into_iter().find(|role| {
+            OPTIONAL_SERVICES
+                .iter()
+                .all(|s| role.optional_services().contains(s) == enabled.contains(s))
+        })
+    }
+}
+
+/// Compose profiles that start exactly `optional` besides the core services
+pub fn profiles_for(optional: &[&str]) -> Vec<String> {
+    if OPTIONAL_SERVICES.iter().all(|s| optional.contains(s)) {
+        return vec![ALL_PROFILE.to_string()];
+    }
+    OPTIONAL_SERVICES
+        .iter()
+        .filter(|s| optional.contains(s))
+        .map(|s| s.to_string())
+        .collect()
+}
+
+fn enabled_optional(profiles: &[String]) -> Vec<&'static str> {
+    let all = profiles.iter().any(|p| p == ALL_PROFILE);
+    OPTIONAL_SERVICES
+        .iter()
+        .copied()
+        .filter(|s| all || profiles.iter().any(|p| p == s))
+        .collect()
+}
+
+/// Services the given profiles run, in `SERVICES` order
+pub fn active_services(profiles: &[String]) -> Vec<&'static str> {
+    let optional = enabled_optional(profiles);
+    SERVICES
+        .iter()
+        .copied()
+        .filter(|s| CORE_SERVICES.contains(s) || optional.contains(s))
+        .collect()
+}
+
+/// `profiles` with `service` switched on or off
+pub fn toggle(profiles: &[String], service: &str, enabled: bool) -> Vec<String> {
+    let mut optional = enabled_optional(profiles);
+    optional.retain(|s| *s != service);
+    if enabled {
+        optional.extend(OPTIONAL_SERVICES.iter().filter(|s| **s == service));
+    }
+    profiles_for(&optional)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RoleInfo {
+    pub role: NodeRole,
+    pub services: Vec<&'static str>,
+}
+
+pub fn roles() -> Vec<RoleInfo> {
+    NodeRole::ALL
+        .into_iter()
+        .map(|role| RoleInfo { role, services: active_services(&role.profiles()) })
+        .collect()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ServiceToggle {
+    pub service: &'static str,
+    pub enabled: bool,
+    /// Core services can't be switched off
+    pub required: bool,
+}
+
+/// The current selection. Changes apply the next time the node starts.
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeServices {
+    /// None if the selection doesn't match a role
+    pub role: Option<NodeRole>,
+    pub profiles: Vec<String>,
+    pub services: Vec<ServiceToggle>,
+}
+
+impl NodeServices {
+    pub fn from_profiles(profiles: Vec<String>) -> Self {
+        let active = active_services(&profiles);
+        Self {
+            role: NodeRole::detect(&profiles),
+            services: SERVICES
+                .iter()
+                .map(|service| ServiceToggle {
+                    service,
+                    enabled: active.contains(service),
+                    required: CORE_SERVICES.contains(service),
+                })
+                .collect(),
+            profiles,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn every_role_is_detected_from_its_profiles() {
+        for role in NodeRole::ALL {
+            assert_eq!(NodeRole::detect(&role.profiles()), Some(role));
+        }
+        assert_eq!(NodeRole::All.profiles(), strings(&["all"]));
+        assert_eq!(NodeRole::detect(&strings(&["jsonrpc", "rest"])), None);
+        // Profiles in any order, and unknown ones, don't matter
+        assert_eq!(
+            NodeRole::detect(&strings(&["block_producer", "jsonrpc", "monitoring"])),
+            Some(NodeRole::Producer)
+        );
+    }
+
+    #[test]
+    fn minimal_keeps_jsonrpc() {
+        assert!(active_services(&NodeRole::Minimal.profiles()).contains(&"jsonrpc"));
+    }
+
+    #[test]
+    fn toggle_switches_one_service() {
+        let api = NodeRole::Api.profiles();
+        assert_eq!(toggle(&api, "grpc", false), strings(&["jsonrpc", "rest"]));
+        assert_eq!(toggle(&api, "account_history", true), strings(&["jsonrpc", "grpc", "rest", "account_history"]));
+        assert_eq!(toggle(&api, "jsonrpc", true), api);
+        // Core and unknown services aren't profiles
+        assert_eq!(toggle(&api, "chain", true), api);
+
+        // Switching one off expands `all` into the rest
+        let indexer = toggle(&strings(&["all"]), "block_producer", false);
+        assert_eq!(indexer, NodeRole::FullIndexer.profiles());
+        assert_eq!(toggle(&indexer, "block_producer", true), strings(&["all"]));
+    }
+}
@@ -0,0 +1,379 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::state_manager::write_atomic;
+use crate::snapshot_downloader::{DEFAULT_CONNECTIONS, MAX_CONNECTIONS};
+use crate::koinos_rpc;
+use crate::metrics::MetricsEndpoint;
+use crate::exporter::ExporterSettings;
+use crate::logger::{log_info, log_warn};
+use crate::error::NodeError;
+
+// Settings that used to live in node_state.json, taken over on first load
+const FROM_NODE_STATE: [&str; 5] = [
+    "stream_snapshot",
+    "snapshot_connections",
+    "reference_endpoints",
+    "metrics_endpoints",
+    "exporter",
+];
+
+/// User-editable settings, kept in `~/.koinos/settings.json`. Missing fields take
+/// their defaults, so the file only needs what the user changed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Checkout of the koinos docker-compose repository
+    pub koinos_path: PathBuf,
+    /// Where node data lives. Changed through setup or `migrate_data_dir`, which
+    /// move the data along with it.
+    pub data_path: PathBuf,
+    pub jsonrpc_port: u16,
+    pub grpc_port: u16,
+    pub p2p_port: u16,
+    /// Profiles passed to compose, which select the optional services. Empty runs
+    /// only the core services.
+    pub compose_profiles: Vec<String>,
+    /// How often the status loop polls the node
+    pub poll_interval_secs: u64,
+    /// Below these the system requirements check fails
+    pub min_ram_gb: u32,
+    pub min_disk_gb: u64,
+    /// Unpack snapshots while downloading instead of keeping the archive
+    pub stream_snapshot: bool,
+    /// Concurrent ranged requests for a snapshot download
+    pub snapshot_connections: u32,
+    /// JSON-RPC endpoints the local chain is checked against. Empty turns the check off.
+    pub reference_endpoints: Vec<String>,
+    /// Prometheus endpoints of the microservices. They don't publish metrics by
+    /// default, so nothing is scraped until the user adds the ones they enabled.
+    pub metrics_endpoints: Vec<MetricsEndpoint>,
+    pub exporter: ExporterSettings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
+        Self {
+            koinos_path: home.join("koinos"),
+            data_path: home.join(".koinos"),
+            jsonrpc_port: 8080,
+            grpc_port: 50051,
+            p2p_port: 8888,
+            compose_profiles: vec!["all".to_string()],
+            poll_interval_secs: 5,
+            min_ram_gb: 4,
+            min_disk_gb: 60,
+            stream_snapshot: false,
+            snapshot_connections: DEFAULT_CONNECTIONS,
+            reference_endpoints: vec![koinos_rpc::MAINNET_ENDPOINT.to_string()],
+            metrics_endpoints: Vec::new(),
+            exporter: ExporterSettings::default(),
+        }
+    }
+}
+
+fn is_http_url(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+impl Settings {
+    pub fn validate(&self) -> Result<(), NodeError> {
+        for (name, path) in [("koinos_path", &self.koinos_path), ("data_path", &self.data_path)] {
+            if !path.is_absolute() {
+                return Err(NodeError::InvalidInput(format!("{} must be an absolute path", name)));
+            }
+        }
+
+        let ports = [
+            ("jsonrpc_port", self.jsonrpc_port),
+            ("grpc_port", self.grpc_port),
+            ("p2p_port", self.p2p_port),
+        ];
+        for (i, (name, port)) in ports.iter().enumerate() {
+            if *port == 0 {
+                return Err(NodeError::InvalidInput(format!("{} can't be 0", name)));
+            }
+            if let Some((other, _)) = ports[..i].iter().find(|(_, p)| p == port) {
+                return Err(NodeError::InvalidInput(format!("{} and {} are both {}", other, name, port)));
+            }
+        }
+
+        if let Some(bad) = self
+            .compose_profiles
+            .iter()
+            .find(|p| p.is_empty() || !p.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+        {
+            return Err(NodeError::InvalidInput(format!("Invalid compose profile {:?}", bad)));
+        }
+
+        if !(1..=300).contains(&self.poll_interval_secs) {
+            return Err(NodeError::InvalidInput("Poll interval must be between 1 and 300 seconds".to_string()));
+        }
+        if self.min_ram_gb == 0 || self.min_disk_gb == 0 {
+            return Err(NodeError::InvalidInput("Requirement thresholds must be greater than zero".to_string()));
+        }
+
+        if !(1..=MAX_CONNECTIONS).contains(&self.snapshot_connections) {
+            return Err(NodeError::InvalidInput(format!("Connections must be between 1 and {}", MAX_CONNECTIONS)));
+        }
+        if let Some(bad) = self.reference_endpoints.iter().find(|e| !is_http_url(e)) {
+            return Err(NodeError::InvalidInput(format!("{} is not an http:// or https:// URL", bad)));
+        }
+        for (i, endpoint) in self.metrics_endpoints.iter().enumerate() {
+            if endpoint.service.is_empty() || endpoint.service.contains(':') {
+                return Err(NodeError::InvalidInput(format!("Invalid service name '{}'", endpoint.service)));
+            }
+            if !is_http_url(&endpoint.url) {
+                return Err(NodeError::InvalidInput(format!("{} is not an http:// or https:// URL", endpoint.url)));
+            }
+            if self.metrics_endpoints[..i].iter().any(|e| e.service == endpoint.service) {
+                return Err(NodeError::InvalidInput(format!("{} is listed more than once", endpoint.service)));
+            }
+        }
+        self.exporter.address()?;
+        Ok(())
+    }
+
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_secs(self.poll_interval_secs)
+    }
+
+    // Changes that only take effect after a restart. The compose path and exporter are
+    // set up when the app starts; ports and profiles are what the node itself is started
+    // with. The app picks up the new ports right away either way.
+    fn restarts(&self, new: &Settings) -> (Vec<String>, Vec<String>) {
+        let mut app = Vec::new();
+        let mut node = Vec::new();
+        if self.koinos_path != new.koinos_path {
+            app.push("koinos_path".to_string());
+        }
+        if self.exporter != new.exporter {
+            app.push("exporter".to_string());
+        }
+        if self.compose_profiles != new.compose_profiles {
+            node.push("compose_profiles".to_string());
+        }
+        for (name, old, new) in [
+            ("jsonrpc_port", self.jsonrpc_port, new.jsonrpc_port),
+            ("grpc_port", self.grpc_port, new.grpc_port),
+            ("p2p_port", self.p2p_port, new.p2p_port),
+        ] {
+            if old != new {
+                node.push(name.to_string());
+            }
+        }
+        (app, node)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SettingsUpdate {
+    pub settings: Settings,
+    /// Changed settings that need the app restarted
+    pub restart_app: Vec<String>,
+    /// Changed settings that need the node restarted
+    pub restart_node: Vec<String>,
+}
+
+/// Shared, live view of the settings. Everything reads through `get`, so changes
+/// that don't need a restart apply on the next use.
+#[derive(Clone)]
+pub struct SettingsStore {
+    path: PathBuf,
+    current: Arc<RwLock<Settings>>,
+    /// Fields of the file that were ignored on load, until the settings are saved
+    problems: Arc<RwLock<Vec<String>>>,
+}
+
+impl SettingsStore {
+    pub fn load() -> Self {
+        let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
+        let path = home.join(".koinos").join("settings.json");
+
+        let mut value = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).map_err(NodeError::from),
+            Err(_) => Ok(serde_json::json!({})),
+        };
+        let adopted = value
+            .as_mut()
+            .is_ok_and(|v| adopt_node_state(v, &home.join(".koinos").join("node_state.json")));
+
+        // A file with problems is left alone for the user to fix
+        let (settings, problems) = match value {
+            Ok(value) => settings_from_value(value),
+            Err(e) => (Settings::default(), vec![format!("settings.json can't be read: {}", e)]),
+        };
+        for problem in &problems {
+            log_warn("Ignoring part of the settings file", Some(problem));
+        }
+
+        let valid = problems.is_empty();
+        let store = Self {
+            path,
+            current: Arc::new(RwLock::new(settings)),
+            problems: Arc::new(RwLock::new(problems)),
+        };
+        if adopted && valid {
+            match store.save(&store.get()) {
+                Ok(()) => log_info("Moved snapshot, metrics and exporter settings out of node_state.json", None),
+                Err(e) => log_warn("Failed to save settings taken from node_state.json", Some(&e.to_string())),
+            }
+        }
+        store
+    }
+
+    fn save(&self, settings: &Settings) -> Result<(), NodeError> {
+        let json = serde_json::to_string_pretty(settings)?;
+        write_atomic(&self.path, json.as_bytes()).map_err(|e| e.context("Failed to save settings"))?;
+        // The ignored fields are gone from the file now
+        self.problems.write().unwrap().clear();
+        Ok(())
+    }
+
+    pub fn get(&self) -> Settings {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Fields of `settings.json` that were ignored when it was loaded, and why
+    pub fn problems(&self) -> Vec<String> {
+        self.problems.read().unwrap().clone()
+    }
+
+    /// Apply the fields present in `changes` on top of the current settings
+    pub fn update(&self, changes: serde_json::Value) -> Result<SettingsUpdate, NodeError> {
+        let serde_json::Value::Object(changes) = changes else {
+            return Err(NodeError::InvalidInput("Settings changes must be an object".to_string()));
+        };
+
+        let mut current = self.current.write().unwrap();
+        let mut merged = serde_json::to_value(&*current)?;
+        for (key, value) in changes {
+            if merged.get(&key).is_none() {
+                return Err(NodeError::InvalidInput(format!("Unknown setting {}", key)));
+            }
+            merged[key] = value;
+        }
+        let new: Settings = serde_json::from_value(merged)
+            .map_err(|e| NodeError::InvalidInput(format!("Invalid settings: {}", e)))?;
+        new.validate()?;
+        if new.data_path != current.data_path {
+            return Err(NodeError::InvalidInput(
+                "data_path can't be changed here, use migrate_data_dir to move the node data".to_string(),
+            ));
+        }
+
+        self.save(&new)?;
+
+        let (restart_app, restart_node) = current.restarts(&new);
+        *current = new.clone();
+        log_info("Settings updated", None);
+        Ok(SettingsUpdate { settings: new, restart_app, restart_node })
+    }
+
+    /// Point at a new data directory. Callers are responsible for the data itself.
+    pub fn set_data_path(&self, path: PathBuf) -> Result<(), NodeError> {
+        let mut current = self.current.write().unwrap();
+        let mut new = current.clone();
+        new.data_path = path;
+        new.validate()?;
+
+        self.save(&new)?;
+        *current = new;
+        Ok(())
+    }
+}
+
+// Take the fields of the file one at a time, so one that is unknown or invalid only
+// costs itself. Above all the paths must survive: defaults would point the app at an
+// empty data directory.
+fn settings_from_value(value: serde_json::Value) -> (Settings, Vec<String>) {
+    let serde_json::Value::Object(fields) = value else {
+        return (Settings::default(), vec!["settings.json is not a JSON object".to_string()]);
+    };
+    let mut fields: Vec<_> = fields.into_iter().collect();
+    // Paths first, so a conflict between fields is blamed on the other one
+    fields.sort_by_key(|(key, _)| !key.ends_with("_path"));
+
+    let mut settings = Settings::default();
+    let mut problems = Vec::new();
+    for (key, field) in fields {
+        let Ok(mut merged) = serde_json::to_value(&settings) else {
+            break;
+        };
+        if merged.get(&key).is_none() {
+            problems.push(format!("{}: unknown setting", key));
+            continue;
+        }
+        merged[&key] = field;
+        match serde_json::from_value::<Settings>(merged)
+            .map_err(NodeError::from)
+            .and_then(|s| s.validate().map(|_| s))
+        {
+            Ok(next) => settings = next,
+            Err(e) => problems.push(format!("{}: {}", key, e)),
+        }
+    }
+    (settings, problems)
+}
+
+// Copy the settings node_state.json still has into `settings` where it doesn't set
+// them itself. The state file drops them the next time it is saved.
+fn adopt_node_state(settings: &mut serde_json::Value, state_path: &Path) -> bool {
+    let Some(serde_json::Value::Object(state)) = fs::read_to_string(state_path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+    else {
+        return false;
+    };
+    let Some(settings) = settings.as_object_mut() else {
+        return false;
+    };
+
+    let mut adopted = false;
+    for key in FROM_NODE_STATE {
+        if let Some(value) = state.get(key) {
+            if !settings.contains_key(key) {
+                settings.insert(key.to_string(), value.clone());
+                adopted = true;
+            }
+        }
+    }
+    adopted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bad_fields_dont_cost_the_paths() {
+        let file = serde_json::json!({
+            "jsonrpc_port": 0,
+            "data_path": "/mnt/koinos",
+            "koinos_path": "/opt/koinos",
+            "grpc_port": "50051",
+            "added_by_a_newer_build": true,
+            "p2p_port": 9999,
+        });
+        let (settings, problems) = settings_from_value(file);
+        assert_eq!(settings.data_path, PathBuf::from("/mnt/koinos"));
+        assert_eq!(settings.koinos_path, PathBuf::from("/opt/koinos"));
+        assert_eq!(settings.p2p_port, 9999);
+        assert_eq!(settings.jsonrpc_port, Settings::default().jsonrpc_port);
+        assert_eq!(settings.grpc_port, Settings::default().grpc_port);
+
+        let mut fields: Vec<&str> = problems.iter().filter_map(|p| p.split(':').next()).collect();
+        fields.sort();
+        assert_eq!(fields, ["added_by_a_newer_build", "grpc_port", "jsonrpc_port"]);
+    }
+
+    #[test]
+    fn valid_file_has_no_problems() {
+        let (settings, problems) = settings_from_value(serde_json::json!({ "poll_interval_secs": 10 }));
+        assert!(problems.is_empty());
+        assert_eq!(settings.poll_interval_secs, 10);
+    }
+}
@@ -0,0 +1,514 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+use crate::download_control::DownloadControl;
+use crate::logger::{log_debug, log_info, log_warn};
+use crate::error::NodeError;
+use crate::state_manager::write_atomic;
+
+pub const DEFAULT_CONNECTIONS: u32 = 4;
+pub const MAX_CONNECTIONS: u32 = 16;
+const MAX_ATTEMPTS: u32 = 8;
+// A segment syncs its writes and reports them as downloaded after this many bytes
+const SYNC_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Payload of the `download_progress` event
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadProgress {
+    pub percent: f32,
+    pub downloaded_bytes: u64,
+    pub total_bytes: u64,
+    pub bytes_per_sec: f64,
+    pub eta_seconds: Option<u64>,
+}
+
+impl DownloadProgress {
+    /// `transferred` is what this session fetched, so resumed bytes don't inflate the rate
+    pub fn measure(downloaded: u64, total: u64, transferred: u64, elapsed: Duration) -> Self {
+        let secs = elapsed.as_secs_f64();
+        let bytes_per_sec = if secs > 0.0 { transferred as f64 / secs } else { 0.0 };
+        let eta_seconds = (bytes_per_sec > 0.0)
+            .then(|| (total.saturating_sub(downloaded) as f64 / bytes_per_sec) as u64);
+        Self {
+            percent: if total > 0 { (downloaded as f32 / total as f32) * 100.0 } else { 0.0 },
+            downloaded_bytes: downloaded,
+            total_bytes: total,
+            bytes_per_sec,
+            eta_seconds,
+        }
+    }
+
+    pub fn complete(total: u64) -> Self {
+        Self {
+            percent: 100.0,
+            downloaded_bytes: total,
+            total_bytes: total,
+            bytes_per_sec: 0.0,
+            eta_seconds: Some(0),
+        }
+    }
+
+    pub fn log(&self) {
+        log_info("Download progress",
+            Some(&format!("{:.1}% - {:.1}GB/{:.1}GB - {:.1} MB/s - ETA: {} min",
+                self.percent,
+                self.downloaded_bytes as f64 / 1_000_000_000.0,
+                self.total_bytes as f64 / 1_000_000_000.0,
+                self.bytes_per_sec / 1_000_000.0,
+                self.eta_seconds.unwrap_or(0) / 60)));
+    }
+}
+
+/// Byte range `[start, end)` of the file, `downloaded` bytes of which are on disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Segment {
+    start: u64,
+    end: u64,
+    downloaded: u64,
+}
+
+/// Sidecar written next to a segmented download (`<file>.segments.json`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SegmentsFile {
+    url: String,
+    total_size: u64,
+    validator: Option<String>,
+    segments: Vec<Segment>,
+}
+
+impl SegmentsFile {
+    fn path_for(snapshot_path: &Path) -> PathBuf {
+        PathBuf::from(format!("{}.segments.json", snapshot_path.display()))
+    }
+
+    fn load(snapshot_path: &Path) -> Option<Self> {
+        let content = fs::read_to_string(Self::path_for(snapshot_path)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    fn save(&self, snapshot_path: &Path) -> Result<(), NodeError> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| NodeError::from(e).context("Failed to serialize download segments"))?;
+        write_atomic(&Self::path_for(snapshot_path), json.as_bytes())
+            .map_err(|e| e.context("Failed to write download segments"))
+    }
+
+    fn downloaded(&self) -> u64 {
+        self.segments.iter().map(|s| s.downloaded).sum()
+    }
+}
+
+pub fn has_segments(snapshot_path: &Path) -> bool {
+    SegmentsFile::path_for(snapshot_path).exists()
+}
+
+/// Remove the segment sidecar of a download, e.g. when the partial file is discarded
+pub fn remove_segments(snapshot_path: &Path) {
+    fs::remove_file(SegmentsFile::path_for(snapshot_path)).ok();
+}
+
+// Split `[prefix, total)` into `connections` ranges. Bytes before `prefix` were already
+// fetched by a single-stream download and are kept as a finished segment.
+fn plan_segments(total: u64, connections: u32, prefix: u64) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    if prefix > 0 {
+        segments.push(Segment { start: 0, end: prefix, downloaded: prefix });
+    }
+    let remaining = total.saturating_sub(prefix);
+    let size = remaining.div_ceil(connections.max(1) as u64).max(1);
+    let mut start = prefix;
+    while start < total {
+        let end = (start + size).min(total);
+        segments.push(Segment { start, end, downloaded: 0 });
+        start = end;
+    }
+    segments
+}
+
+/// A file to download; `validator` goes into `If-Range` so a replaced file is noticed
+#[derive(Debug, Clone)]
+pub struct DownloadTarget {
+    pub url: String,
+    /// Other mirrors of the same file, tried in order when a segment gives up on `url`
+    pub fallbacks: Vec<String>,
+    pub path: PathBuf,
+    pub total_size: u64,
+    pub validator: Option<String>,
+}
+
+enum SegmentError {
+    Retry(String),
+    /// This mirror can't serve the range; another one may
+    Mirror(NodeError),
+    Fatal(NodeError),
+}
+
+// Aborts the segment tasks when dropped, so none outlive the download
+struct SegmentTasks(Vec<tokio::task::AbortHandle>);
+
+impl Drop for SegmentTasks {
+    fn drop(&mut self) {
+        for task in &self.0 {
+            task.abort();
+        }
+    }
+}
+
+/// Download `target` over `connections` concurrent ranged requests. Progress is
+/// persisted in a sidecar so an interrupted download resumes per segment.
+pub async fn download_segmented(
+    client: &reqwest::Client,
+    target: &DownloadTarget,
+    connections: u32,
+    control: &DownloadControl,
+    progress_callback: &impl Fn(DownloadProgress),
+) -> Result<(), NodeError> {
+    let (url, path) = (target.url.as_str(), target.path.as_path());
+    let total_size = target.total_size;
+    let validator = target.validator.clone();
+    let existing_size = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+
+    let fresh = |prefix: u64| SegmentsFile {
+        url: url.to_string(),
+        total_size,
+        validator: validator.clone(),
+        segments: plan_segments(total_size, connections, prefix),
+    };
+    // Mirror ranking may pick another primary next time; the file is what has to match
+    let mut state = match SegmentsFile::load(path) {
+        Some(mut s) if s.total_size == total_size
+            && s.validator == validator
+            && existing_size == total_size => {
+            log_info("Resuming segmented download",
+                Some(&format!("{:.1}GB already downloaded", s.downloaded() as f64 / 1_000_000_000.0)));
+            s.url = url.to_string();
+            s
+        }
+        Some(_) => {
+            log_warn("Discarding segmented download of a different snapshot", None);
+            fs::remove_file(path).ok();
+            fresh(0)
+        }
+        None if existing_size == total_size => {
+            // No sidecar and a full-size file: a previous download finished
+            progress_callback(DownloadProgress::complete(total_size));
+            return Ok(());
+        }
+        // A shorter file is a single-stream partial the caller already validated
+        None => fresh(existing_size.min(total_size)),
+    };
+
+    // Sidecar first, then preallocate, so a full-size file without a sidecar is always complete
+    state.save(path)?;
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)
+        .map_err(|e| NodeError::from(e).context("Failed to create snapshot file"))?;
+    file.set_len(total_size)
+        .map_err(|e| NodeError::from(e).context("Failed to allocate snapshot file"))?;
+    drop(file);
+
+    let pending = state.segments.iter().filter(|s| s.downloaded < s.end - s.start).count();
+    log_info("Starting segmented download",
+        Some(&format!("{} segments, {} connections", pending, connections)));
+
+    let counters: Vec<Arc<AtomicU64>> = state
+        .segments
+        .iter()
+        .map(|s| Arc::new(AtomicU64::new(s.downloaded)))
+        .collect();
+    let handles: Vec<_> = state
+        .segments
+        .iter()
+        .zip(&counters)
+        .filter(|(s, _)| s.downloaded < s.end - s.start)
+        .map(|(s, counter)| {
+            tokio::spawn(fetch_segment(
+                client.clone(),
+                target.clone(),
+                s.clone(),
+                counter.clone(),
+                control.clone(),
+            ))
+        })
+        .collect();
+    let tasks = SegmentTasks(handles.iter().map(|h| h.abort_handle()).collect());
+
+    let resumed_bytes = state.downloaded();
+    let start_time = Instant::now();
+    let joined = futures_util::future::join_all(handles);
+    tokio::pin!(joined);
+    let mut ticker = tokio::time::interval(Duration::from_secs(5));
+    ticker.tick().await;
+
+    let results = loop {
+        tokio::select! {
+            results = &mut joined => break results,
+            _ = ticker.tick() => {
+                // A segment waiting on the network won't reach its checkpoint
+                if control.is_cancelled() {
+                    drop(tasks);
+                    let _ = (&mut joined).await;
+                    break Vec::new();
+                }
+                for (segment, counter) in state.segments.iter_mut().zip(&counters) {
+                    segment.downloaded = counter.load(Ordering::Relaxed);
+                }
+                state.save(path).ok();
+
+                let downloaded = state.downloaded();
+                let progress = DownloadProgress::measure(
+                    downloaded,
+                    total_size,
+                    downloaded - resumed_bytes,
+                    start_time.elapsed(),
+                );
+                progress.log();
+                progress_callback(progress);
+            }
+        }
+    };
+
+    for (segment, counter) in state.segments.iter_mut().zip(&counters) {
+        segment.downloaded = counter.load(Ordering::Relaxed);
+    }
+    state.save(path)?;
+
+    if control.is_cancelled() {
+        return Err(NodeError::Cancelled("Snapshot download cancelled".to_string()));
+    }
+    for result in results {
+        result.map_err(|e| NodeError::from(e).context("Download task failed"))??;
+    }
+
+    remove_segments(path);
+    progress_callback(DownloadProgress::complete(total_size));
+    log_info("Download completed",
+        Some(&format!("Total: {}GB", total_size / 1_000_000_000)));
+    Ok(())
+}
+
+// Fetch one segment, retrying with exponential backoff. Attempts reset whenever a
+// try made progress, so only a segment that is really stuck gives up on its mirror
+// and continues from the next one.
+async fn fetch_segment(
+    client: reqwest::Client,
+    target: DownloadTarget,
+    segment: Segment,
+    counter: Arc<AtomicU64>,
+    control: DownloadControl,
+) -> Result<(), NodeError> {
+    let urls: Vec<&str> = std::iter::once(target.url.as_str())
+        .chain(target.fallbacks.iter().map(String::as_str))
+        .collect();
+    let mut mirror = 0;
+    let mut attempt = 0u32;
+    loop {
+        let before = counter.load(Ordering::Relaxed);
+        let offset = segment.start + before;
+        if offset >= segment.end {
+            return Ok(());
+        }
+
+        // The validator comes from the primary; other mirrors are checked by size
+        let validator = if mirror == 0 { target.validator.as_deref() } else { None };
+        let failure = match fetch_range(&client, &target, urls[mirror], validator, offset..segment.end, &counter, &control).await {
+            Ok(()) => return Ok(()),
+            Err(SegmentError::Fatal(e)) => return Err(e),
+            Err(SegmentError::Retry(_) | SegmentError::Mirror(_)) if control.is_cancelled() => {
+                return Err(NodeError::Cancelled("Snapshot download cancelled".to_string()));
+            }
+            Err(SegmentError::Retry(e)) => {
+                if counter.load(Ordering::Relaxed) > before {
+                    attempt = 0;
+                }
+                attempt += 1;
+                if attempt < MAX_ATTEMPTS {
+                    let delay = Duration::from_secs((1u64 << attempt).min(60));
+                    log_warn("Snapshot segment interrupted, retrying",
+                        Some(&format!("bytes {}-{}: {} (retry in {}s)", offset, segment.end - 1, e, delay.as_secs())));
+                    tokio::time::sleep(delay).await;
+                    continue;
+                }
+                NodeError::DownloadInterrupted(format!(
+                    "Download of bytes {}-{} failed after {} attempts. Will resume on next attempt. Error: {}",
+                    offset, segment.end - 1, MAX_ATTEMPTS, e
+                ))
+            }
+            Err(SegmentError::Mirror(e)) => e,
+        };
+
+        mirror += 1;
+        attempt = 0;
+        let Some(next) = urls.get(mirror) else {
+            return Err(failure);
+        };
+        log_warn("Continuing snapshot segment from another mirror",
+            Some(&format!("bytes {}-{} from {}: {}", offset, segment.end - 1, next, failure)));
+    }
+}
+
+// Start and total size from `Content-Range: bytes <start>-<end>/<total>`; the total
+// may be `*`
+fn parse_content_range(header: &str) -> Option<(u64, Option<u64>)> {
+    let (range, total) = header.strip_prefix("bytes ")?.split_once('/')?;
+    let start = range.split('-').next()?.trim().parse().ok()?;
+    Some((start, total.trim().parse().ok()))
+}
+
+async fn fetch_range(
+    client: &reqwest::Client,
+    target: &DownloadTarget,
+    url: &str,
+    validator: Option<&str>,
+    range: std::ops::Range<u64>,
+    counter: &AtomicU64,
+    control: &DownloadControl,
+) -> Result<(), SegmentError> {
+    let (offset, end) = (range.start, range.end);
+    let mut request = client
+        .get(url)
+        .header("Range", format!("bytes={}-{}", offset, end - 1));
+    // If the snapshot changed, the server answers 200 with the whole new file
+    if let Some(validator) = validator {
+        request = request.header("If-Range", validator);
+    }
+
+    let response = request
+        .send()
+        .await
+        .map_err(|e| SegmentError::Retry(e.to_string()))?;
+    match response.status() {
+        reqwest::StatusCode::PARTIAL_CONTENT => {}
+        reqwest::StatusCode::OK if validator.is_some() => {
+            return Err(SegmentError::Fatal(NodeError::DownloadInterrupted(
+                "The snapshot changed on the server during download".to_string(),
+            )))
+        }
+        reqwest::StatusCode::OK => {
+            return Err(SegmentError::Mirror(NodeError::SnapshotUnavailable(
+                format!("{} doesn't support ranged downloads", url),
+            )))
+        }
+        status if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS => {
+            return Err(SegmentError::Retry(format!("HTTP {}", status)))
+        }
+        status => return Err(SegmentError::Mirror(NodeError::SnapshotUnavailable(
+            format!("Snapshot download failed: HTTP {}", status),
+        ))),
+    }
+    let content_range = response
+        .headers()
+        .get(reqwest::header::CONTENT_RANGE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    let matches = match parse_content_range(content_range) {
+        Some((start, total)) => start == offset && total.is_none_or(|total| total == target.total_size),
+        None => content_range.is_empty(),
+    };
+    if !matches {
+        return Err(SegmentError::Mirror(NodeError::SnapshotUnavailable(format!(
+            "{} serves a different file ({})",
+            url, content_range
+        ))));
+    }
+
+    let mut file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .open(&target.path)
+        .await
+        .map_err(|e| SegmentError::Fatal(NodeError::from(e).context("Failed to open snapshot file")))?;
+    file.seek(std::io::SeekFrom::Start(offset))
+        .await
+        .map_err(|e| SegmentError::Fatal(NodeError::from(e).context("Failed to seek snapshot file")))?;
+
+    // The counter only covers synced bytes, so the sidecar never claims data a crash lost
+    let mut position = offset;
+    let mut unsynced = 0u64;
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| SegmentError::Retry(e.to_string()))?;
+        // Never write past the segment, even if the server sends more than asked for
+        let len = (chunk.len() as u64).min(end - position) as usize;
+        file.write_all(&chunk[..len])
+            .await
+            .map_err(|e| SegmentError::Fatal(NodeError::from(e).context("Write error")))?;
+        position += len as u64;
+        unsynced += len as u64;
+        if unsynced >= SYNC_BYTES {
+            sync_segment(&mut file).await?;
+            counter.fetch_add(unsynced, Ordering::Relaxed);
+            unsynced = 0;
+        }
+        control.checkpoint(len).await.map_err(SegmentError::Fatal)?;
+        if position >= end {
+            break;
+        }
+    }
+    sync_segment(&mut file).await?;
+    counter.fetch_add(unsynced, Ordering::Relaxed);
+
+    if position < end {
+        return Err(SegmentError::Retry("Connection closed early".to_string()));
+    }
+    log_debug("Snapshot segment complete", Some(&format!("bytes {}-{}", offset, end - 1)));
+    Ok(())
+}
+
+async fn sync_segment(file: &mut tokio::fs::File) -> Result<(), SegmentError> {
+    let fatal = |e: std::io::Error| SegmentError::Fatal(NodeError::from(e).context("Failed to sync snapshot file"));
+    file.flush().await.map_err(fatal)?;
+    file.sync_data().await.map_err(fatal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranges(segments: &[Segment]) -> Vec<(u64, u64, u64)> {
+        segments.iter().map(|s| (s.start, s.end, s.downloaded)).collect()
+    }
+
+    #[test]
+    fn splits_evenly_across_connections() {
+        assert_eq!(ranges(&plan_segments(100, 4, 0)), [(0, 25, 0), (25, 50, 0), (50, 75, 0), (75, 100, 0)]);
+    }
+
+    #[test]
+    fn last_segment_takes_the_remainder() {
+        assert_eq!(ranges(&plan_segments(10, 3, 0)), [(0, 4, 0), (4, 8, 0), (8, 10, 0)]);
+    }
+
+    #[test]
+    fn single_stream_prefix_is_a_finished_segment() {
+        let segments = plan_segments(100, 2, 40);
+        assert_eq!(ranges(&segments), [(0, 40, 40), (40, 70, 0), (70, 100, 0)]);
+        assert_eq!(segments.iter().map(|s| s.end - s.start).sum::<u64>(), 100);
+    }
+
+    #[test]
+    fn more_connections_than_bytes() {
+        assert_eq!(ranges(&plan_segments(3, 8, 0)), [(0, 1, 0), (1, 2, 0), (2, 3, 0)]);
+        assert_eq!(ranges(&plan_segments(5, 0, 0)), [(0, 5, 0)]);
+    }
+
+    #[test]
+    fn complete_prefix_leaves_nothing_to_fetch() {
+        assert_eq!(ranges(&plan_segments(50, 4, 50)), [(0, 50, 50)]);
+    }
+
+    #[test]
+    fn content_range_start_and_total() {
+        assert_eq!(parse_content_range("bytes 100-199/1000"), Some((100, Some(1000))));
+        assert_eq!(parse_content_range("bytes 100-199/*"), Some((100, None)));
+        assert_eq!(parse_content_range("bytes */1000"), None);
+        assert_eq!(parse_content_range(""), None);
+    }
+}
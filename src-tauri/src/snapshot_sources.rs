@@ -0,0 +1,527 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use crate::logger::{log_debug, log_info, log_warn};
+use crate::error::NodeError;
+use crate::snapshot_verify;
+
+/// How a mirror lists its snapshots
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexFormat {
+    /// Plain directory listing page
+    Html,
+    /// `[{"name", "url", "size", "sha256"}]`, optionally wrapped in `{"snapshots": [...]}`
+    Json,
+    /// S3 `ListBucketResult`
+    S3Xml,
+    /// A snapshot file, or a directory containing snapshot files
+    Local,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotMirror {
+    pub name: String,
+    pub url: String,
+    pub format: IndexFormat,
+    /// Regex a file name must match to count as a snapshot. Any `.tar.gz` if unset.
+    #[serde(default)]
+    pub pattern: Option<String>,
+    #[serde(default)]
+    pub builtin: bool,
+}
+
+/// One snapshot file offered by a mirror. `url` is a filesystem path for local mirrors.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotEntry {
+    pub name: String,
+    pub url: String,
+    pub size: Option<u64>,
+    pub sha256: Option<String>,
+    /// When the snapshot was taken: the date in its name, else the one the mirror reports
+    pub date: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MirrorProbe {
+    pub mirror: SnapshotMirror,
+    pub latest: Option<SnapshotEntry>,
+    pub latency_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+/// The snapshot to download, plus other mirrors serving the same file
+#[derive(Debug, Clone)]
+pub struct ResolvedSnapshot {
+    pub mirror: String,
+    pub entry: SnapshotEntry,
+    pub local: bool,
+    pub fallbacks: Vec<String>,
+}
+
+fn builtin_mirrors() -> Vec<SnapshotMirror> {
+    vec![SnapshotMirror {
+        name: "koinosblocks".to_string(),
+        url: "https://backup.koinosblocks.com/".to_string(),
+        format: IndexFormat::Html,
+        pattern: Some(r"^backup_\d{4}-\d{2}-\d{2}\.tar\.gz$".to_string()),
+        builtin: true,
+    }]
+}
+
+/// Built-in mirrors plus the ones the user added (kept in `~/.koinos/snapshot_sources.json`)
+pub struct SnapshotSources {
+    path: PathBuf,
+    custom: Vec<SnapshotMirror>,
+}
+
+impl SnapshotSources {
+    pub fn load() -> Self {
+        let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
+        let path = home.join(".koinos").join("snapshot_sources.json");
+        let custom = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self { path, custom }
+    }
+
+    pub fn mirrors(&self) -> Vec<SnapshotMirror> {
+        let mut mirrors = builtin_mirrors();
+        mirrors.extend(self.custom.iter().cloned());
+        mirrors
+    }
+
+    pub fn add(
+        &mut self,
+        name: String,
+        url: String,
+        format: IndexFormat,
+        pattern: Option<String>,
+    ) -> Result<SnapshotMirror, NodeError> {
+        let name = name.trim().to_string();
+        if name.is_empty() {
+            return Err(NodeError::InvalidInput("Mirror name is required".to_string()));
+        }
+        if self.mirrors().iter().any(|m| m.name == name) {
+            return Err(NodeError::InvalidInput(format!("A mirror named {} already exists", name)));
+        }
+        match format {
+            IndexFormat::Local => {
+                if !Path::new(&url).exists() {
+                    return Err(NodeError::InvalidInput(format!("{} does not exist", url)));
+                }
+            }
+            _ => {
+                if !url.starts_with("http://") && !url.starts_with("https://") {
+                    return Err(NodeError::InvalidInput("Mirror URL must start with http:// or https://".to_string()));
+                }
+            }
+        }
+
+        let pattern = pattern.map(|p| p.trim().to_string()).filter(|p| !p.is_empty());
+        if let Some(pattern) = &pattern {
+            regex::Regex::new(pattern)
+                .map_err(|e| NodeError::InvalidInput(format!("Invalid snapshot name pattern: {}", e)))?;
+        }
+
+        let mirror = SnapshotMirror { name, url, format, pattern, builtin: false };
+        self.custom.push(mirror.clone());
+        self.save()?;
+        log_info("Snapshot mirror added", Some(&mirror.url));
+        Ok(mirror)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<(), NodeError> {
+        if builtin_mirrors().iter().any(|m| m.name == name) {
+            return Err(NodeError::InvalidInput("Built-in mirrors can't be removed".to_string()));
+        }
+        let before = self.custom.len();
+        self.custom.retain(|m| m.name != name);
+        if self.custom.len() == before {
+            return Err(NodeError::InvalidInput(format!("No mirror named {}", name)));
+        }
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), NodeError> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| NodeError::from(e).context("Failed to create state directory"))?;
+        }
+        let json = serde_json::to_string_pretty(&self.custom)
+            .map_err(|e| NodeError::from(e).context("Failed to serialize mirrors"))?;
+        fs::write(&self.path, json)
+            .map_err(|e| NodeError::from(e).context("Failed to write mirrors file"))
+    }
+}
+
+fn is_snapshot_name(name: &str) -> bool {
+    name.ends_with(".tar.gz")
+}
+
+// Snapshot names carry the day they were taken, e.g. backup_2024-05-01.tar.gz
+fn date_from_name(name: &str) -> Option<DateTime<Utc>> {
+    let re = regex::Regex::new(r"(\d{4}-\d{2}-\d{2})").unwrap();
+    let day = NaiveDate::parse_from_str(re.captures(name)?.get(1)?.as_str(), "%Y-%m-%d").ok()?;
+    Some(day.and_hms_opt(0, 0, 0)?.and_utc())
+}
+
+// RFC 3339 (manifests, S3), RFC 2822 (Last-Modified) or a bare day
+fn parse_date(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .or_else(|_| DateTime::parse_from_rfc2822(value))
+        .map(|d| d.with_timezone(&Utc))
+        .ok()
+        .or_else(|| date_from_name(value))
+}
+
+// Resolve a link from the index at `base`, which is taken as a directory. A link
+// starting with `/` is relative to the origin.
+fn join_url(base: &str, href: &str) -> String {
+    let dir = format!("{}/", base.trim_end_matches('/'));
+    match reqwest::Url::parse(&dir).and_then(|dir| dir.join(href)) {
+        Ok(url) => url.to_string(),
+        Err(_) => format!("{}{}", dir, href.trim_start_matches('/')),
+    }
+}
+
+fn file_name(path: &str) -> String {
+    path.rsplit('/').next().unwrap_or(path).to_string()
+}
+
+fn parse_html(base: &str, body: &str) -> Vec<SnapshotEntry> {
+    let re = regex::Regex::new(r#"[\w.\-/:]+\.tar\.gz"#).unwrap();
+    let mut names: Vec<String> = re.find_iter(body).map(|m| m.as_str().to_string()).collect();
+    names.sort();
+    names.dedup();
+    names
+        .into_iter()
+        .map(|href| SnapshotEntry {
+            name: file_name(&href),
+            url: join_url(base, &href),
+            size: None,
+            sha256: None,
+            date: None,
+        })
+        .collect()
+}
+
+fn parse_json(base: &str, body: &str) -> Result<Vec<SnapshotEntry>, NodeError> {
+    let value: serde_json::Value = serde_json::from_str(body)
+        .map_err(|e| NodeError::from(e).context("Invalid JSON manifest"))?;
+    let items = value
+        .get("snapshots")
+        .unwrap_or(&value)
+        .as_array()
+        .ok_or_else(|| NodeError::Serialization("JSON manifest has no snapshot list".to_string()))?;
+
+    Ok(items
+        .iter()
+        .filter_map(|item| {
+            // Either a bare file name/URL or an object describing the file
+            if let Some(s) = item.as_str() {
+                return Some(SnapshotEntry {
+                    name: file_name(s),
+                    url: join_url(base, s),
+                    size: None,
+                    sha256: None,
+                    date: None,
+                });
+            }
+            let location = item.get("url").or_else(|| item.get("name"))?.as_str()?;
+            let name = item
+                .get("name")
+                .and_then(|v| v.as_str())
+                .map(file_name)
+                .unwrap_or_else(|| file_name(location));
+            Some(SnapshotEntry {
+                name,
+                url: join_url(base, location),
+                size: item.get("size").and_then(|v| v.as_u64()),
+                sha256: item.get("sha256").and_then(|v| v.as_str()).map(|s| s.to_lowercase()),
+                date: ["date", "last_modified"]
+                    .iter()
+                    .find_map(|k| item.get(*k).and_then(|v| v.as_str()).and_then(parse_date)),
+            })
+        })
+        .filter(|e| is_snapshot_name(&e.name))
+        .collect())
+}
+
+fn parse_s3(base: &str, body: &str) -> Vec<SnapshotEntry> {
+    let contents = regex::Regex::new(r"(?s)<Contents>(.*?)</Contents>").unwrap();
+    let key = regex::Regex::new(r"<Key>([^<]+)</Key>").unwrap();
+    let size = regex::Regex::new(r"<Size>(\d+)</Size>").unwrap();
+    let modified = regex::Regex::new(r"<LastModified>([^<]+)</LastModified>").unwrap();
+
+    contents
+        .captures_iter(body)
+        .filter_map(|block| {
+            let block = block.get(1)?.as_str();
+            let key = key.captures(block)?.get(1)?.as_str().to_string();
+            if !is_snapshot_name(&key) {
+                return None;
+            }
+            Some(SnapshotEntry {
+                name: file_name(&key),
+                url: join_url(base, &key),
+                size: size
+                    .captures(block)
+                    .and_then(|c| c.get(1))
+                    .and_then(|m| m.as_str().parse().ok()),
+                sha256: None,
+                date: modified
+                    .captures(block)
+                    .and_then(|c| c.get(1))
+                    .and_then(|m| parse_date(m.as_str())),
+            })
+        })
+        .collect()
+}
+
+fn list_local(path: &Path) -> Result<Vec<SnapshotEntry>, NodeError> {
+    let files: Vec<PathBuf> = if path.is_dir() {
+        fs::read_dir(path)
+            .map_err(|e| NodeError::from(e).context(&format!("Failed to read {}", path.display())))?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .collect()
+    } else {
+        vec![path.to_path_buf()]
+    };
+
+    Ok(files
+        .into_iter()
+        .filter_map(|file| {
+            let name = file.file_name()?.to_string_lossy().to_string();
+            if !is_snapshot_name(&name) {
+                return None;
+            }
+            let metadata = fs::metadata(&file).ok();
+            Some(SnapshotEntry {
+                name,
+                size: metadata.as_ref().map(|m| m.len()),
+                url: file.to_string_lossy().to_string(),
+                sha256: None,
+                date: metadata.and_then(|m| m.modified().ok()).map(DateTime::<Utc>::from),
+            })
+        })
+        .collect())
+}
+
+/// Every snapshot a mirror offers, oldest first
+pub async fn list_snapshots(client: &reqwest::Client, mirror: &SnapshotMirror) -> Result<Vec<SnapshotEntry>, NodeError> {
+    let mut entries = if mirror.format == IndexFormat::Local {
+        list_local(Path::new(&mirror.url))?
+    } else {
+        let response = client
+            .get(&mirror.url)
+            .send()
+            .await
+            .map_err(|e| NodeError::from(e).context("Failed to fetch snapshot list"))?;
+        if !response.status().is_success() {
+            return Err(NodeError::SnapshotUnavailable(format!("Snapshot list returned HTTP {}", response.status())));
+        }
+        let body = response
+            .text()
+            .await
+            .map_err(|e| NodeError::from(e).context("Failed to read snapshot list"))?;
+
+        match mirror.format {
+            IndexFormat::Html => parse_html(&mirror.url, &body),
+            IndexFormat::Json => parse_json(&mirror.url, &body)?,
+            IndexFormat::S3Xml => parse_s3(&mirror.url, &body),
+            IndexFormat::Local => unreachable!(),
+        }
+    };
+
+    if let Some(pattern) = &mirror.pattern {
+        let re = regex::Regex::new(pattern)
+            .map_err(|e| NodeError::InvalidInput(format!("Invalid snapshot name pattern for {}: {}", mirror.name, e)))?;
+        entries.retain(|e| re.is_match(&e.name));
+    }
+    for entry in &mut entries {
+        entry.date = date_from_name(&entry.name).or(entry.date);
+    }
+    entries.sort_by(|a, b| a.date.cmp(&b.date).then_with(|| a.name.cmp(&b.name)));
+    Ok(entries)
+}
+
+async fn probe_mirror(client: &reqwest::Client, mirror: SnapshotMirror) -> MirrorProbe {
+    let start = Instant::now();
+    match list_snapshots(client, &mirror).await {
+        Ok(entries) => {
+            let latency_ms = Some(start.elapsed().as_millis() as u64);
+            let mut latest = entries.into_iter().last();
+            // Nothing dated it; ask the server when the file was last modified
+            if let Some(entry) = latest.as_mut().filter(|e| e.date.is_none() && mirror.format != IndexFormat::Local) {
+                let remote = snapshot_verify::probe_remote(client, &entry.url).await;
+                entry.date = remote.last_modified.as_deref().and_then(parse_date);
+            }
+            let error = latest.is_none().then(|| "No snapshots found".to_string());
+            MirrorProbe { mirror, latest, latency_ms, error }
+        }
+        Err(e) => MirrorProbe { mirror, latest: None, latency_ms: None, error: Some(e.to_string()) },
+    }
+}
+
+/// Probe every mirror concurrently. Results are ranked freshest first, then fastest;
+/// mirrors that failed come last.
+pub async fn probe_mirrors(client: &reqwest::Client, mirrors: Vec<SnapshotMirror>) -> Vec<MirrorProbe> {
+    let mut probes =
+        futures_util::future::join_all(mirrors.into_iter().map(|m| probe_mirror(client, m))).await;
+
+    // Failed mirrors have no entry and undated snapshots sort below dated ones
+    probes.sort_by(|a, b| {
+        let a_date = a.latest.as_ref().map(|e| e.date);
+        let b_date = b.latest.as_ref().map(|e| e.date);
+        b_date
+            .cmp(&a_date)
+            .then(a.latency_ms.unwrap_or(u64::MAX).cmp(&b.latency_ms.unwrap_or(u64::MAX)))
+    });
+
+    for probe in &probes {
+        match &probe.error {
+            Some(e) => log_warn("Snapshot mirror unavailable", Some(&format!("{}: {}", probe.mirror.name, e))),
+            None => log_debug("Snapshot mirror probed", Some(&format!(
+                "{}: {} ({}ms)",
+                probe.mirror.name,
+                probe.latest.as_ref().map(|e| e.name.as_str()).unwrap_or("-"),
+                probe.latency_ms.unwrap_or(0)
+            ))),
+        }
+    }
+    probes
+}
+
+/// Pick the freshest snapshot across all mirrors. Other remote mirrors that serve the
+/// same file (and the same size, when both know it) become download fallbacks.
+pub async fn resolve_latest(client: &reqwest::Client, sources: &SnapshotSources) -> Result<ResolvedSnapshot, NodeError> {
+    let probes = probe_mirrors(client, sources.mirrors()).await;
+    let mut available = probes.into_iter().filter_map(|p| p.latest.map(|e| (p.mirror, e)));
+
+    let (mirror, entry) = available.next().ok_or_else(|| NodeError::SnapshotUnavailable("No snapshots found on any mirror".to_string()))?;
+    let fallbacks = available
+        .filter(|(m, e)| {
+            m.format != IndexFormat::Local
+                && e.name == entry.name
+                && match (e.size, entry.size) {
+                    (Some(a), Some(b)) => a == b,
+                    _ => true,
+                }
+        })
+        .map(|(_, e)| e.url)
+        .collect();
+
+    log_info("Selected snapshot", Some(&format!("{} from {}", entry.name, mirror.name)));
+    Ok(ResolvedSnapshot {
+        local: mirror.format == IndexFormat::Local,
+        mirror: mirror.name,
+        entry,
+        fallbacks,
+    })
+}
+
+/// Ask another mirror for the rest of a file, starting at `offset`. Only accepted if the
+/// mirror answers with exactly that range of a file of the same size.
+pub async fn continue_from_mirror(
+    client: &reqwest::Client,
+    url: &str,
+    offset: u64,
+    total_size: Option<u64>,
+) -> Result<reqwest::Response, NodeError> {
+    let response = client
+        .get(url)
+        .header("Range", format!("bytes={}-", offset))
+        .send()
+        .await
+        .map_err(|e| NodeError::from(e).context(url))?;
+    if response.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+        return Err(NodeError::SnapshotUnavailable(format!("{} doesn't support resume (HTTP {})", url, response.status())));
+    }
+
+    // Content-Range: bytes <start>-<end>/<total>
+    let content_range = response
+        .headers()
+        .get(reqwest::header::CONTENT_RANGE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    let (range, total) = content_range
+        .trim_start_matches("bytes ")
+        .split_once('/')
+        .ok_or_else(|| NodeError::SnapshotUnavailable(format!("{} sent no Content-Range", url)))?;
+    let start: Option<u64> = range.split('-').next().and_then(|s| s.parse().ok());
+    if start != Some(offset) {
+        return Err(NodeError::SnapshotUnavailable(format!("{} returned the wrong range ({})", url, content_range)));
+    }
+    if let Some(expected) = total_size {
+        if total.parse::<u64>().ok() != Some(expected) {
+            return Err(NodeError::SnapshotUnavailable(format!("{} serves a different file size ({})", url, content_range)));
+        }
+    }
+
+    log_info("Continuing download from mirror", Some(url));
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn html_listing_links_become_entries() {
+        let body = r#"<a href="backup_2024-05-01.tar.gz">backup_2024-05-01.tar.gz</a>
+            <a href="/old/backup_2024-04-01.tar.gz">old</a>
+            <a href="backup_2024-05-01.tar.gz">again</a>"#;
+        let entries = parse_html("https://example.com/snapshots/", body);
+        let urls: Vec<&str> = entries.iter().map(|e| e.url.as_str()).collect();
+        assert_eq!(urls, [
+            "https://example.com/old/backup_2024-04-01.tar.gz",
+            "https://example.com/snapshots/backup_2024-05-01.tar.gz",
+        ]);
+        assert_eq!(entries[1].name, "backup_2024-05-01.tar.gz");
+    }
+
+    #[test]
+    fn links_resolve_like_a_browser() {
+        let base = "https://example.com/snapshots";
+        assert_eq!(join_url(base, "a.tar.gz"), "https://example.com/snapshots/a.tar.gz");
+        assert_eq!(join_url(base, "/a.tar.gz"), "https://example.com/a.tar.gz");
+        assert_eq!(join_url(base, "../a.tar.gz"), "https://example.com/a.tar.gz");
+        assert_eq!(join_url(base, "https://cdn.example.com/a.tar.gz"), "https://cdn.example.com/a.tar.gz");
+    }
+
+    #[test]
+    fn builtin_pattern_only_accepts_dated_backups() {
+        let pattern = regex::Regex::new(builtin_mirrors()[0].pattern.as_deref().unwrap()).unwrap();
+        assert!(pattern.is_match("backup_2024-05-01.tar.gz"));
+        assert!(!pattern.is_match("koinos-tools.tar.gz"));
+        assert!(!pattern.is_match("backup_latest.tar.gz"));
+    }
+
+    #[test]
+    fn s3_listing_reads_size_and_last_modified() {
+        let body = r#"<ListBucketResult>
+            <Contents><Key>snapshots/backup_2024-05-01.tar.gz</Key><LastModified>2024-05-01T06:30:00.000Z</LastModified><Size>1234</Size></Contents>
+            <Contents><Key>snapshots/readme.txt</Key><Size>10</Size></Contents>
+        </ListBucketResult>"#;
+        let entries = parse_s3("https://bucket.example.com", body);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "backup_2024-05-01.tar.gz");
+        assert_eq!(entries[0].url, "https://bucket.example.com/snapshots/backup_2024-05-01.tar.gz");
+        assert_eq!(entries[0].size, Some(1234));
+        assert_eq!(entries[0].date, parse_date("2024-05-01T06:30:00Z"));
+    }
+
+    #[test]
+    fn dates_come_from_names_and_headers() {
+        assert_eq!(date_from_name("backup_2024-05-01.tar.gz"), parse_date("2024-05-01T00:00:00Z"));
+        assert_eq!(date_from_name("latest.tar.gz"), None);
+        assert_eq!(parse_date("Wed, 01 May 2024 06:30:00 GMT"), parse_date("2024-05-01T06:30:00Z"));
+        // A later day wins even when the name sorts lower
+        assert!(date_from_name("z_2024-04-30.tar.gz") < date_from_name("a_2024-05-01.tar.gz"));
+    }
+}
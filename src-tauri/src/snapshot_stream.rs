@@ -0,0 +1,319 @@
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use futures_util::StreamExt;
+use sha2::{Digest, Sha256};
+use tokio::sync::mpsc;
+use crate::download_control::DownloadControl;
+use crate::snapshot_downloader::DownloadProgress;
+use crate::snapshot_sources;
+use crate::logger::{log_debug, log_info, log_warn};
+use crate::error::NodeError;
+use crate::snapshot_verify::{self, RemoteSnapshotInfo, SnapshotManifest};
+
+/// Top-level directories a snapshot archive contains
+pub const SNAPSHOT_DIRS: [&str; 9] = [
+    "chain",
+    "block_store",
+    "account_history",
+    "contract_meta_store",
+    "transaction_store",
+    "mempool",
+    "p2p",
+    "grpc",
+    "jsonrpc",
+];
+
+// Reconnects tried when the stream drops before the attempt gives up
+const RECONNECT_ATTEMPTS: u32 = 3;
+
+// Hands downloaded chunks to the blocking extractor. A closed channel reads as EOF.
+struct ChannelReader {
+    rx: mpsc::Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.pos >= self.chunk.len() {
+            match self.rx.blocking_recv() {
+                Some(chunk) => {
+                    self.chunk = chunk;
+                    self.pos = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.chunk.len() - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Download the snapshot and unpack it on the fly into `<data_path>/.snapshot-staging`,
+/// then swap the extracted directories into `data_path` once the checksum matches.
+///
+/// Resuming works within an attempt only: a dropped connection is picked up at the
+/// byte it stopped at while the decoder still holds its state. A gzip stream can't be
+/// entered halfway, so an attempt that fails or is cancelled discards what it unpacked
+/// and the next one starts from byte 0.
+pub async fn stream_snapshot(
+    client: &reqwest::Client,
+    url: &str,
+    remote: &RemoteSnapshotInfo,
+    data_path: &Path,
+    control: &DownloadControl,
+    progress_callback: impl Fn(DownloadProgress),
+) -> Result<(), NodeError> {
+    let staging = data_path.join(".snapshot-staging");
+    log_info("Streaming snapshot extraction", Some(&staging.display().to_string()));
+    // Whatever an earlier attempt left can't be continued
+    discard_staging(&staging);
+
+    let response = client.get(url)
+        .send()
+        .await
+        .map_err(|e| NodeError::from(e).context("Failed to download snapshot"))?;
+    if !response.status().is_success() {
+        return Err(NodeError::SnapshotUnavailable(format!("Failed to download snapshot: HTTP {}", response.status())));
+    }
+
+    fs::create_dir_all(&staging)
+        .map_err(|e| NodeError::from(e).context("Failed to create staging directory"))?;
+
+    let known_size = response.content_length();
+    let total_size = known_size
+        .or(remote.size)
+        .unwrap_or(30_000_000_000); // ~30GB
+
+    let (tx, rx) = mpsc::channel::<Vec<u8>>(64);
+    let extract_dir = staging.clone();
+    let extractor = tokio::task::spawn_blocking(move || {
+        let reader = ChannelReader { rx, chunk: Vec::new(), pos: 0 };
+        unpack_stream(reader, &extract_dir)
+    });
+
+    let mut hasher = Sha256::new();
+    let mut downloaded = 0u64;
+    let mut stream = response.bytes_stream();
+    let start_time = std::time::Instant::now();
+    let mut last_progress_time = std::time::Instant::now();
+    let mut interrupted = None;
+    let mut cancelled = None;
+
+    while let Some(chunk_result) = stream.next().await {
+        let chunk = match chunk_result {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                // Pick the byte stream up where it stopped so the decoder never notices
+                match reconnect(client, url, downloaded, known_size, control).await {
+                    Some(response) => {
+                        log_warn("Snapshot stream interrupted, reconnected",
+                            Some(&format!("{:.1}GB so far. Error: {}", downloaded as f64 / 1_000_000_000.0, e)));
+                        stream = response.bytes_stream();
+                        continue;
+                    }
+                    None => {
+                        interrupted = Some(e.to_string());
+                        break;
+                    }
+                }
+            }
+        };
+
+        hasher.update(&chunk);
+        downloaded += chunk.len() as u64;
+
+        // The extractor hung up, so it has failed - its error is reported below
+        if tx.send(chunk.to_vec()).await.is_err() {
+            break;
+        }
+
+        // Pause, schedule window, rate limit and cancel
+        if let Err(e) = control.checkpoint(chunk.len()).await {
+            cancelled = Some(e);
+            break;
+        }
+
+        // Report progress every 5 seconds to avoid UI spam
+        if last_progress_time.elapsed() >= std::time::Duration::from_secs(5) {
+            let progress = DownloadProgress::measure(
+                downloaded,
+                total_size,
+                downloaded,
+                start_time.elapsed(),
+            );
+            progress.log();
+            progress_callback(progress);
+            last_progress_time = std::time::Instant::now();
+        }
+    }
+    drop(tx);
+
+    let extracted = extractor
+        .await
+        .map_err(|e| NodeError::from(e).context("Extraction task failed"))?;
+
+    if let Some(e) = cancelled {
+        discard_staging(&staging);
+        return Err(e);
+    }
+    if let Some(e) = interrupted {
+        discard_staging(&staging);
+        log_warn("Streamed download interrupted",
+            Some(&format!("Streamed {:.1}GB. Error: {}", downloaded as f64 / 1_000_000_000.0, e)));
+        return Err(NodeError::DownloadInterrupted(format!(
+            "Download interrupted at {:.1}GB of {:.1}GB. A streamed download can't be resumed, the next attempt starts over. Error: {}",
+            downloaded as f64 / 1_000_000_000.0,
+            total_size as f64 / 1_000_000_000.0,
+            e
+        )));
+    }
+
+    let unpacked = match extracted {
+        Ok(unpacked) => unpacked,
+        Err(e) => {
+            discard_staging(&staging);
+            return Err(e);
+        }
+    };
+    log_info("Snapshot streamed", Some(&format!("{} entries unpacked", unpacked)));
+
+    // The gzip CRC was checked while decompressing; also check the published checksum
+    let computed = snapshot_verify::hex_digest(hasher);
+    match snapshot_verify::fetch_published_checksum(client, url).await {
+        Some(expected) if expected != computed => {
+            discard_staging(&staging);
+            return Err(NodeError::SnapshotCorrupt(format!(
+                "Snapshot checksum mismatch (expected {}, got {})",
+                expected, computed
+            )));
+        }
+        Some(_) => log_info("Snapshot verified", Some(&computed)),
+        None => log_warn("No published checksum, relying on archive integrity", None),
+    }
+
+    swap_in(&staging, data_path)?;
+    progress_callback(DownloadProgress::complete(total_size));
+    Ok(())
+}
+
+// Ask the server for the rest of the stream, a few times with backoff. After a pause
+// the server has most likely dropped the connection, so the first try goes right away.
+async fn reconnect(
+    client: &reqwest::Client,
+    url: &str,
+    offset: u64,
+    total_size: Option<u64>,
+    control: &DownloadControl,
+) -> Option<reqwest::Response> {
+    let paused = control.take_interrupted();
+    for attempt in 0..RECONNECT_ATTEMPTS {
+        if attempt > 0 || !paused {
+            tokio::time::sleep(std::time::Duration::from_secs(2 << attempt)).await;
+        }
+        if control.is_cancelled() {
+            return None;
+        }
+        match snapshot_sources::continue_from_mirror(client, url, offset, total_size).await {
+            Ok(response) => return Some(response),
+            Err(e) => log_warn("Failed to reconnect snapshot stream", Some(&e.to_string())),
+        }
+    }
+    None
+}
+
+// Runs on a blocking thread: gunzip + untar everything the reader yields
+fn unpack_stream(reader: ChannelReader, staging: &Path) -> Result<u64, NodeError> {
+    let mut decoder = flate2::read::MultiGzDecoder::new(reader);
+    let mut unpacked = 0u64;
+    {
+        let mut archive = tar::Archive::new(&mut decoder);
+        let entries = archive.entries()
+            .map_err(|e| NodeError::from(e).context("Failed to read snapshot archive"))?;
+
+        for entry in entries {
+            let mut entry = entry.map_err(|e| NodeError::SnapshotCorrupt(format!("Snapshot archive is corrupt: {}", e)))?;
+            let path = entry.path()
+                .map_err(|e| NodeError::from(e).context("Invalid path in snapshot"))?
+                .to_string_lossy()
+                .to_string();
+
+            // unpack_in refuses paths that would escape the staging directory
+            let inside = entry.unpack_in(staging)
+                .map_err(|e| NodeError::from(e).context(&format!("Failed to unpack {}", path)))?;
+            if !inside {
+                log_warn("Skipping unsafe path in snapshot", Some(&path));
+                continue;
+            }
+
+            unpacked += 1;
+            if unpacked.is_multiple_of(1000) {
+                log_debug("Unpacked snapshot entries", Some(&unpacked.to_string()));
+            }
+        }
+    }
+
+    // Read past the tar trailer so the gzip CRC is checked
+    std::io::copy(&mut decoder, &mut std::io::sink())
+        .map_err(|e| NodeError::SnapshotCorrupt(format!("Snapshot archive is corrupt: {}", e)))?;
+
+    Ok(unpacked)
+}
+
+// Move the staged directories over the live ones. Everything is on the same filesystem
+// so each rename is atomic; if one fails the previous data is put back.
+fn swap_in(staging: &Path, data_path: &Path) -> Result<(), NodeError> {
+    let previous = data_path.join(".snapshot-previous");
+    fs::remove_dir_all(&previous).ok();
+    fs::create_dir_all(&previous)
+        .map_err(|e| NodeError::from(e).context("Failed to create backup directory"))?;
+
+    let mut swapped: Vec<&str> = Vec::new();
+    let mut result = Ok(());
+    for dir in SNAPSHOT_DIRS {
+        let src = staging.join(dir);
+        let dst = data_path.join(dir);
+        if !src.exists() {
+            log_warn("Directory not found in extracted data", Some(dir));
+            continue;
+        }
+        if dst.exists() {
+            if let Err(e) = fs::rename(&dst, previous.join(dir)) {
+                result = Err(NodeError::from(e).context(&format!("Failed to move aside {}", dir)));
+                break;
+            }
+        }
+        if let Err(e) = fs::rename(&src, &dst) {
+            fs::rename(previous.join(dir), &dst).ok();
+            result = Err(NodeError::from(e).context(&format!("Failed to move {}", dir)));
+            break;
+        }
+        swapped.push(dir);
+        log_info("Moved directory", Some(dir));
+    }
+
+    if let Err(e) = result {
+        for dir in swapped {
+            fs::rename(data_path.join(dir), staging.join(dir)).ok();
+            if previous.join(dir).exists() {
+                fs::rename(previous.join(dir), data_path.join(dir)).ok();
+            }
+        }
+        return Err(e);
+    }
+
+    fs::remove_dir_all(&previous).ok();
+    discard_staging(staging);
+    log_info("All blockchain data moved successfully", None);
+    Ok(())
+}
+
+fn discard_staging(staging: &Path) {
+    if staging.exists() {
+        fs::remove_dir_all(staging).ok();
+    }
+    SnapshotManifest::remove(staging);
+}
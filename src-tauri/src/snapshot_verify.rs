@@ -0,0 +1,239 @@
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use crate::logger::{log_info, log_warn};
+use crate::error::NodeError;
+
+// Snapshots that failed verification, kept next to where they were downloaded
+const QUARANTINE_DIR: &str = "koinos_snapshot_quarantine";
+
+/// What the server told us about a snapshot, used to make sure a resumed download
+/// continues the same file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RemoteSnapshotInfo {
+    pub size: Option<u64>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    #[serde(default)]
+    pub accept_ranges: bool,
+}
+
+impl RemoteSnapshotInfo {
+    pub fn from_headers(headers: &reqwest::header::HeaderMap, size: Option<u64>) -> Self {
+        let header = |name: reqwest::header::HeaderName| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.to_string())
+        };
+        Self {
+            size,
+            etag: header(reqwest::header::ETAG),
+            last_modified: header(reqwest::header::LAST_MODIFIED),
+            accept_ranges: header(reqwest::header::ACCEPT_RANGES).as_deref() == Some("bytes"),
+        }
+    }
+
+    /// Value for an `If-Range` header. Weak ETags are not allowed there, so fall back to
+    /// Last-Modified for those.
+    pub fn validator(&self) -> Option<String> {
+        match &self.etag {
+            Some(etag) if !etag.starts_with("W/") => Some(etag.clone()),
+            _ => self.last_modified.clone(),
+        }
+    }
+}
+
+/// Sidecar written next to a snapshot download (`<file>.manifest.json`).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SnapshotManifest {
+    pub url: String,
+    pub remote: RemoteSnapshotInfo,
+    pub expected_sha256: Option<String>,
+    pub computed_sha256: Option<String>,
+    pub verified: bool,
+}
+
+impl SnapshotManifest {
+    pub fn path_for(snapshot_path: &Path) -> PathBuf {
+        PathBuf::from(format!("{}.manifest.json", snapshot_path.display()))
+    }
+
+    pub fn load(snapshot_path: &Path) -> Option<Self> {
+        let content = fs::read_to_string(Self::path_for(snapshot_path)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    pub fn save(&self, snapshot_path: &Path) -> Result<(), NodeError> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| NodeError::from(e).context("Failed to serialize snapshot manifest"))?;
+        fs::write(Self::path_for(snapshot_path), json)
+            .map_err(|e| NodeError::from(e).context("Failed to write snapshot manifest"))
+    }
+
+    pub fn remove(snapshot_path: &Path) {
+        fs::remove_file(Self::path_for(snapshot_path)).ok();
+    }
+
+    /// Whether a partial file described by this manifest can be continued from `url`.
+    /// Another mirror of the same file will do when its size and validator match.
+    pub fn same_snapshot(&self, url: &str, remote: &RemoteSnapshotInfo) -> bool {
+        // The HEAD probe failed; the If-Range header on the resume request still protects
+        // us, as long as it goes to the server the validator came from
+        if remote.size.is_none() && remote.etag.is_none() && remote.last_modified.is_none() {
+            return self.url == url && self.remote.validator().is_some();
+        }
+        if let (Some(a), Some(b)) = (self.remote.size, remote.size) {
+            if a != b {
+                return false;
+            }
+        }
+        if let (Some(a), Some(b)) = (&self.remote.etag, &remote.etag) {
+            return a == b;
+        }
+        if let (Some(a), Some(b)) = (&self.remote.last_modified, &remote.last_modified) {
+            return a == b;
+        }
+        // Nothing to compare against - don't trust the partial file
+        false
+    }
+}
+
+/// HEAD the snapshot to learn its size and validators
+pub async fn probe_remote(client: &reqwest::Client, url: &str) -> RemoteSnapshotInfo {
+    match client.head(url).send().await {
+        Ok(response) if response.status().is_success() => {
+            let size = response.content_length();
+            RemoteSnapshotInfo::from_headers(response.headers(), size)
+        }
+        _ => RemoteSnapshotInfo::default(),
+    }
+}
+
+/// Fetch the published `<url>.sha256` checksum, if the host provides one
+pub async fn fetch_published_checksum(client: &reqwest::Client, url: &str) -> Option<String> {
+    let response = client.get(format!("{}.sha256", url)).send().await.ok()?;
+    if !response.status().is_success() {
+        return None;
+    }
+    let text = response.text().await.ok()?;
+    parse_checksum(&text)
+}
+
+// sha256sum format is "<hex>  <filename>"; accept a bare hash as well
+fn parse_checksum(text: &str) -> Option<String> {
+    let token = text.split_whitespace().next()?.to_lowercase();
+    if token.len() == 64 && token.chars().all(|c| c.is_ascii_hexdigit()) {
+        Some(token)
+    } else {
+        None
+    }
+}
+
+pub fn hex_digest(hasher: Sha256) -> String {
+    format!("{:x}", hasher.finalize())
+}
+
+/// SHA-256 of a file, computed off the async runtime
+pub async fn sha256_file(path: &Path) -> Result<String, NodeError> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let mut file = fs::File::open(&path).map_err(|e| NodeError::from(e).context("Failed to open snapshot"))?;
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0u8; 4 * 1024 * 1024];
+        loop {
+            let read = file
+                .read(&mut buffer)
+                .map_err(|e| NodeError::from(e).context("Failed to read snapshot"))?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+        }
+        Ok(hex_digest(hasher))
+    })
+    .await
+    .map_err(|e| NodeError::from(e).context("Checksum task failed"))?
+}
+
+/// Decompress the whole archive to make sure the gzip stream and its CRC are intact
+pub async fn verify_gzip(path: &Path) -> Result<(), NodeError> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let file = fs::File::open(&path).map_err(|e| NodeError::from(e).context("Failed to open snapshot"))?;
+        let mut decoder = flate2::read::MultiGzDecoder::new(std::io::BufReader::new(file));
+        std::io::copy(&mut decoder, &mut std::io::sink())
+            .map(|_| ())
+            .map_err(|e| NodeError::SnapshotCorrupt(format!("Snapshot archive is corrupt: {}", e)))
+    })
+    .await
+    .map_err(|e| NodeError::from(e).context("Integrity check task failed"))?
+}
+
+/// Move a snapshot that failed verification out of the way so it is never extracted
+/// or resumed. Returns where it was moved to.
+pub fn quarantine(snapshot_path: &Path) -> Option<PathBuf> {
+    // Next to the archive, so the move is a rename on the same filesystem
+    let quarantine_dir = snapshot_path.parent()?.join(QUARANTINE_DIR);
+    fs::create_dir_all(&quarantine_dir).ok()?;
+    let name = snapshot_path.file_name()?.to_string_lossy().to_string();
+    let target = quarantine_dir.join(format!(
+        "{}.{}",
+        name,
+        chrono::Local::now().format("%Y%m%d%H%M%S")
+    ));
+
+    SnapshotManifest::remove(snapshot_path);
+    match fs::rename(snapshot_path, &target) {
+        Ok(()) => {
+            log_warn("Snapshot quarantined", Some(&target.display().to_string()));
+            Some(target)
+        }
+        Err(e) => {
+            // Left in place it would be picked up again as a partial download
+            log_warn("Failed to quarantine snapshot, deleting it", Some(&e.to_string()));
+            fs::remove_file(snapshot_path).ok();
+            None
+        }
+    }
+}
+
+/// Check a finished download against the published checksum (or, if there is none, the
+/// gzip CRC) and record the result in the manifest. Failed files are quarantined.
+pub async fn verify_snapshot(
+    client: &reqwest::Client,
+    snapshot_path: &Path,
+    manifest: &mut SnapshotManifest,
+    computed_sha256: String,
+) -> Result<(), NodeError> {
+    log_info("Verifying snapshot", Some(&computed_sha256));
+
+    if manifest.expected_sha256.is_none() {
+        manifest.expected_sha256 = fetch_published_checksum(client, &manifest.url).await;
+    }
+
+    let result = match &manifest.expected_sha256 {
+        Some(expected) if *expected != computed_sha256 => Err(NodeError::SnapshotCorrupt(format!(
+            "Snapshot checksum mismatch (expected {}, got {})",
+            expected, computed_sha256
+        ))),
+        Some(_) => Ok(()),
+        None => {
+            log_warn("No published checksum, checking archive integrity instead", None);
+            verify_gzip(snapshot_path).await
+        }
+    };
+
+    if let Err(e) = result {
+        quarantine(snapshot_path);
+        return Err(e);
+    }
+
+    manifest.computed_sha256 = Some(computed_sha256);
+    manifest.verified = true;
+    manifest.save(snapshot_path)?;
+    log_info("Snapshot verified", None);
+    Ok(())
+}
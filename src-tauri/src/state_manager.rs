@@ -1,9 +1,26 @@
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use crate::container_runtime::RuntimeKind;
+use crate::node_manager::NodeMode;
+use crate::error::NodeError;
+use crate::logger::{log_error, log_info, log_warn};
+
+/// Schema version written to node_state.json. Bump it and add a migration to
+/// `MIGRATIONS` when a change needs more than a serde default.
+pub const STATE_VERSION: u32 = 1;
+
+// MIGRATIONS[n] upgrades a version n file to version n + 1
+const MIGRATIONS: &[fn(&mut serde_json::Value)] = &[
+    // 0: files from before versioning; every field they lack has a serde default
+    |_| {},
+];
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NodeState {
+    #[serde(default)]
+    pub version: u32,
     pub last_block: u64,
     pub last_sync_progress: f32,
     pub total_uptime_seconds: u64,
@@ -12,11 +29,16 @@ pub struct NodeState {
     pub first_sync_completed: bool,
     pub install_date: String,
     pub last_run_date: String,
+    #[serde(default)]
+    pub container_runtime: Option<RuntimeKind>,
+    #[serde(default)]
+    pub node_mode: NodeMode,
 }
 
 impl Default for NodeState {
     fn default() -> Self {
         Self {
+            version: STATE_VERSION,
             last_block: 0,
             last_sync_progress: 0.0,
             total_uptime_seconds: 0,
@@ -25,6 +47,8 @@ impl Default for NodeState {
             first_sync_completed: false,
             install_date: chrono::Local::now().to_rfc3339(),
             last_run_date: chrono::Local::now().to_rfc3339(),
+            container_runtime: None,
+            node_mode: NodeMode::default(),
         }
     }
 }
@@ -37,46 +61,73 @@ pub struct StateManager {
 impl StateManager {
     pub fn new() -> Self {
         let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
-        let state_path = home.join(".koinos").join("node_state.json");
-        
-        let state = if state_path.exists() {
-            fs::read_to_string(&state_path)
-                .ok()
-                .and_then(|content| serde_json::from_str(&content).ok())
-                .unwrap_or_default()
-        } else {
-            NodeState::default()
-        };
+        Self::with_path(home.join(".koinos").join("node_state.json"))
+    }
+
+    pub fn with_path(state_path: PathBuf) -> Self {
+        let mut manager = Self { state_path, state: NodeState::default() };
+        manager.load();
+        manager
+    }
 
-        Self { state_path, state }
+    fn backup_path(&self) -> PathBuf {
+        self.state_path.with_extension("json.bak")
     }
 
-    pub fn load(&mut self) -> Result<NodeState, String> {
+    /// Read the state file, falling back to the backup and then to defaults. A file
+    /// that can't be read is kept as `.corrupt` so defaults don't overwrite it.
+    pub fn load(&mut self) -> NodeState {
+        if !self.state_path.exists() && !self.backup_path().exists() {
+            return self.state.clone();
+        }
+
+        let primary = match read_state(&self.state_path) {
+            Ok(state) => {
+                self.state = state;
+                return self.state.clone();
+            }
+            Err(e) => e,
+        };
+        log_warn("State file unreadable, trying backup", Some(&primary.to_string()));
+
+        match read_state(&self.backup_path()) {
+            Ok(state) => {
+                log_info("Recovered node state from backup", None);
+                self.state = state;
+            }
+            Err(e) => {
+                log_error(
+                    "Backup state unreadable, starting from defaults",
+                    Some(&e.to_string()),
+                );
+                self.state = NodeState::default();
+            }
+        }
+
         if self.state_path.exists() {
-            let content = fs::read_to_string(&self.state_path)
-                .map_err(|e| format!("Failed to read state file: {}", e))?;
-            
-            self.state = serde_json::from_str(&content)
-                .map_err(|e| format!("Failed to parse state file: {}", e))?;
+            fs::rename(&self.state_path, self.state_path.with_extension("json.corrupt")).ok();
         }
-        
-        Ok(self.state.clone())
+        // Rewrite the primary right away so the next start doesn't go through this again
+        if let Err(e) = self.save() {
+            log_error("Failed to save recovered state", Some(&e.to_string()));
+        }
+        self.state.clone()
     }
 
-    pub fn save(&self) -> Result<(), String> {
-        // Ensure directory exists
-        if let Some(parent) = self.state_path.parent() {
-            fs::create_dir_all(parent)
-                .map_err(|e| format!("Failed to create state directory: {}", e))?;
-        }
+    /// Atomically replace the state file, keeping the previous one as `.bak`
+    pub fn save(&self) -> Result<(), NodeError> {
+        let mut state = self.state.clone();
+        state.version = STATE_VERSION;
+        let json = serde_json::to_string_pretty(&state)
+            .map_err(|e| NodeError::from(e).context("Failed to serialize state"))?;
 
-        let json = serde_json::to_string_pretty(&self.state)
-            .map_err(|e| format!("Failed to serialize state: {}", e))?;
-        
-        fs::write(&self.state_path, json)
-            .map_err(|e| format!("Failed to write state file: {}", e))?;
-        
-        Ok(())
+        // Only a file that loads is worth keeping as the backup
+        if read_state(&self.state_path).is_ok() {
+            fs::copy(&self.state_path, self.backup_path())
+                .map_err(|e| NodeError::from(e).context("Failed to back up state file"))?;
+        }
+        write_atomic(&self.state_path, json.as_bytes())
+            .map_err(|e| e.context("Failed to write state file"))
     }
 
     pub fn update_sync_progress(&mut self, block: u64, progress: f32) {
@@ -104,19 +155,27 @@ impl StateManager {
         }
     }
 
+    /// Doesn't save; the accounting counters are bumped together and saved once by the caller
     pub fn increment_uptime(&mut self, seconds: u64) {
         self.state.total_uptime_seconds += seconds;
-        let _ = self.save();
     }
 
     pub fn increment_blocks_validated(&mut self, count: u64) {
         self.state.blocks_validated += count;
-        let _ = self.save();
     }
 
     pub fn add_data_relayed(&mut self, gb: f32) {
         self.state.data_relayed_gb += gb;
-        let _ = self.save();
+    }
+
+    pub fn set_container_runtime(&mut self, kind: RuntimeKind) -> Result<(), NodeError> {
+        self.state.container_runtime = Some(kind);
+        self.save()
+    }
+
+    pub fn set_node_mode(&mut self, mode: NodeMode) -> Result<(), NodeError> {
+        self.state.node_mode = mode;
+        self.save()
     }
 
     pub fn get_state(&self) -> &NodeState {
@@ -137,4 +196,125 @@ impl StateManager {
             format!("{}m", minutes)
         }
     }
-}
\ No newline at end of file
+}
+
+fn read_state(path: &Path) -> Result<NodeState, NodeError> {
+    let content = fs::read_to_string(path)?;
+    let mut value: serde_json::Value = serde_json::from_str(&content)?;
+
+    let version = value.get("version").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+    if version > STATE_VERSION {
+        log_warn(
+            "State file is from a newer version of the app",
+            Some(&format!("version {}, this build understands {}", version, STATE_VERSION)),
+        );
+    }
+    for (from, migrate) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        migrate(&mut value);
+        log_info("Migrated node state", Some(&format!("version {} -> {}", from, from + 1)));
+    }
+
+    Ok(serde_json::from_value(value)?)
+}
+
+/// Write to a temporary file, fsync it and rename it over `path`, so a crash leaves
+/// either the old or the new contents
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), NodeError> {
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    fs::create_dir_all(dir)
+        .map_err(|e| NodeError::from(e).context("Failed to create state directory"))?;
+
+    let tmp = path.with_extension("tmp");
+    {
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+    }
+    fs::rename(&tmp, path)?;
+
+    // Persist the rename itself
+    #[cfg(unix)]
+    if let Ok(dir) = fs::File::open(dir) {
+        dir.sync_all().ok();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("koinos-state-{}-{}", name, std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn corrupt_state_is_recovered_from_backup_and_kept_aside() {
+        let dir = temp_dir("recover");
+        let path = dir.join("node_state.json");
+
+        let mut manager = StateManager::with_path(path.clone());
+        manager.increment_blocks_validated(500);
+        manager.save().unwrap();
+        manager.increment_blocks_validated(100);
+        manager.save().unwrap();
+        fs::write(&path, "{ \"blocks_validated\": 6").unwrap();
+
+        let manager = StateManager::with_path(path.clone());
+        assert_eq!(manager.get_state().blocks_validated, 500);
+        assert_eq!(fs::read_to_string(path.with_extension("json.corrupt")).unwrap(), "{ \"blocks_validated\": 6");
+        assert_eq!(read_state(&path).unwrap().blocks_validated, 500);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn unloadable_state_is_not_backed_up() {
+        let dir = temp_dir("backup");
+        let path = dir.join("node_state.json");
+
+        let mut manager = StateManager::with_path(path.clone());
+        manager.increment_blocks_validated(500);
+        manager.save().unwrap();
+        manager.save().unwrap();
+        // Valid JSON, but not a node state
+        fs::write(&path, "{}").unwrap();
+        manager.save().unwrap();
+
+        assert_eq!(read_state(&manager.backup_path()).unwrap().blocks_validated, 500);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn files_from_before_versioning_are_migrated() {
+        let dir = temp_dir("migrate");
+        let path = dir.join("node_state.json");
+        fs::write(
+            &path,
+            r#"{
+                "last_block": 42,
+                "last_sync_progress": 1.5,
+                "total_uptime_seconds": 60,
+                "blocks_validated": 42,
+                "data_relayed_gb": 0.0,
+                "first_sync_completed": false,
+                "install_date": "2024-01-01T00:00:00+00:00",
+                "last_run_date": "2024-01-02T00:00:00+00:00"
+            }"#,
+        )
+        .unwrap();
+
+        let state = read_state(&path).unwrap();
+        assert_eq!(state.last_block, 42);
+        assert_eq!(state.container_runtime, None);
+        assert_eq!(state.node_mode, NodeMode::default());
+
+        // Saving stamps the current version
+        StateManager::with_path(path.clone()).save().unwrap();
+        let saved: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved["version"], STATE_VERSION);
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,555 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::Command as AsyncCommand;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use crate::logger::{log_debug, log_error, log_info, log_warn};
+use crate::error::NodeError;
+
+const LOG_MAX_BYTES: u64 = 10 * 1024 * 1024;
+const LOG_KEEP_FILES: u32 = 5;
+
+// A service that stays up this long has its backoff reset
+const STABLE_RUN: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RestartPolicy {
+    Always,
+    OnFailure,
+    Never,
+}
+
+impl RestartPolicy {
+    fn restarts(self, failed: bool) -> bool {
+        match self {
+            RestartPolicy::Always => true,
+            RestartPolicy::OnFailure => failed,
+            RestartPolicy::Never => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceStatus {
+    Starting,
+    Running,
+    Backoff,
+    Stopped,
+    Failed,
+}
+
+/// How to run one supervised process.
+#[derive(Debug, Clone)]
+pub struct ServiceSpec {
+    pub name: String,
+    pub binary: PathBuf,
+    pub args: Vec<String>,
+    pub working_dir: PathBuf,
+    pub policy: RestartPolicy,
+    pub max_restarts: u32,
+    pub backoff_base: Duration,
+    pub backoff_max: Duration,
+    /// How long to wait after spawning before starting the next service
+    pub startup_grace: Duration,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceState {
+    pub name: String,
+    pub status: ServiceStatus,
+    pub pid: Option<u32>,
+    pub restarts: u32,
+    pub last_exit_code: Option<i32>,
+    pub last_exit_reason: Option<String>,
+    pub started_at: Option<String>,
+    pub last_exit_at: Option<String>,
+}
+
+impl ServiceState {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            status: ServiceStatus::Stopped,
+            pid: None,
+            restarts: 0,
+            last_exit_code: None,
+            last_exit_reason: None,
+            started_at: None,
+            last_exit_at: None,
+        }
+    }
+}
+
+struct SupervisedService {
+    name: String,
+    shutdown: watch::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+/// Owns the child processes of native mode, restarts them according to their policy and
+/// pipes their output into per-service rotating logs.
+#[derive(Clone)]
+pub struct Supervisor {
+    logs_dir: PathBuf,
+    run_dir: PathBuf,
+    states: Arc<Mutex<HashMap<String, ServiceState>>>,
+    services: Arc<tokio::sync::Mutex<Vec<SupervisedService>>>,
+}
+
+impl Supervisor {
+    pub fn new(logs_dir: PathBuf, run_dir: PathBuf) -> Self {
+        Self {
+            logs_dir,
+            run_dir,
+            states: Arc::new(Mutex::new(HashMap::new())),
+            services: Arc::new(tokio::sync::Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Start services in the given (dependency) order. Each service gets its startup grace
+    /// period and must still be alive afterwards before the next one is launched.
+    pub async fn start(&self, specs: Vec<ServiceSpec>) -> Result<(), NodeError> {
+        fs::create_dir_all(&self.logs_dir).map_err(|e| NodeError::from(e).context("Failed to create logs directory"))?;
+        fs::create_dir_all(&self.run_dir).map_err(|e| NodeError::from(e).context("Failed to create run directory"))?;
+
+        // Services that gave up leave the node half down; an explicit start begins afresh
+        let gave_up = self.states().iter().any(|s| s.status == ServiceStatus::Failed);
+        let finished = self.services.lock().await.iter().all(|s| s.task.is_finished());
+        if gave_up || finished {
+            self.stop().await;
+        }
+
+        let mut services = self.services.lock().await;
+        if !services.is_empty() {
+            return Err(NodeError::NodeRunning("Native services are already running".to_string()));
+        }
+
+        for spec in specs {
+            {
+                let mut states = self.states.lock().unwrap();
+                states.insert(spec.name.clone(), ServiceState::new(&spec.name));
+            }
+
+            let (shutdown_tx, shutdown_rx) = watch::channel(false);
+            let grace = spec.startup_grace;
+            let policy = spec.policy;
+            let name = spec.name.clone();
+            let task = tokio::spawn(run_service(
+                spec,
+                self.states.clone(),
+                self.logs_dir.clone(),
+                self.run_dir.clone(),
+                shutdown_rx,
+            ));
+            services.push(SupervisedService { name: name.clone(), shutdown: shutdown_tx, task });
+
+            tokio::time::sleep(grace).await;
+            let state = self.state(&name);
+            let status = state.as_ref().map(|s| s.status);
+            // A service that may stop on its own is only a failed start if it crashed
+            let finished = policy == RestartPolicy::OnFailure
+                && status == Some(ServiceStatus::Stopped)
+                && state.as_ref().and_then(|s| s.last_exit_code) == Some(0);
+            if status != Some(ServiceStatus::Running) && !finished {
+                let reason = state
+                    .and_then(|s| s.last_exit_reason)
+                    .unwrap_or_else(|| "did not stay up".to_string());
+                drop(services);
+                self.stop().await;
+                return Err(NodeError::Process(format!("{} failed to start: {}", name, reason)));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Stop all services in reverse start order and wait for them to exit
+    pub async fn stop(&self) {
+        let mut services = self.services.lock().await;
+        while let Some(service) = services.pop() {
+            log_debug("Stopping supervised service", Some(&service.name));
+            service.shutdown.send(true).ok();
+            if service.task.await.is_err() {
+                log_error("Supervisor task panicked", Some(&service.name));
+            }
+        }
+    }
+
+    pub fn state(&self, name: &str) -> Option<ServiceState> {
+        self.states.lock().unwrap().get(name).cloned()
+    }
+
+    pub fn states(&self) -> Vec<ServiceState> {
+        let mut states: Vec<ServiceState> = self.states.lock().unwrap().values().cloned().collect();
+        states.sort_by(|a, b| a.name.cmp(&b.name));
+        states
+    }
+
+    pub fn is_running(&self, name: &str) -> bool {
+        self.state(name)
+            .map(|s| s.status == ServiceStatus::Running)
+            .unwrap_or(false)
+    }
+}
+
+fn update_state(states: &Mutex<HashMap<String, ServiceState>>, name: &str, f: impl FnOnce(&mut ServiceState)) {
+    if let Ok(mut states) = states.lock() {
+        if let Some(state) = states.get_mut(name) {
+            f(state);
+        }
+    }
+}
+
+async fn run_service(
+    spec: ServiceSpec,
+    states: Arc<Mutex<HashMap<String, ServiceState>>>,
+    logs_dir: PathBuf,
+    run_dir: PathBuf,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let log = Arc::new(Mutex::new(RotatingLog::new(logs_dir.join(format!("{}.log", spec.name)))));
+    let pid_file = run_dir.join(format!("{}.pid", spec.name));
+    let mut consecutive_failures = 0u32;
+
+    loop {
+        update_state(&states, &spec.name, |s| s.status = ServiceStatus::Starting);
+
+        let spawned = AsyncCommand::new(&spec.binary)
+            .args(&spec.args)
+            .current_dir(&spec.working_dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn();
+
+        let mut child = match spawned {
+            Ok(child) => child,
+            Err(e) => {
+                log_error(&format!("Failed to spawn {}", spec.name), Some(&e.to_string()));
+                update_state(&states, &spec.name, |s| {
+                    s.status = ServiceStatus::Failed;
+                    s.last_exit_reason = Some(format!("spawn failed: {}", e));
+                });
+                return;
+            }
+        };
+
+        let pid = child.id();
+        if let Some(pid) = pid {
+            fs::write(&pid_file, pid.to_string()).ok();
+        }
+        update_state(&states, &spec.name, |s| {
+            s.status = ServiceStatus::Running;
+            s.pid = pid;
+            s.started_at = Some(chrono::Local::now().to_rfc3339());
+        });
+        log_info("Supervised service started", Some(&format!("{} (pid {:?})", spec.name, pid)));
+
+        if let Some(stdout) = child.stdout.take() {
+            tokio::spawn(pump_output(stdout, log.clone()));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            tokio::spawn(pump_output(stderr, log.clone()));
+        }
+
+        let started = Instant::now();
+        let exit = tokio::select! {
+            exit = child.wait() => exit,
+            _ = shutdown.changed() => {
+                // Ask nicely first, then force it
+                if let Some(pid) = pid {
+                    terminate(pid, false);
+                }
+                let exit = match tokio::time::timeout(Duration::from_secs(10), child.wait()).await {
+                    Ok(exit) => exit,
+                    Err(_) => {
+                        log_warn("Service did not exit, killing", Some(&spec.name));
+                        child.kill().await.ok();
+                        child.wait().await
+                    }
+                };
+                record_exit(&states, &spec.name, exit.ok(), ServiceStatus::Stopped);
+                fs::remove_file(&pid_file).ok();
+                return;
+            }
+        };
+
+        fs::remove_file(&pid_file).ok();
+        let exit = exit.ok();
+        let failed = !exit.map(|e| e.success()).unwrap_or(false);
+        record_exit(&states, &spec.name, exit, ServiceStatus::Backoff);
+
+        if !spec.policy.restarts(failed) {
+            update_state(&states, &spec.name, |s| s.status = ServiceStatus::Stopped);
+            return;
+        }
+
+        if started.elapsed() >= STABLE_RUN {
+            consecutive_failures = 0;
+        }
+        consecutive_failures += 1;
+        if consecutive_failures > spec.max_restarts {
+            log_error(
+                "Service keeps crashing, giving up",
+                Some(&format!("{} after {} restarts", spec.name, spec.max_restarts)),
+            );
+            update_state(&states, &spec.name, |s| s.status = ServiceStatus::Failed);
+            return;
+        }
+
+        let delay = backoff_delay(&spec, consecutive_failures);
+        log_warn(
+            "Supervised service exited, restarting",
+            Some(&format!("{} in {}s", spec.name, delay.as_secs())),
+        );
+
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = shutdown.changed() => {
+                update_state(&states, &spec.name, |s| s.status = ServiceStatus::Stopped);
+                return;
+            }
+        }
+        update_state(&states, &spec.name, |s| s.restarts += 1);
+    }
+}
+
+// Exponential backoff: base * 2^(failures - 1), capped
+fn backoff_delay(spec: &ServiceSpec, failures: u32) -> Duration {
+    spec.backoff_base
+        .saturating_mul(2u32.saturating_pow(failures.saturating_sub(1)))
+        .min(spec.backoff_max)
+}
+
+fn record_exit(
+    states: &Mutex<HashMap<String, ServiceState>>,
+    name: &str,
+    exit: Option<ExitStatus>,
+    status: ServiceStatus,
+) {
+    let reason = match exit {
+        Some(exit) => describe_exit(&exit),
+        None => "unknown".to_string(),
+    };
+    log_debug("Supervised service exited", Some(&format!("{}: {}", name, reason)));
+    update_state(states, name, |s| {
+        s.status = status;
+        s.pid = None;
+        s.last_exit_code = exit.and_then(|e| e.code());
+        s.last_exit_reason = Some(reason);
+        s.last_exit_at = Some(chrono::Local::now().to_rfc3339());
+    });
+}
+
+fn describe_exit(exit: &ExitStatus) -> String {
+    if let Some(code) = exit.code() {
+        return format!("exit code {}", code);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = exit.signal() {
+            return format!("signal {}", signal);
+        }
+    }
+    "terminated".to_string()
+}
+
+async fn pump_output<R: AsyncRead + Unpin>(reader: R, log: Arc<Mutex<RotatingLog>>) {
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if let Ok(mut log) = log.lock() {
+            log.write_line(&line);
+        }
+    }
+}
+
+/// Size-capped log file that keeps `LOG_KEEP_FILES` rotated generations (`name.log.1` ...).
+struct RotatingLog {
+    path: PathBuf,
+    file: Option<fs::File>,
+    size: u64,
+    max_bytes: u64,
+}
+
+impl RotatingLog {
+    fn new(path: PathBuf) -> Self {
+        let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        Self { path, file: None, size, max_bytes: LOG_MAX_BYTES }
+    }
+
+    fn write_line(&mut self, line: &str) {
+        if self.size + line.len() as u64 + 1 > self.max_bytes {
+            self.rotate();
+        }
+        if self.file.is_none() {
+            self.file = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .ok();
+        }
+        if let Some(file) = self.file.as_mut() {
+            if writeln!(file, "{}", line).is_ok() {
+                self.size += line.len() as u64 + 1;
+            }
+        }
+    }
+
+    fn rotate(&mut self) {
+        self.file = None;
+        let rotated = |n: u32| PathBuf::from(format!("{}.{}", self.path.display(), n));
+        fs::remove_file(rotated(LOG_KEEP_FILES)).ok();
+        for n in (1..LOG_KEEP_FILES).rev() {
+            fs::rename(rotated(n), rotated(n + 1)).ok();
+        }
+        fs::rename(&self.path, rotated(1)).ok();
+        self.size = 0;
+    }
+}
+
+// A pid is only ours if it is alive and still runs the expected binary (pids get reused)
+pub fn pid_alive(pid: u32, binary: &str) -> bool {
+    #[cfg(unix)]
+    {
+        Command::new("ps")
+            .args(["-p", &pid.to_string(), "-o", "args="])
+            .output()
+            .map(|o| o.status.success() && String::from_utf8_lossy(&o.stdout).contains(binary))
+            .unwrap_or(false)
+    }
+
+    #[cfg(windows)]
+    {
+        Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {}", pid), "/NH"])
+            .output()
+            .map(|o| String::from_utf8_lossy(&o.stdout).contains(binary))
+            .unwrap_or(false)
+    }
+}
+
+pub fn terminate(pid: u32, force: bool) {
+    #[cfg(unix)]
+    {
+        let signal = if force { "-KILL" } else { "-TERM" };
+        let _ = Command::new("kill").args([signal, &pid.to_string()]).output();
+    }
+
+    #[cfg(windows)]
+    {
+        let mut args = vec!["/PID".to_string(), pid.to_string()];
+        if force {
+            args.push("/F".to_string());
+        }
+        let _ = Command::new("taskkill").args(args).output();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(base_secs: u64, max_secs: u64) -> ServiceSpec {
+        ServiceSpec {
+            name: "chain".to_string(),
+            binary: PathBuf::from("koinos_chain"),
+            args: Vec::new(),
+            working_dir: PathBuf::new(),
+            policy: RestartPolicy::OnFailure,
+            max_restarts: 5,
+            backoff_base: Duration::from_secs(base_secs),
+            backoff_max: Duration::from_secs(max_secs),
+            startup_grace: Duration::ZERO,
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("koinos-supervisor-{}-{}", name, std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let spec = spec(2, 30);
+        let delays: Vec<u64> = (1..=6).map(|n| backoff_delay(&spec, n).as_secs()).collect();
+        assert_eq!(delays, vec![2, 4, 8, 16, 30, 30]);
+        // Far past the cap nothing overflows
+        assert_eq!(backoff_delay(&spec, 200).as_secs(), 30);
+    }
+
+    #[test]
+    fn policy_decides_on_restart() {
+        assert!(RestartPolicy::Always.restarts(true));
+        assert!(RestartPolicy::Always.restarts(false));
+        assert!(RestartPolicy::OnFailure.restarts(true));
+        assert!(!RestartPolicy::OnFailure.restarts(false));
+        assert!(!RestartPolicy::Never.restarts(true));
+        assert!(!RestartPolicy::Never.restarts(false));
+    }
+
+    #[test]
+    fn logs_rotate_and_keep_a_fixed_number_of_generations() {
+        let dir = temp_dir("rotate");
+        let path = dir.join("chain.log");
+        let mut log = RotatingLog { path: path.clone(), file: None, size: 0, max_bytes: 20 };
+        let rotated = |n: u32| PathBuf::from(format!("{}.{}", path.display(), n));
+
+        // Each line is 10 bytes with its newline, so every other line rotates
+        for n in 0..(LOG_KEEP_FILES + 2) * 2 {
+            log.write_line(&format!("line {:04}", n));
+        }
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "line 0012\nline 0013\n");
+        assert_eq!(fs::read_to_string(rotated(1)).unwrap(), "line 0010\nline 0011\n");
+        assert_eq!(fs::read_to_string(rotated(LOG_KEEP_FILES)).unwrap(), "line 0002\nline 0003\n");
+        assert!(!rotated(LOG_KEEP_FILES + 1).exists());
+
+        // A reopened log picks up the size already on disk
+        let reopened = RotatingLog::new(path.clone());
+        assert_eq!(reopened.size, 20);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn start_replaces_services_that_gave_up() {
+        let dir = temp_dir("restart");
+        let supervisor = Supervisor::new(dir.join("logs"), dir.join("run"));
+        let shell = |script: &str| ServiceSpec {
+            binary: PathBuf::from("/bin/sh"),
+            args: vec!["-c".to_string(), script.to_string()],
+            working_dir: dir.clone(),
+            max_restarts: 0,
+            startup_grace: Duration::from_millis(100),
+            ..spec(0, 0)
+        };
+
+        supervisor.start(vec![shell("sleep 0.3; exit 1")]).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        assert_eq!(supervisor.state("chain").unwrap().status, ServiceStatus::Failed);
+
+        supervisor.start(vec![shell("sleep 5")]).await.unwrap();
+        assert!(supervisor.is_running("chain"));
+        assert!(matches!(
+            supervisor.start(vec![shell("sleep 5")]).await,
+            Err(NodeError::NodeRunning(_))
+        ));
+
+        supervisor.stop().await;
+        assert_eq!(supervisor.state("chain").unwrap().status, ServiceStatus::Stopped);
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use crate::koinos_rpc::{HeadInfo, KoinosRpc};
+
+// Samples older than this don't count towards the rate
+const SAMPLE_WINDOW: Duration = Duration::from_secs(300);
+// Samples must span at least this long before a rate is reported
+const MIN_RATE_SPAN: Duration = Duration::from_secs(15);
+// A head block younger than this counts as in sync
+pub const SYNCED_LAG_SECS: u64 = 60;
+// Koinos targets one block every 3 seconds
+const DEFAULT_BLOCK_INTERVAL_SECS: f64 = 3.0;
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    at: Instant,
+    height: u64,
+    head_time_ms: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct SyncEstimate {
+    pub progress: f32,
+    pub synced: bool,
+    pub head_lag_seconds: u64,
+    pub blocks_per_sec: Option<f64>,
+    pub eta_seconds: Option<u64>,
+    /// Estimated network head height
+    pub target_block: u64,
+}
+
+#[derive(Default)]
+struct Inner {
+    samples: VecDeque<Sample>,
+    genesis_time_ms: Option<u64>,
+}
+
+/// Derives sync progress and ETA from the local head block alone: how far its
+/// timestamp lags behind the clock, and how fast blocks are being applied.
+#[derive(Clone, Default)]
+pub struct SyncTracker {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl SyncTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn genesis_time(&self) -> Option<u64> {
+        self.inner.lock().unwrap().genesis_time_ms
+    }
+
+    /// Timestamp of block 1, which progress is measured from
+    pub fn set_genesis_time(&self, ms: u64) {
+        self.inner.lock().unwrap().genesis_time_ms = Some(ms);
+    }
+
+    /// Forget the samples, e.g. after the node was stopped
+    pub fn reset(&self) {
+        self.inner.lock().unwrap().samples.clear();
+    }
+
+    /// Add a sample of the local head and estimate where the sync stands
+    pub fn record(&self, head: &HeadInfo) -> SyncEstimate {
+        let now_ms = chrono::Utc::now().timestamp_millis().max(0) as u64;
+        self.record_at(head, Instant::now(), now_ms)
+    }
+
+    // `at` orders the samples, `now_ms` is the wall clock the head time lags behind
+    fn record_at(&self, head: &HeadInfo, at: Instant, now_ms: u64) -> SyncEstimate {
+        let height = head.head_topology.height;
+        let mut inner = self.inner.lock().unwrap();
+
+        // A lower height means the node was reset or replaced; old samples no longer apply
+        if inner.samples.back().is_some_and(|s| s.height > height) {
+            inner.samples.clear();
+        }
+        inner.samples.push_back(Sample { at, height, head_time_ms: head.head_block_time });
+        while inner.samples.front().is_some_and(|s| at.duration_since(s.at) > SAMPLE_WINDOW) {
+            inner.samples.pop_front();
+        }
+
+        let lag_ms = now_ms.saturating_sub(head.head_block_time);
+        let head_lag_seconds = lag_ms / 1000;
+        let synced = head_lag_seconds < SYNCED_LAG_SECS;
+
+        let first = *inner.samples.front().unwrap();
+        let last = *inner.samples.back().unwrap();
+        let span = last.at.duration_since(first.at);
+        let applied = last.height - first.height;
+
+        let blocks_per_sec = (span >= MIN_RATE_SPAN).then(|| applied as f64 / span.as_secs_f64());
+        let block_interval = if applied > 0 && last.head_time_ms > first.head_time_ms {
+            (last.head_time_ms - first.head_time_ms) as f64 / 1000.0 / applied as f64
+        } else {
+            DEFAULT_BLOCK_INTERVAL_SECS
+        };
+        let remaining_blocks = if synced { 0.0 } else { lag_ms as f64 / 1000.0 / block_interval };
+
+        // New blocks keep arriving while we catch up
+        let eta_seconds = if synced {
+            Some(0)
+        } else {
+            blocks_per_sec
+                .map(|rate| rate - 1.0 / block_interval)
+                .filter(|net| *net > 0.0)
+                .map(|net| (remaining_blocks / net) as u64)
+        };
+
+        let progress = if synced {
+            100.0
+        } else if let Some(genesis) = inner.genesis_time_ms.filter(|g| now_ms > *g) {
+            let done = head.head_block_time.saturating_sub(genesis) as f64;
+            (done / (now_ms - genesis) as f64 * 100.0).min(99.9) as f32
+        } else if height > 0 {
+            (height as f64 / (height as f64 + remaining_blocks) * 100.0).min(99.9) as f32
+        } else {
+            0.0
+        };
+
+        SyncEstimate {
+            progress,
+            synced,
+            head_lag_seconds,
+            blocks_per_sec,
+            eta_seconds,
+            target_block: height + remaining_blocks as u64,
+        }
+    }
+}
+
+/// Timestamp of block 1 as stored by the node
+pub async fn fetch_genesis_time(rpc: &KoinosRpc, head: &HeadInfo) -> Option<u64> {
+    let blocks = rpc.blocks_by_height(&head.head_topology.id, 1, 1, true).await.ok()?;
+    let timestamp = blocks.first()?.block.as_ref()?.get("header")?.get("timestamp")?;
+    match timestamp {
+        serde_json::Value::String(text) => text.parse().ok(),
+        value => value.as_u64(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::koinos_rpc::BlockTopology;
+
+    const NOW_MS: u64 = 1_700_000_000_000;
+
+    fn head(height: u64, head_block_time: u64) -> HeadInfo {
+        HeadInfo {
+            head_topology: BlockTopology { height, ..Default::default() },
+            last_irreversible_block: 0,
+            head_block_time,
+            head_state_merkle_root: String::new(),
+        }
+    }
+
+    #[test]
+    fn recent_head_is_synced() {
+        let tracker = SyncTracker::new();
+        let estimate = tracker.record_at(&head(5000, NOW_MS - 10_000), Instant::now(), NOW_MS);
+        assert!(estimate.synced);
+        assert_eq!(estimate.progress, 100.0);
+        assert_eq!(estimate.eta_seconds, Some(0));
+        assert_eq!(estimate.target_block, 5000);
+        assert_eq!(estimate.head_lag_seconds, 10);
+    }
+
+    #[test]
+    fn catching_up_reports_rate_and_eta() {
+        let tracker = SyncTracker::new();
+        let start = Instant::now();
+        // 300 blocks in 30 seconds, covering 900 seconds of chain time
+        tracker.record_at(&head(1000, NOW_MS - 4_500_000), start, NOW_MS);
+        let estimate = tracker.record_at(&head(1300, NOW_MS - 3_600_000), start + Duration::from_secs(30), NOW_MS + 30_000);
+
+        assert!(!estimate.synced);
+        assert_eq!(estimate.blocks_per_sec, Some(10.0));
+        // 3630 seconds behind at 3 seconds a block, gaining 10 - 1/3 blocks a second
+        assert_eq!(estimate.target_block, 1300 + 1210);
+        assert_eq!(estimate.eta_seconds, Some(125));
+        assert!(estimate.progress > 0.0 && estimate.progress < 100.0);
+    }
+
+    #[test]
+    fn falling_behind_has_no_eta() {
+        let tracker = SyncTracker::new();
+        let start = Instant::now();
+        // 6 blocks in 60 seconds while the network makes 20
+        tracker.record_at(&head(1000, NOW_MS - 3_600_000), start, NOW_MS);
+        let estimate = tracker.record_at(&head(1006, NOW_MS - 3_582_000), start + Duration::from_secs(60), NOW_MS + 60_000);
+
+        assert_eq!(estimate.blocks_per_sec, Some(0.1));
+        assert_eq!(estimate.eta_seconds, None);
+    }
+
+    #[test]
+    fn short_spans_report_no_rate() {
+        let tracker = SyncTracker::new();
+        let start = Instant::now();
+        tracker.record_at(&head(1000, NOW_MS - 3_600_000), start, NOW_MS);
+        let estimate = tracker.record_at(&head(1100, NOW_MS - 3_300_000), start + MIN_RATE_SPAN - Duration::from_secs(1), NOW_MS);
+
+        assert_eq!(estimate.blocks_per_sec, None);
+        assert_eq!(estimate.eta_seconds, None);
+    }
+
+    #[test]
+    fn lower_height_starts_over() {
+        let tracker = SyncTracker::new();
+        let start = Instant::now();
+        tracker.record_at(&head(1000, NOW_MS - 3_600_000), start, NOW_MS);
+        let estimate = tracker.record_at(&head(1300, NOW_MS - 2_700_000), start + Duration::from_secs(30), NOW_MS);
+        assert!(estimate.blocks_per_sec.is_some());
+
+        // Without the reset this would be a negative rate
+        let estimate = tracker.record_at(&head(10, NOW_MS - 7_200_000), start + Duration::from_secs(60), NOW_MS);
+        assert_eq!(estimate.blocks_per_sec, None);
+        let estimate = tracker.record_at(&head(310, NOW_MS - 6_300_000), start + Duration::from_secs(90), NOW_MS);
+        assert_eq!(estimate.blocks_per_sec, Some(10.0));
+    }
+
+    #[test]
+    fn progress_counts_from_genesis() {
+        let tracker = SyncTracker::new();
+        tracker.set_genesis_time(NOW_MS - 1_000_000);
+        let estimate = tracker.record_at(&head(100, NOW_MS - 750_000), Instant::now(), NOW_MS);
+        assert!((estimate.progress - 25.0).abs() < 0.01);
+    }
+}
@@ -0,0 +1,425 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use chrono::{TimeZone, Utc};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use crate::node_manager::{NodeStatus, ResourceUsage};
+use crate::logger::log_warn;
+use crate::error::NodeError;
+
+const MINUTE_MS: i64 = 60_000;
+const HOUR_MS: i64 = 60 * MINUTE_MS;
+const DAY_MS: i64 = 24 * HOUR_MS;
+
+// Records buffered before a tier is appended to disk. Each append is its own gzip
+// member, so batching keeps the files compact; a crash loses at most one batch, and
+// the member it cut short is trimmed off before the file is appended to again.
+const RAW_BATCH: usize = 12;
+const MINUTE_BATCH: usize = 10;
+const HOUR_BATCH: usize = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Resolution {
+    /// Every sample of the status loop
+    Raw,
+    Minute,
+    Hour,
+}
+
+impl Resolution {
+    fn dir_name(&self) -> &'static str {
+        match self {
+            Resolution::Raw => "raw",
+            Resolution::Minute => "minute",
+            Resolution::Hour => "hour",
+        }
+    }
+
+    fn retention_ms(&self) -> i64 {
+        match self {
+            Resolution::Raw => 2 * DAY_MS,
+            Resolution::Minute => 30 * DAY_MS,
+            Resolution::Hour => 365 * DAY_MS,
+        }
+    }
+
+    // Raw and minute data go in daily files, hourly data in monthly ones. Keys sort
+    // chronologically, which retention and queries rely on.
+    fn file_key(&self, at_ms: i64) -> String {
+        let at = Utc.timestamp_millis_opt(at_ms).single().unwrap_or_default();
+        match self {
+            Resolution::Raw | Resolution::Minute => at.format("%Y-%m-%d").to_string(),
+            Resolution::Hour => at.format("%Y-%m").to_string(),
+        }
+    }
+
+    /// The finest resolution whose retention still covers `from`, capped so a long
+    /// range doesn't return tens of thousands of points
+    fn auto(from: i64, to: i64) -> Self {
+        let now = Utc::now().timestamp_millis();
+        let span = to - from;
+        if span <= 2 * HOUR_MS && now - from <= Resolution::Raw.retention_ms() {
+            Resolution::Raw
+        } else if span <= 7 * DAY_MS && now - from <= Resolution::Minute.retention_ms() {
+            Resolution::Minute
+        } else {
+            Resolution::Hour
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Record {
+    /// Unix milliseconds; start of the bucket for downsampled records
+    at: i64,
+    values: BTreeMap<String, f64>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct HistoryPoint {
+    pub at: i64,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct History {
+    pub metric: String,
+    pub resolution: Resolution,
+    pub points: Vec<HistoryPoint>,
+}
+
+// Running averages for one minute or hour
+struct Bucket {
+    start: i64,
+    sums: BTreeMap<String, (f64, u32)>,
+}
+
+impl Bucket {
+    fn new(start: i64) -> Self {
+        Self { start, sums: BTreeMap::new() }
+    }
+
+    fn add(&mut self, values: &BTreeMap<String, f64>) {
+        for (metric, value) in values {
+            let entry = self.sums.entry(metric.clone()).or_insert((0.0, 0));
+            entry.0 += value;
+            entry.1 += 1;
+        }
+    }
+
+    fn average(&self) -> Record {
+        Record {
+            at: self.start,
+            values: self
+                .sums
+                .iter()
+                .map(|(metric, (sum, count))| (metric.clone(), sum / *count as f64))
+                .collect(),
+        }
+    }
+}
+
+struct Tier {
+    resolution: Resolution,
+    width_ms: i64,
+    batch: usize,
+    bucket: Option<Bucket>,
+    pending: Vec<Record>,
+}
+
+impl Tier {
+    fn new(resolution: Resolution, width_ms: i64, batch: usize) -> Self {
+        Self { resolution, width_ms, batch, bucket: None, pending: Vec::new() }
+    }
+
+    // Feed a sample; a finished bucket becomes a pending record
+    fn add(&mut self, record: &Record) {
+        if self.width_ms == 0 {
+            self.pending.push(record.clone());
+            return;
+        }
+        let start = record.at - record.at.rem_euclid(self.width_ms);
+        if self.bucket.as_ref().is_some_and(|b| b.start != start) {
+            let done = self.bucket.take().unwrap();
+            self.pending.push(done.average());
+        }
+        self.bucket.get_or_insert_with(|| Bucket::new(start)).add(&record.values);
+    }
+}
+
+struct Inner {
+    dir: PathBuf,
+    tiers: Vec<Tier>,
+    last_prune: Option<String>,
+    /// Files checked for a truncated member since the app started
+    repaired: HashSet<PathBuf>,
+}
+
+/// Status and resource samples over time, kept as gzip-compressed JSON lines under
+/// `~/.koinos/telemetry/<resolution>/`. Samples are downsampled to minute and hour
+/// averages, and old files are removed once they fall out of retention.
+#[derive(Clone)]
+pub struct TelemetryStore {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl TelemetryStore {
+    pub fn new() -> Self {
+        let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                dir: home.join(".koinos").join("telemetry"),
+                tiers: vec![
+                    Tier::new(Resolution::Raw, 0, RAW_BATCH),
+                    Tier::new(Resolution::Minute, MINUTE_MS, MINUTE_BATCH),
+                    Tier::new(Resolution::Hour, HOUR_MS, HOUR_BATCH),
+                ],
+                last_prune: None,
+                repaired: HashSet::new(),
+            })),
+        }
+    }
+
+    /// Record one tick of the status loop
+    pub fn record(&self, status: &NodeStatus, usage: Option<&ResourceUsage>) {
+        let mut values = BTreeMap::new();
+        values.insert("sync_progress".to_string(), status.sync_progress as f64);
+        values.insert("current_block".to_string(), status.current_block as f64);
+        values.insert("peers".to_string(), status.peers_count as f64);
+        if let Some(rate) = status.blocks_per_sec {
+            values.insert("blocks_per_sec".to_string(), rate);
+        }
+        if let Some(lag) = status.head_lag_seconds {
+            values.insert("head_lag_seconds".to_string(), lag as f64);
+        }
+        if let Some(usage) = usage {
+            values.insert("cpu_percent".to_string(), usage.cpu_percent as f64);
+            values.insert("memory_mb".to_string(), usage.memory_mb as f64);
+            values.insert("disk_used_gb".to_string(), usage.disk_used_gb as f64);
+            values.insert("net_rx_bytes".to_string(), usage.net_rx_bytes as f64);
+            values.insert("net_tx_bytes".to_string(), usage.net_tx_bytes as f64);
+            values.insert("block_read_bytes".to_string(), usage.block_read_bytes as f64);
+            values.insert("block_write_bytes".to_string(), usage.block_write_bytes as f64);
+        }
+
+        let record = Record { at: Utc::now().timestamp_millis(), values };
+        let mut guard = self.inner.lock().unwrap();
+        let inner = &mut *guard;
+        let dir = inner.dir.clone();
+        for tier in inner.tiers.iter_mut() {
+            tier.add(&record);
+            if tier.pending.len() >= tier.batch {
+                match append(&dir, tier.resolution, &tier.pending, &mut inner.repaired) {
+                    Ok(()) => tier.pending.clear(),
+                    Err(e) => {
+                        log_warn("Failed to write telemetry", Some(&e.to_string()));
+                        // Don't let a persistently failing disk grow the buffer forever
+                        if tier.pending.len() > tier.batch * 10 {
+                            tier.pending.clear();
+                        }
+                    }
+                }
+            }
+        }
+
+        let today = Resolution::Raw.file_key(record.at);
+        if inner.last_prune.as_deref() != Some(today.as_str()) {
+            prune(&dir, record.at);
+            inner.last_prune = Some(today);
+        }
+    }
+
+    /// Values of `metric` between `from` and `to` (unix milliseconds). Without a
+    /// resolution, one is picked from the range.
+    pub fn history(
+        &self,
+        metric: &str,
+        from: i64,
+        to: i64,
+        resolution: Option<Resolution>,
+    ) -> Result<History, NodeError> {
+        if to < from {
+            return Err(NodeError::InvalidInput("History range ends before it starts".to_string()));
+        }
+        let resolution = resolution.unwrap_or_else(|| Resolution::auto(from, to));
+
+        // Unflushed records are read under the lock, files after releasing it
+        let (dir, pending) = {
+            let inner = self.inner.lock().unwrap();
+            let tier = inner.tiers.iter().find(|t| t.resolution == resolution).unwrap();
+            (inner.dir.clone(), tier.pending.clone())
+        };
+
+        let tier_dir = dir.join(resolution.dir_name());
+        let (first, last) = (resolution.file_key(from), resolution.file_key(to));
+        let mut files: Vec<PathBuf> = match fs::read_dir(&tier_dir) {
+            Ok(entries) => entries
+                .flatten()
+                .map(|e| e.path())
+                .filter(|path| {
+                    file_key_of(path).is_some_and(|key| key >= first.as_str() && key <= last.as_str())
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        files.sort();
+
+        let mut points = Vec::new();
+        let mut collect = |record: Record| {
+            if record.at >= from && record.at <= to {
+                if let Some(value) = record.values.get(metric) {
+                    points.push(HistoryPoint { at: record.at, value: *value });
+                }
+            }
+        };
+        for path in files {
+            for record in read_records(&path)? {
+                collect(record);
+            }
+        }
+        for record in pending {
+            collect(record);
+        }
+
+        Ok(History { metric: metric.to_string(), resolution, points })
+    }
+}
+
+fn file_key_of(path: &Path) -> Option<&str> {
+    path.file_name()?.to_str()?.strip_suffix(".jsonl.gz")
+}
+
+fn append(
+    dir: &Path,
+    resolution: Resolution,
+    records: &[Record],
+    repaired: &mut HashSet<PathBuf>,
+) -> Result<(), NodeError> {
+    let tier_dir = dir.join(resolution.dir_name());
+    fs::create_dir_all(&tier_dir)
+        .map_err(|e| NodeError::from(e).context("Failed to create telemetry directory"))?;
+
+    // A batch may straddle midnight, so each record goes to the file of its own day
+    let mut by_file: BTreeMap<String, Vec<&Record>> = BTreeMap::new();
+    for record in records {
+        by_file.entry(resolution.file_key(record.at)).or_default().push(record);
+    }
+    for (key, records) in by_file {
+        let path = tier_dir.join(format!("{}.jsonl.gz", key));
+        if !repaired.contains(&path) {
+            repair(&path)?;
+            repaired.insert(path.clone());
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let mut encoder = GzEncoder::new(file, Compression::default());
+        for record in records {
+            serde_json::to_writer(&mut encoder, record)?;
+            encoder.write_all(b"\n")?;
+        }
+        encoder.finish()?.sync_data()?;
+    }
+    Ok(())
+}
+
+// Cut a member left incomplete by a crash off the end of the file. Appending after it
+// would hide every later member, since decoding stops at the broken one.
+fn repair(path: &Path) -> Result<(), NodeError> {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut rest: &[u8] = &data;
+    while !rest.is_empty() {
+        let mut decoder = flate2::bufread::GzDecoder::new(rest);
+        if std::io::copy(&mut decoder, &mut std::io::sink()).is_err() {
+            break;
+        }
+        rest = decoder.into_inner();
+    }
+    if !rest.is_empty() {
+        let good = (data.len() - rest.len()) as u64;
+        log_warn("Trimming incomplete telemetry data", Some(&format!("{} ({} bytes)", path.display(), rest.len())));
+        OpenOptions::new().write(true).open(path)?.set_len(good)?;
+    }
+    Ok(())
+}
+
+// A member cut short by a crash ends the file early; the lines before it are kept
+fn read_records(path: &Path) -> Result<Vec<Record>, NodeError> {
+    let file = fs::File::open(path)?;
+    let reader = BufReader::new(flate2::read::MultiGzDecoder::new(BufReader::new(file)));
+    Ok(reader
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect())
+}
+
+fn prune(dir: &Path, now_ms: i64) {
+    for resolution in [Resolution::Raw, Resolution::Minute, Resolution::Hour] {
+        let cutoff = resolution.file_key(now_ms - resolution.retention_ms());
+        let Ok(entries) = fs::read_dir(dir.join(resolution.dir_name())) else {
+            continue;
+        };
+        for path in entries.flatten().map(|e| e.path()) {
+            if file_key_of(&path).is_some_and(|key| key < cutoff.as_str()) {
+                fs::remove_file(&path).ok();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("koinos-telemetry-{}-{}", name, std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        dir
+    }
+
+    fn record(at: i64, value: f64) -> Record {
+        Record { at, values: BTreeMap::from([("peers".to_string(), value)]) }
+    }
+
+    #[test]
+    fn batch_across_midnight_is_split_by_day() {
+        let dir = temp_dir("midnight");
+        let midnight = DAY_MS * 19_000;
+        let records = [record(midnight - 1_000, 1.0), record(midnight + 1_000, 2.0)];
+        append(&dir, Resolution::Raw, &records, &mut HashSet::new()).unwrap();
+
+        let tier = dir.join("raw");
+        let before = read_records(&tier.join(format!("{}.jsonl.gz", Resolution::Raw.file_key(midnight - 1_000)))).unwrap();
+        let after = read_records(&tier.join(format!("{}.jsonl.gz", Resolution::Raw.file_key(midnight)))).unwrap();
+        assert_eq!(before.iter().map(|r| r.at).collect::<Vec<_>>(), [midnight - 1_000]);
+        assert_eq!(after.iter().map(|r| r.at).collect::<Vec<_>>(), [midnight + 1_000]);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn truncated_member_is_trimmed_before_appending() {
+        let dir = temp_dir("truncated");
+        let path = dir.join("raw").join(format!("{}.jsonl.gz", Resolution::Raw.file_key(0)));
+        append(&dir, Resolution::Raw, &[record(0, 1.0)], &mut HashSet::new()).unwrap();
+        let good = fs::metadata(&path).unwrap().len();
+        append(&dir, Resolution::Raw, &[record(1, 2.0)], &mut HashSet::new()).unwrap();
+
+        // A crash halfway through writing the second member
+        let len = fs::metadata(&path).unwrap().len();
+        OpenOptions::new().write(true).open(&path).unwrap().set_len(len - 5).unwrap();
+
+        append(&dir, Resolution::Raw, &[record(2, 3.0)], &mut HashSet::new()).unwrap();
+        let values: Vec<f64> = read_records(&path).unwrap().iter().map(|r| r.values["peers"]).collect();
+        assert_eq!(values, [1.0, 3.0]);
+        assert!(fs::metadata(&path).unwrap().len() > good);
+        fs::remove_dir_all(&dir).ok();
+    }
+}
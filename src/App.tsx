@@ -7,6 +7,7 @@ import InitializationProgress from "./components/InitializationProgress";
 import DebugConsole from "./components/DebugConsole";
 import StatusModal from "./components/StatusModal";
 import { NodeStatus } from "./components/StatusIndicator";
+import { BackendError, errorCode, errorMessage } from "./errors";
 
 interface BackendNodeStatus {
   status: string;
@@ -15,6 +16,30 @@ interface BackendNodeStatus {
   target_block: number;
   peers_count: number;
   error_message?: string;
+  blocks_per_sec: number | null;
+  head_lag_seconds: number | null;
+  eta_seconds: number | null;
+}
+
+interface NodeStateChange {
+  from: NodeStatus;
+  to: NodeStatus;
+  reason: string;
+  at: string;
+}
+
+type ConsistencyAlert =
+  | { kind: 'fork'; endpoint: string; height: number; local_block_id: string; reference_block_id: string }
+  | { kind: 'stalled'; height: number; stalled_seconds: number };
+
+interface ServiceUsage {
+  service: string;
+  cpu_percent: number;
+  memory_mb: number;
+  net_rx_bytes: number | null;
+  net_tx_bytes: number | null;
+  block_read_bytes: number | null;
+  block_write_bytes: number | null;
 }
 
 interface ResourceUsage {
@@ -23,8 +48,55 @@ interface ResourceUsage {
   memory_total_mb: number;
   disk_used_gb: number;
   disk_total_gb: number;
+  net_rx_bytes: number;
+  net_tx_bytes: number;
+  block_read_bytes: number;
+  block_write_bytes: number;
+  services: ServiceUsage[];
 }
 
+interface DownloadProgress {
+  percent: number;
+  downloaded_bytes: number;
+  total_bytes: number;
+  bytes_per_sec: number;
+  eta_seconds: number | null;
+}
+
+interface JobInfo {
+  id: number;
+  kind: string;
+  state: 'running' | 'completed' | 'failed' | 'cancelled';
+  progress: number | null;
+  message: string | null;
+  error: BackendError | null;
+}
+
+// Long operations run as backend jobs; start one and wait until it finishes
+const runJob = async (command: string): Promise<void> => {
+  const jobId = await invoke<number>(command);
+  return new Promise((resolve, reject) => {
+    let unlisten: (() => void) | undefined;
+    const settle = (job: JobInfo) => {
+      if (job.id !== jobId || job.state === 'running') return;
+      unlisten?.();
+      if (job.state === 'completed') {
+        resolve();
+      } else {
+        reject(job.error ?? `${command} was ${job.state}`);
+      }
+    };
+    listen<JobInfo>('job_updated', (event) => settle(event.payload))
+      .then((fn) => {
+        unlisten = fn;
+        // The job may have finished before the listener was registered
+        return invoke<JobInfo>('get_job', { id: jobId });
+      })
+      .then(settle)
+      .catch(reject);
+  });
+};
+
 const APP_VERSION = '0.4.0';
 
 function App() {
@@ -34,6 +106,8 @@ function App() {
   const [currentBlock, setCurrentBlock] = useState(0);
   const [targetBlock, setTargetBlock] = useState(0);
   const [peersCount, setPeersCount] = useState(0);
+  const [blocksPerSec, setBlocksPerSec] = useState<number | null>(null);
+  const [syncEtaSeconds, setSyncEtaSeconds] = useState<number | null>(null);
   const [resourceUsage, setResourceUsage] = useState<ResourceUsage | null>(null);
   const [isInitializing, setIsInitializing] = useState(false);
   const [error, setError] = useState<string | null>(null);
@@ -78,31 +152,49 @@ function App() {
       setCurrentBlock(status.current_block);
       setTargetBlock(status.target_block);
       setPeersCount(status.peers_count);
+      setBlocksPerSec(status.blocks_per_sec);
+      setSyncEtaSeconds(status.eta_seconds);
       
       if (status.error_message) {
         setError(status.error_message);
       }
     });
 
+    // State transitions arrive as they happen, ahead of the next status poll
+    const unsubscribeState = listen<NodeStateChange>('node_state_changed', (event) => {
+      setNodeStatus(event.payload.to);
+      if (event.payload.to === 'error') {
+        setError(event.payload.reason);
+      }
+    });
+
+    const unsubscribeAlerts = listen<ConsistencyAlert>('consistency_alert', (event) => {
+      const alert = event.payload;
+      if (alert.kind === 'fork') {
+        setError(`Your node is on a different fork than ${alert.endpoint} at block ${alert.height}. Resync from a snapshot.`);
+      } else {
+        setError(`Your node has been stuck at block ${alert.height} for ${Math.round(alert.stalled_seconds / 60)} minutes. Try restarting it.`);
+      }
+    });
+
     // Listen for download progress
-    const unsubscribeDownload = listen<number>('download_progress', (event) => {
-      const progress = event.payload;
-      setDownloadProgress(progress);
+    const unsubscribeDownload = listen<DownloadProgress>('download_progress', (event) => {
+      const { percent, bytes_per_sec, eta_seconds } = event.payload;
+      setDownloadProgress(percent);
       
-      // Calculate download speed (mock for now)
-      const mbPerSec = 5 + Math.random() * 10;
-      setDownloadSpeed(`${mbPerSec.toFixed(1)} MB/s`);
-      
-      // Calculate estimated time
-      const remainingGB = 30 * (1 - progress / 100);
-      const remainingSeconds = (remainingGB * 1024) / mbPerSec;
-      const minutes = Math.floor(remainingSeconds / 60);
-      const hours = Math.floor(minutes / 60);
+      if (bytes_per_sec > 0) {
+        setDownloadSpeed(`${(bytes_per_sec / 1_000_000).toFixed(1)} MB/s`);
+      }
       
-      if (hours > 0) {
-        setEstimatedTime(`${hours}h ${minutes % 60}m`);
-      } else {
-        setEstimatedTime(`${minutes}m`);
+      if (eta_seconds !== null) {
+        const minutes = Math.floor(eta_seconds / 60);
+        const hours = Math.floor(minutes / 60);
+        
+        if (hours > 0) {
+          setEstimatedTime(`${hours}h ${minutes % 60}m`);
+        } else {
+          setEstimatedTime(`${minutes}m`);
+        }
       }
     });
 
@@ -129,6 +221,8 @@ function App() {
 
     return () => {
       unsubscribeStatus.then(fn => fn());
+      unsubscribeState.then(fn => fn());
+      unsubscribeAlerts.then(fn => fn());
       unsubscribeDownload.then(fn => fn());
       clearInterval(resourceInterval);
     };
@@ -174,11 +268,11 @@ function App() {
             return;
           }
         } catch (installErr) {
-          const errorMsg = installErr as string;
-          console.error('Auto-install failed:', errorMsg);
+          const errorMsg = errorMessage(installErr);
+          console.error('Auto-install failed:', installErr);
           
           // If it's a guided error message, show it as-is
-          if (errorMsg.includes('Please') || errorMsg.includes('Terminal')) {
+          if (errorCode(installErr) === 'docker_missing' || errorMsg.includes('Please') || errorMsg.includes('Terminal')) {
             setError(errorMsg);
           } else {
             setError(`Failed to install requirements: ${errorMsg}`);
@@ -191,7 +285,7 @@ function App() {
 
       // Step 2: Setup Docker
       setInitStep('docker');
-      await invoke('setup_node');
+      await runJob('setup_node');
       setInitProgress(40);
       
       // Step 3: Download snapshot if needed
@@ -199,7 +293,7 @@ function App() {
       if (!hasSnapshot) {
         setInitStep('download');
         setInitProgress(50);
-        await invoke('download_snapshot');
+        await runJob('download_snapshot');
       }
       setInitProgress(90);
       
@@ -215,14 +309,14 @@ function App() {
       // Automatically start the node after successful setup
       console.log('Setup complete, starting node...');
       try {
-        await invoke('start_node');
+        await runJob('start_node');
         console.log('Node started successfully');
       } catch (startErr) {
         console.error('Failed to auto-start node:', startErr);
         // Don't show error here, let the Dashboard handle it
       }
     } catch (err) {
-      setError(err as string);
+      setError(errorMessage(err));
       console.error('Initialization error:', err);
     } finally {
       setIsInitializing(false);
@@ -232,14 +326,13 @@ function App() {
   const handleStartNode = async () => {
     try {
       setError(null);
-      await invoke('start_node');
+      await runJob('start_node');
     } catch (err) {
-      const errorMsg = err as string;
-      setError(errorMsg);
-      console.error('Failed to start node:', errorMsg);
+      setError(errorMessage(err));
+      console.error('Failed to start node:', err);
       
       // If not initialized, prompt to go through setup
-      if (errorMsg.includes('not initialized') || errorMsg.includes('Please run setup')) {
+      if (errorCode(err) === 'not_initialized') {
         const shouldReset = window.confirm('Node is not initialized. Would you like to run the setup process?');
         if (shouldReset) {
           localStorage.removeItem('koinos-node-launched');
@@ -254,7 +347,7 @@ function App() {
       setError(null);
       await invoke('stop_node');
     } catch (err) {
-      setError(err as string);
+      setError(errorMessage(err));
       console.error('Failed to stop node:', err);
     }
   };
@@ -262,9 +355,9 @@ function App() {
   const handleRestartNode = async () => {
     try {
       setError(null);
-      await invoke('restart_node');
+      await runJob('restart_node');
     } catch (err) {
-      setError(err as string);
+      setError(errorMessage(err));
       console.error('Failed to restart node:', err);
     }
   };
@@ -367,6 +460,8 @@ function App() {
         currentBlock={currentBlock}
         targetBlock={targetBlock}
         peersCount={peersCount}
+        blocksPerSec={blocksPerSec}
+        syncEtaSeconds={syncEtaSeconds}
         onStartNode={handleStartNode}
         onStopNode={handleStopNode}
         onRestartNode={handleRestartNode}
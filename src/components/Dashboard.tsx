@@ -11,6 +11,8 @@ interface DashboardProps {
   currentBlock: number;
   targetBlock: number;
   peersCount: number;
+  blocksPerSec?: number | null;
+  syncEtaSeconds?: number | null;
   onStartNode: () => void;
   onStopNode: () => void;
   onRestartNode: () => void;
@@ -30,6 +32,8 @@ const Dashboard: React.FC<DashboardProps> = ({
   currentBlock,
   targetBlock,
   peersCount,
+  blocksPerSec,
+  syncEtaSeconds,
   onStartNode,
   onStopNode,
   onRestartNode,
@@ -71,6 +75,8 @@ const Dashboard: React.FC<DashboardProps> = ({
               currentBlock={currentBlock}
               targetBlock={targetBlock}
               peersCount={peersCount}
+              blocksPerSec={blocksPerSec}
+              etaSeconds={syncEtaSeconds}
             />
             
             <NodeControls
@@ -93,7 +99,7 @@ const Dashboard: React.FC<DashboardProps> = ({
                 <div>
                   <div className="flex items-center space-x-2 mb-2">
                     <Cpu className="w-4 h-4 text-koinos-purple-400" />
-                    <span className="text-sm">CPU Usage</span>
+                    <span className="text-sm">Node CPU</span>
                   </div>
                   <ProgressBar
                     progress={resourceUsage?.cpu_percent || 0}
@@ -106,7 +112,7 @@ const Dashboard: React.FC<DashboardProps> = ({
                 <div>
                   <div className="flex items-center space-x-2 mb-2">
                     <HardDrive className="w-4 h-4 text-koinos-purple-400" />
-                    <span className="text-sm">Memory Usage</span>
+                    <span className="text-sm">Node Memory</span>
                   </div>
                   <ProgressBar
                     progress={resourceUsage ? (resourceUsage.memory_mb / resourceUsage.memory_total_mb * 100) : 0}
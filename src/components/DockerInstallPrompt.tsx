@@ -2,6 +2,7 @@ import React, { useState } from 'react';
 import { motion } from 'framer-motion';
 import { AlertCircle, Download, ExternalLink, Loader2, CheckCircle } from 'lucide-react';
 import { invoke } from '@tauri-apps/api/core';
+import { errorMessage } from '../errors';
 
 interface DockerInstallPromptProps {
   onClose: () => void;
@@ -38,7 +39,7 @@ const DockerInstallPrompt: React.FC<DockerInstallPromptProps> = ({ onClose, onRe
       }, 5000);
     } catch (err) {
       setInstallStatus('error');
-      setInstallError(err as string);
+      setInstallError(errorMessage(err));
       setIsInstalling(false);
     }
   };
@@ -37,7 +37,7 @@ const NodeControls: React.FC<NodeControlsProps> = ({
   };
 
   const canStart = status === 'stopped' || status === 'error';
-  const canStop = status === 'running' || status === 'syncing' || status === 'starting';
+  const canStop = status === 'running' || status === 'syncing' || status === 'starting' || status === 'error';
   const canRestart = status === 'running' || status === 'syncing';
 
   return (
@@ -4,7 +4,7 @@ import { CheckCircle2, AlertCircle, XCircle, Loader2, Wifi, WifiOff } from 'luci
 import clsx from 'clsx';
 import ProgressBar from './ProgressBar';
 
-export type NodeStatus = 'stopped' | 'starting' | 'syncing' | 'running' | 'error';
+export type NodeStatus = 'stopped' | 'starting' | 'syncing' | 'running' | 'stopping' | 'error';
 
 interface StatusIndicatorProps {
   status: NodeStatus;
@@ -12,14 +12,26 @@ interface StatusIndicatorProps {
   currentBlock?: number;
   targetBlock?: number;
   peersCount?: number;
+  blocksPerSec?: number | null;
+  etaSeconds?: number | null;
 }
 
+const formatEta = (seconds: number) => {
+  const hours = Math.floor(seconds / 3600);
+  const minutes = Math.floor((seconds % 3600) / 60);
+  if (hours >= 24) return `${Math.floor(hours / 24)}d ${hours % 24}h`;
+  if (hours > 0) return `${hours}h ${minutes}m`;
+  return `${minutes}m`;
+};
+
 const StatusIndicator: React.FC<StatusIndicatorProps> = ({
   status,
   syncProgress = 0,
   currentBlock = 0,
   targetBlock = 0,
   peersCount = 0,
+  blocksPerSec = null,
+  etaSeconds = null,
 }) => {
   const getStatusConfig = () => {
     switch (status) {
@@ -59,6 +71,15 @@ const StatusIndicator: React.FC<StatusIndicatorProps> = ({
           description: 'Fully synchronized',
           pulseColor: 'bg-green-500',
         };
+      case 'stopping':
+        return {
+          icon: <Loader2 className="w-8 h-8 animate-spin" />,
+          color: 'text-yellow-500',
+          bgColor: 'bg-yellow-500/20',
+          label: 'Stopping Node',
+          description: 'Shutting down services...',
+          pulseColor: 'bg-yellow-500',
+        };
       case 'error':
         return {
           icon: <XCircle className="w-8 h-8" />,
@@ -126,7 +147,12 @@ const StatusIndicator: React.FC<StatusIndicatorProps> = ({
               color="blue"
               animate
               showPercentage={false}
-              details={`Syncing at ~${Math.round(Math.random() * 50 + 50)} blocks/sec`}
+              details={
+                blocksPerSec !== null
+                  ? `Syncing at ${blocksPerSec.toFixed(1)} blocks/sec` +
+                    (etaSeconds !== null ? `, about ${formatEta(etaSeconds)} left` : '')
+                  : 'Measuring sync speed...'
+              }
             />
           </motion.div>
         )}
@@ -9,7 +9,8 @@ interface StatusData {
     current_block: number;
     target_block: number;
     percentage: number;
-    time_remaining: string;
+    synced: boolean;
+    eta_seconds: number | null;
   };
   network: {
     connected_peers: number;
@@ -36,6 +37,13 @@ const StatusModal: React.FC<StatusModalProps> = ({ isOpen, onClose, statusData:
   const [statusData, setStatusData] = useState<StatusData | null>(initialData);
   const [isRefreshing, setIsRefreshing] = useState(false);
   const formatNumber = (num: number) => num.toLocaleString();
+  const formatEta = (seconds: number) => {
+    const hours = Math.floor(seconds / 3600);
+    const minutes = Math.floor((seconds % 3600) / 60);
+    if (hours >= 24) return `${Math.floor(hours / 24)}d ${hours % 24}h`;
+    if (hours > 0) return `${hours}h ${minutes}m`;
+    return `${minutes}m`;
+  };
 
   // Update local state when prop changes
   useEffect(() => {
@@ -162,7 +170,12 @@ const StatusModal: React.FC<StatusModalProps> = ({ isOpen, onClose, statusData:
                     </div>
                     <div className="flex justify-between items-center">
                       <span className="text-sm text-koinos-dark-400">Time Remaining:</span>
-                      <span className="font-mono text-yellow-400">{statusData.sync.time_remaining}</span>
+                      <span className="font-mono text-yellow-400">{statusData.sync.synced
+                          ? 'Synced'
+                          : statusData.sync.eta_seconds !== null
+                            ? formatEta(statusData.sync.eta_seconds)
+                            : 'Unknown'}
+                      </span>
                     </div>
                     {/* Progress Bar */}
                     <div className="mt-3">
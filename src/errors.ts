@@ -0,0 +1,20 @@
+// Errors from backend commands arrive as { code, message, hint }
+export interface BackendError {
+  code: string;
+  message: string;
+  hint: string | null;
+}
+
+export const isBackendError = (err: unknown): err is BackendError =>
+  typeof err === 'object' && err !== null && 'code' in err && 'message' in err;
+
+export const errorCode = (err: unknown): string | null =>
+  isBackendError(err) ? err.code : null;
+
+// Message followed by the remediation hint, for display
+export const errorMessage = (err: unknown): string => {
+  if (isBackendError(err)) {
+    return err.hint ? `${err.message}. ${err.hint}` : err.message;
+  }
+  return err instanceof Error ? err.message : String(err);
+};